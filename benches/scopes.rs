@@ -0,0 +1,82 @@
+// compile time and peak memory of generated programs with thousands of blueprint instances and blocks
+// cargo bench --bench scopes [-- sizes...]
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use covalent::analysis::Analyzer;
+use covalent::ir::gen::IRGen;
+use covalent::ir::Codegen;
+use covalent::parser::parse::Parse;
+use covalent::parser::Parser;
+
+// counts the bytes in use to find the peak of a compile
+struct Counting;
+
+static USED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let used = USED.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+        PEAK.fetch_max(used, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        USED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOC: Counting = Counting;
+
+// n blueprints, each called from top level code and with a few nested blocks
+fn program(n: usize) -> String {
+    let mut code = String::new();
+    for i in 0..n {
+        code.push_str(&format!(
+            "set f{i}: x {{\n\tset a = x + {i}\n\tif a > 10 {{\n\t\tset b = a * 2\n\t\twhile b > 100 {{\n\t\t\tb = b - 100\n\t\t}}\n\t\tret b\n\t}}\n\tret a\n}}\n"
+        ));
+    }
+    for i in 0..n {
+        code.push_str(&format!("set v{i} = f{i}: {i}\n"));
+    }
+    code
+}
+
+// time and peak bytes allocated while compiling code
+fn compile(code: String) -> (Duration, usize) {
+    let base = USED.load(Ordering::Relaxed);
+    PEAK.store(base, Ordering::Relaxed);
+    let start = Instant::now();
+    let mut parser = Parser::new(code);
+    let prog = parser.parse_prog();
+    let (ast, prog) =
+        Analyzer::analyz_prog(parser.ast, prog, parser.functions, ".".to_string()).unwrap();
+    Codegen::new().gen_prog(&ast, prog).unwrap();
+    (start.elapsed(), PEAK.load(Ordering::Relaxed) - base)
+}
+
+fn main() {
+    let sizes: Vec<usize> = std::env::args()
+        .skip(1)
+        .filter_map(|arg| arg.parse().ok())
+        .collect();
+    let sizes = if sizes.is_empty() {
+        vec![500, 1000, 2000, 4000]
+    } else {
+        sizes
+    };
+
+    for n in sizes {
+        let (time, peak) = compile(program(n));
+        println!(
+            "{n:>5} blueprints: {:>8.1}ms ({:.1}us per blueprint), peak {:.1}MB",
+            time.as_secs_f64() * 1000.0,
+            time.as_secs_f64() * 1e6 / n as f64,
+            peak as f64 / 1e6
+        );
+    }
+}
@@ -1,53 +1,44 @@
-use std::fs;
-
 use types::can_implicitly_convert;
 
-use crate::parser::parse::Parse;
-use crate::types::{mangle_types, type_mangle, AtomType};
+use crate::types::{conversion, demangle, type_mangle, AtomType};
 
 use crate::err;
 use crate::err::{ATErr, ErrKind};
 
-use crate::parser::ast::{Blueprint, Expr, Ident, Node};
+use crate::parser::ast::{Ast, BinOp, Blueprint, Expr, Ident, Literal, NodeId};
 
+use super::recursion::Instance;
 use super::*;
 
 impl Analyzer {
     #[inline]
-    pub fn analyz_body(&mut self, body: Vec<Node>, top: bool) -> Result<Vec<Node>, ErrKind> {
+    pub fn analyz_body(&mut self, body: Vec<NodeId>, top: bool) -> Result<Vec<NodeId>, ErrKind> {
         let mut analyzed_body = vec![];
         if !top {
             self.env.child();
         }
 
         for node in body {
-            if let &Expr::Use(ref path) = &node.expr {
-                let abs = format!("{}/{}", self.workdir.clone(), path);
-                let read = fs::read_to_string(abs)
-                    .expect(format!("failed to open path {} to use", path).as_str());
-
-                use crate::parser::Parser;
-                let mut parser = Parser::new(read);
-
-                let ast = parser.parse_prog();
-
-                self.blueprints(parser.functions)?;
-                let mut ast = self.analyz_body(ast, true)?;
-
-                analyzed_body.append(&mut ast);
+            if let Expr::Use(_) = self.ast.expr(node) {
+                self.analyz_use(node)?;
                 continue;
             }
             analyzed_body.push(self.analyz(node)?);
         }
 
         if !top {
+            for sym in self.env.locals() {
+                if sym.is_variable() && !self.ended.contains(&sym.name) {
+                    self.ended.push(sym.name);
+                }
+            }
             self.env.parent();
         }
         Ok(analyzed_body)
     }
 
     #[inline]
-    pub fn analyz_items(&mut self, items: Vec<Node>) -> Result<Vec<Node>, ErrKind> {
+    pub fn analyz_items(&mut self, items: Vec<NodeId>) -> Result<Vec<NodeId>, ErrKind> {
         let mut analyzed_items = vec![];
         for node in items {
             analyzed_items.push(self.analyz(node)?);
@@ -55,12 +46,14 @@ impl Analyzer {
         Ok(analyzed_items)
     }
 
+    // returns the ast with the analyzed nodes added and the analyzed program
     pub fn analyz_prog(
-        exprs: Vec<Node>,
+        ast: Ast,
+        exprs: Vec<NodeId>,
         functions: Vec<Blueprint>,
         workdir: String,
-    ) -> Result<Vec<Node>, ErrKind> {
-        let mut analyzer = Analyzer::new(workdir);
+    ) -> Result<(Ast, Vec<NodeId>), ErrKind> {
+        let mut analyzer = Analyzer::new(ast, workdir);
         let mut analyzed_prog = Vec::new();
 
         analyzer.import(
@@ -77,63 +70,96 @@ impl Analyzer {
             }],
         );
 
+        // the main file is a module too, above the root scope with the built-ins
+        analyzer.env.child();
         // setting our env blueprints to our uncompiled functions (blueprints are then compiled pased on call arguments)
-        analyzer.blueprints(functions)?;
-        analyzed_prog.append(&mut analyzer.analyz_body(exprs, true)?);
+        let mut body = analyzer.analyz_module(exprs, functions)?;
+        analyzer.analyz_entry(&mut body)?;
+        analyzer.resolve_body(&body)?;
 
+        // module initializers run first, the main file ends with the call to the entry point
+        analyzed_prog.append(&mut analyzer.modules.inits);
+        analyzed_prog.append(&mut body);
         analyzed_prog = [
             analyzer.imports.clone(),
             analyzer.functions.clone(),
             analyzed_prog,
         ]
         .concat();
-        Ok(analyzed_prog)
+        Ok((analyzer.ast, analyzed_prog))
     }
 
-    pub fn analyz(&mut self, node: Node) -> Result<Node, ErrKind> {
-        match node.expr.clone() {
+    // atoms and interfaces are declared before blueprints so blueprint params can be tagged with them,
+    // impls come right after, like blueprints all of them can be used before their declaration
+    pub fn analyz_decls(
+        &mut self,
+        body: Vec<NodeId>,
+        functions: Vec<Blueprint>,
+    ) -> Result<(Vec<NodeId>, Vec<NodeId>), ErrKind> {
+        let (decls, rest): (Vec<NodeId>, Vec<NodeId>) = body.into_iter().partition(|node| {
+            matches!(
+                self.ast.expr(*node),
+                Expr::Atom { .. } | Expr::Iface { .. } | Expr::Impl { .. } | Expr::Alias { .. }
+            )
+        });
+        let (impls, types): (Vec<NodeId>, Vec<NodeId>) = decls
+            .into_iter()
+            .partition(|node| matches!(self.ast.expr(*node), Expr::Impl { .. }));
+
+        let mut analyzed = self.analyz_items(types)?;
+        self.blueprints(functions)?;
+        analyzed.append(&mut self.analyz_items(impls)?);
+
+        Ok((analyzed, rest))
+    }
+
+    // analyzes a parsed node into a new typed node, the parsed node is left as it is
+    pub fn analyz(&mut self, node: NodeId) -> Result<NodeId, ErrKind> {
+        // errors are reported at the node and the nodes made for it take its position
+        let pos = self.ast.pos(node);
+        (self.line, self.column) = pos;
+        self.ast.at = pos;
+
+        let analyzed = self.analyz_expr(node)?;
+        self.ast.set_pos(analyzed, pos);
+        Ok(analyzed)
+    }
+
+    fn analyz_expr(&mut self, node: NodeId) -> Result<NodeId, ErrKind> {
+        match self.ast.expr(node).clone() {
             Expr::Literal(literal) => {
                 let ty = literal.get_ty();
-                Ok(Node {
-                    expr: Expr::Literal(literal),
-                    ty,
-                })
+                Ok(self.ast.push(Expr::Literal(literal), ty))
             }
 
             Expr::ListExpr(items) => {
                 let items = self.analyz_items(items)?;
 
-                let item_ty = if items.len() > 0 {
-                    (&items.first().unwrap()).ty.clone()
-                } else {
-                    AtomType {
-                        kind: AtomKind::Unknown,
-                        details: None,
-                    } // empty list unknown type figure out type on push
-                };
-
-                for (i, item) in (&items).iter().enumerate() {
-                    if let &AtomKind::Unknown = &item_ty.kind {
-                        break;
-                    }
-
-                    if &item.ty != &item_ty {
-                        err!(self, ErrKind::InvaildType, format!("list items have to be of the same type, item {} is of an invaild type", i-1));
-                    }
+                // empty lists item type is figured out on push
+                let mut item_ty = self.infer.fresh();
+                for (i, item) in items.iter().enumerate() {
+                    let ty = self.ast.ty(*item).clone();
+                    item_ty = match self.infer.unify(&item_ty, &ty) {
+                        Ok(ty) => ty,
+                        Err(_) => {
+                            err!(self, ErrKind::InvaildType, format!("list items have to be of the same type, item {i} is {ty} expected {item_ty}"));
+                        }
+                    };
                 }
                 let ty = AtomType {
                     kind: AtomKind::Atom(types::List.spec(&[item_ty])),
                     details: None,
                 };
                 let expr = Expr::ListExpr(items);
-                Ok(Node { expr, ty })
+                Ok(self.ast.push(expr, ty))
             }
 
-            Expr::BinaryExpr { op, left, right } => self.analyz_binary_expr(*left, *right, op),
+            Expr::BinaryExpr { op, left, right } => self.analyz_binary_expr(left, right, op),
             Expr::Ident(id) => self.analyz_id(id),
 
-            Expr::VarDeclare { name, val } => self.analyz_var_declare(name, *val),
-            Expr::VarAssign { name, val } => self.analyz_var_assign(*name, *val),
+            Expr::VarDeclare { name, val, mutable } => self.analyz_var_declare(name, val, mutable),
+            Expr::VarAssign { name, val } => self.analyz_var_assign(name, val),
+            Expr::Const { name, val, public } => self.analyz_const(name, val, public),
 
             Expr::Discard(expr) => {
                 let ty = AtomType {
@@ -141,120 +167,207 @@ impl Analyzer {
                     details: None,
                 };
 
-                let expr = self.analyz(*expr)?;
-                let expr = Expr::Discard(Box::new(expr));
+                let expr = self.analyz(expr)?;
+                let expr = Expr::Discard(expr);
 
-                Ok(Node { expr, ty })
+                Ok(self.ast.push(expr, ty))
             }
 
             Expr::PosInfo(x, line, column) => {
                 self.line = line;
                 self.column = column;
-                Ok(Node {
-                    expr: Expr::PosInfo(x, line, column),
-                    ty: AtomType {
+                Ok(self.ast.push(
+                    Expr::PosInfo(x, line, column),
+                    AtomType {
                         kind: AtomKind::Basic(BasicType::Void),
                         details: None,
                     },
-                })
+                ))
             }
 
             Expr::RetExpr(expr) => {
-                let expr = self.analyz(*expr)?;
-                let ty = expr.ty.clone();
+                let expr = self.analyz(expr)?;
+                let ty = self.ast.ty(expr).clone();
 
-                let expr = Expr::RetExpr(Box::new(expr));
-                Ok(Node { expr, ty })
+                let expr = Expr::RetExpr(expr);
+                Ok(self.ast.push(expr, ty))
             }
 
-            Expr::FnCall { name, args } => self.analyz_call(*name, args),
-            Expr::Extern { name, params } => self.analyz_extern(name, params),
+            Expr::FnCall { name, args } => self.analyz_call(name, args),
+            Expr::Extern {
+                name,
+                params,
+                public,
+            } => self.analyz_extern(name, params, public),
+
+            Expr::Atom {
+                name,
+                fields,
+                public,
+            } => self.analyz_atom(name, fields, public),
+            Expr::Iface { name, methods } => self.analyz_iface(name, methods),
+            Expr::Impl {
+                iface,
+                target,
+                methods,
+            } => self.analyz_impl(iface, target, methods),
+            Expr::Alias { name, generics, ty } => self.analyz_alias(name, generics, ty),
 
             Expr::IfExpr {
                 condition,
                 body,
                 alt,
-            } => self.analyz_if_expr(
-                *condition,
-                body,
-                match alt {
-                    Some(alt) => Some(*alt),
-                    None => None,
-                },
-            ),
+            } => self.analyz_if_expr(condition, body, alt),
 
             Expr::Block(block) => {
                 let block = self.analyz_body(block, false)?;
 
-                let last = block.last();
-                let ty = if last.is_none() {
-                    AtomType {
+                let ty = match block.last() {
+                    None => AtomType {
                         kind: AtomKind::Basic(BasicType::Void),
                         details: None,
-                    }
-                } else {
-                    last.unwrap().ty.clone()
+                    },
+                    Some(last) => self.ast.ty(*last).clone(),
                 };
                 let expr = Expr::Block(block);
 
-                Ok(Node { expr, ty })
+                Ok(self.ast.push(expr, ty))
             }
 
-            Expr::WhileExpr { condition, body } => self.analyz_while_expr(*condition, body),
+            Expr::WhileExpr { condition, body } => self.analyz_while_expr(condition, body),
+            Expr::Cast(expr, into) => self.analyz_cast(expr, into),
+            Expr::Is(expr, ty) => self.analyz_is(expr, ty),
+            Expr::TypeOf(expr) => self.analyz_typeof(expr),
 
-            Expr::MemberExpr { parent, child } => self.analyz_member(*parent, child),
-            Expr::IndexExpr { parent, index } => self.analyz_index(*parent, *index),
+            Expr::MemberExpr { parent, child } => self.analyz_member(parent, child),
+            Expr::IndexExpr { parent, index } => self.analyz_index(parent, index),
 
             Expr::SpecExpr { parent, spec } => {
-                let parent = Box::new(self.analyz(*parent)?);
-                let spec = self.analyz_items(spec)?;
+                if let Expr::Ident(id) = self.ast.expr(parent) {
+                    if let Some(alias) = self.env.get_alias(id.val()) {
+                        return self.spec_alias(alias.clone(), spec);
+                    }
+                }
+
+                let parent = self.analyz(parent)?;
+                let args = spec;
+                let mut spec = self.analyz_items(args.clone())?;
+                let parent_ty = self.ast.ty(parent).clone();
 
-                if !parent.ty.is_type() {
+                if !parent_ty.is_type() {
                     err!(
                         self,
                         ErrKind::InvaildType,
-                        format!("{} is not a type", parent.ty)
+                        format!("{} is not a type", parent_ty)
                     );
                 }
 
-                if spec.len() as i32 != parent.ty.generics() {
+                // List(T, N) is a list of N items, N is known at compile time
+                let len = match spec.len() {
+                    2 if types::is_list(&parent_ty) => Some(self.list_size(spec.pop().unwrap())?),
+                    _ => None,
+                };
+
+                if spec.len() as i32 != parent_ty.generics() {
                     err!(
                         self,
                         ErrKind::InvaildType,
                         format!(
                             "expected {} generics got {}, for type {}",
-                            parent.ty.generics(),
+                            parent_ty.generics(),
                             spec.len(),
-                            parent.ty
+                            parent_ty
                         )
                     );
                 }
 
-                let spec_types: Vec<AtomType> = spec.iter().map(|x| x.ty.clone()).collect();
+                self.check_generic_args(&parent_ty.to_string(), &args, &spec, false)?;
+                let spec_types: Vec<AtomType> =
+                    spec.iter().map(|x| self.ast.ty(*x).instance()).collect();
 
-                let ty = if let &AtomKind::Atom(ref atom) = &parent.ty.kind {
+                let ty = if let &AtomKind::Atom(ref atom) = &parent_ty.kind {
                     AtomType {
-                        kind: AtomKind::Atom(atom.spec(&spec_types)),
+                        kind: AtomKind::Atom(atom.spec(&spec_types).sized(len)),
                         details: Some(AtomDetails::Type),
                     }
                 } else {
-                    panic!("type {} is not an atom", parent.ty);
+                    panic!("type {} is not an atom", parent_ty);
                 };
 
-                Ok(Node {
-                    expr: Expr::SpecExpr { parent, spec },
-                    ty,
-                })
+                Ok(self.ast.push(Expr::SpecExpr { parent, spec }, ty))
             }
-            _ => todo!("node {:#?}", node),
+            expr => todo!("node {:#?}", expr),
         }
     }
 
+    // a main blueprint is the entry point, it is called after the top level code ran
+    // with the command line args if it takes any, its int return is the exit code
+    pub fn analyz_entry(&mut self, prog: &mut Vec<NodeId>) -> Result<(), ErrKind> {
+        let main = SymbolId::intern("main");
+        let Some(AtomKind::Blueprint(main)) = self.env.get_ty(main).map(|ty| ty.kind) else {
+            return Ok(());
+        };
+
+        for overload in &main.overloads {
+            let blueprint = self.env.get_blueprint(*overload).unwrap();
+            if blueprint.args.len() > 1 {
+                (self.line, self.column) = (blueprint.line, blueprint.column);
+                err!(
+                    self,
+                    ErrKind::UnexceptedArgs,
+                    format!(
+                        "main takes {} params, it can only take none or the command line args as one List(str) param",
+                        blueprint.args.len()
+                    )
+                );
+            }
+        }
+
+        let takes_args = main
+            .overloads
+            .iter()
+            .any(|overload| !self.env.get_blueprint(*overload).unwrap().args.is_empty());
+        let args = if takes_args {
+            let str = AtomType {
+                kind: AtomKind::Atom(types::Str.clone()),
+                details: None,
+            };
+            vec![self.ast.push(
+                Expr::Args,
+                AtomType {
+                    kind: AtomKind::Atom(types::List.spec(&[str])),
+                    details: None,
+                },
+            )]
+        } else {
+            Vec::new()
+        };
+
+        let args_types = args.iter().map(|arg| self.ast.ty(*arg).clone()).collect();
+        let call = self.handle_blueprint_call(main, args, args_types)?;
+        let ty = self.resolve(self.ast.ty(call));
+        let entry = match ty.kind {
+            AtomKind::Basic(BasicType::Int) => Expr::RetExpr(call),
+            AtomKind::Basic(BasicType::Void) => Expr::Discard(call),
+            _ => {
+                err!(
+                    self,
+                    ErrKind::InvaildType,
+                    format!("main returns {ty}, it can only return an int exit code or nothing")
+                );
+            }
+        };
+
+        prog.push(self.ast.push(entry, ty));
+        Ok(())
+    }
+
     pub fn analyz_extern(
         &mut self,
         name: Ident,
         untyped_params: Vec<Ident>,
-    ) -> Result<Node, ErrKind> {
+        public: bool,
+    ) -> Result<NodeId, ErrKind> {
         let name = self.analyz_unknown_id(name)?;
 
         let mut params: Vec<Ident> = Vec::new();
@@ -275,15 +388,20 @@ impl Analyzer {
         };
 
         self.env.add(Symbol {
-            name: name.val().clone(),
+            name: name.sym(),
             ty: ty.clone(),
             value: None,
             expected: None,
+            mutable: false,
         });
 
-        let expr = Expr::Extern { name, params };
+        let expr = Expr::Extern {
+            name,
+            params,
+            public,
+        };
 
-        Ok(Node { expr, ty })
+        Ok(self.ast.push(expr, ty))
     }
 
     pub fn analyz_blueprint(
@@ -291,172 +409,192 @@ impl Analyzer {
         blueprint: Blueprint,
         types: Vec<AtomType>,
     ) -> Result<String, ErrKind> {
-        let mangle = type_mangle(blueprint.name.val().clone(), types.clone());
-        if self.env.has(&mangle) {
-            if let AtomKind::Function(_) = self.env.get_ty(&mangle).unwrap().kind {
-                return Ok(mangle);
-            }
+        if let Some(module) = self.blueprint_module(&blueprint) {
+            return self.analyz_blueprint_in(module, blueprint, types);
+        }
+
+        let mangle = type_mangle(blueprint.name.val().to_string(), types.clone());
+        let id = SymbolId::intern(&mangle);
+        if let Some(AtomKind::Function(_)) = self.env.get_ty(id).map(|ty| ty.kind) {
+            return Ok(mangle);
         }
 
         self.env.child();
-        self.expect_as(&mangle, &blueprint.name)?;
-        // allows for the function to call itself
+        // set name@T: args tags the return type
+        let expected = match blueprint.name {
+            Ident::Tagged(..) => Some(self.analyz_unknown_id(blueprint.name.clone())?.ty().clone()),
+            _ => None,
+        };
+        // allows for the function to call itself, its return type is solved from the body
         let placeholder = FunctionType {
-            return_type: Box::new(AtomType {
-                kind: AtomKind::Unknown,
-                details: None,
-            }),
-            params: Vec::new(),
+            return_type: Box::new(expected.unwrap_or_else(|| self.infer.fresh())),
+            params: types.clone(),
         };
 
-        self.env.push_function(mangle.clone(), placeholder.clone());
+        self.env.push_function(id, placeholder.clone());
 
         let mut typed_params = Vec::new();
         for (i, arg) in (&blueprint.args).into_iter().enumerate() {
             self.env.add(Symbol {
-                name: arg.val().clone(),
+                name: arg.sym(),
                 ty: types[i].clone(),
 
                 value: None,
                 expected: None,
+                mutable: true,
             });
 
-            typed_params.push(Ident::Typed(types[i].clone(), arg.val().clone()))
+            typed_params.push(Ident::Typed(Box::new(types[i].clone()), arg.sym()))
         }
 
+        // every instance analyzes the same parsed body into nodes of its own
+        let index = self.enter_instance(&mangle);
         let body = self.analyz_body(blueprint.body, false)?;
-        let ty = get_fn_type(&body);
-
-        if !self.env.is_expected(&mangle, &ty) {
-            err!(
-                self,
-                ErrKind::InvaildType,
-                format!(
-                    "invaild return type for function {}, expected {} got {}",
-                    mangle,
-                    self.env.get(&mangle).unwrap().expected.as_ref().unwrap(),
-                    ty
-                )
-            );
-        }
-
-        // let placeholder = AtomType {
-        //     kind: AtomKind::Function(placeholder),
-        //     details: None,
-        // };
-
-        let func_type = FunctionType {
-            return_type: Box::new(ty.clone()),
-            params: types.clone(),
-        };
-
-        // replace_body_ty(
-        //     &mut body,
-        //     &placeholder,
-        //     &AtomType {
-        //         kind: AtomKind::Function(func_type.clone()),
-        //         details: None,
-        //     },
-        // );
-
+        let ret = self.get_fn_type(&mangle, &body, &placeholder.return_type)?;
         self.env.parent();
 
-        self.env.push_function(mangle.clone(), func_type.clone());
-
-        let func = Expr::Func {
-            ret: ty,
-            name: mangle.clone(),
-            args: typed_params,
+        let instance = Instance {
+            public: blueprint.public,
+            mangle: mangle.clone(),
+            params: typed_params,
+            types,
+            ret,
             body,
+            pos: (blueprint.line, blueprint.column),
         };
-
-        self.functions.push(Node {
-            ty: AtomType {
-                kind: AtomKind::Function(func_type),
-                details: None,
-            },
-            expr: func,
-        });
+        self.leave_instance(index, instance)?;
 
         Ok(mangle)
     }
 
     pub fn analyz_binary_expr(
         &mut self,
-        left: Node,
-        right: Node,
-        op: String,
-    ) -> Result<Node, ErrKind> {
+        left: NodeId,
+        right: NodeId,
+        op: BinOp,
+    ) -> Result<NodeId, ErrKind> {
         let mut lhs = self.analyz(left)?;
         let mut rhs = self.analyz(right)?;
+        let lhs_ty = self.ast.ty(lhs).clone();
 
-        if &rhs.ty.kind == &AtomKind::Unknown && &lhs.ty.kind == &AtomKind::Unknown {
-        } else if let &AtomKind::Unknown = &rhs.ty.kind {
-            rhs.ty = AtomType {
-                kind: AtomKind::Unknown,
-                details: Some(AtomDetails::Unknown(Box::new(lhs.ty.clone()))),
-            }; // assume the unknown side as the other side
-        } else if let &AtomKind::Unknown = &lhs.ty.kind {
-            lhs.ty = AtomType {
-                kind: AtomKind::Unknown,
-                details: Some(AtomDetails::Unknown(Box::new(rhs.ty.clone()))),
+        // operators on types without built-in support resolve through interfaces
+        if !supports_op(&lhs_ty, op) {
+            if let Some((iface, method)) = op_iface(op) {
+                if self.env.get_impl(iface, &lhs_ty).is_none() {
+                    err!(
+                        self,
+                        ErrKind::OperationNotGranted,
+                        format!("{lhs_ty} does not implement {iface}, required by operator {op}")
+                    );
+                }
+
+                return self.call_method(lhs, method, vec![rhs]);
+            }
+        }
+
+        // a side that isn't known yet is the same type as the other side
+        let rhs_ty = self.ast.ty(rhs).clone();
+        if lhs_ty.has_vars() || rhs_ty.has_vars() {
+            let Ok(ty) = self.infer.unify(&lhs_ty, &rhs_ty) else {
+                err!(
+                    self,
+                    ErrKind::InvaildType,
+                    format!("operator {op} cannot be applied to {lhs_ty} and {rhs_ty}")
+                );
             };
+            self.ast.set_ty(lhs, ty.clone());
+            self.ast.set_ty(rhs, ty);
         }
 
-        (lhs, rhs) = self.unify_types(lhs, rhs)?;
-        let ty = match op.as_str() {
-            "==" | ">" | "<" | ">=" | "<=" => AtomType {
+        (lhs, rhs) = self.unify_types(lhs, rhs, op)?;
+        let lhs_ty = self.ast.ty(lhs).clone();
+        let ty = match op.is_comparison() {
+            true => AtomType {
                 kind: AtomKind::Basic(BasicType::Bool),
                 details: None,
             },
-
-            _ => {
-                if let &Some(AtomDetails::Unknown(ref ty)) = &lhs.ty.details {
-                    (**ty).clone()
-                } else {
-                    lhs.ty.clone()
-                }
-            }
+            false => lhs_ty.clone(),
         };
 
-        if !supports_op(&lhs.ty, &op) {
-            err!(self,ErrKind::OperationNotGranted, format!("one of possible types [{:?}] does not support operator {}, use the do keyword to do it anyways", lhs.ty, op));
+        if !supports_op(&lhs_ty, op) {
+            err!(self,ErrKind::OperationNotGranted, format!("one of possible types [{:?}] does not support operator {}, use the do keyword to do it anyways", lhs_ty, op));
         }
-        let left = Box::new(lhs);
-        let right = Box::new(rhs);
 
-        let expr = Expr::BinaryExpr { op, left, right };
-        Ok(Node { expr, ty })
+        let expr = Expr::BinaryExpr {
+            op,
+            left: lhs,
+            right: rhs,
+        };
+        let node = self.ast.push(expr, ty);
+        self.fold(node)
     }
 
-    pub fn analyz_call(&mut self, name: Node, args: Vec<Node>) -> Result<Node, ErrKind> {
-        let name = Box::new(self.analyz(name)?);
+    pub fn analyz_call(&mut self, name: NodeId, args: Vec<NodeId>) -> Result<NodeId, ErrKind> {
+        let name = if let Expr::MemberExpr { parent, child } = *self.ast.expr(name) {
+            let parent = self.analyz(parent)?;
+            let parent_ty = self.ast.ty(parent).clone();
+            let method = child.as_str();
+
+            if types::is_list(&parent_ty) && (method == "push" || method == "pop") {
+                let args = self.analyz_items(args)?;
+                return self.list_method(parent, method, args);
+            }
+
+            // parent.method: args
+            if parent_ty.get(method).is_none()
+                && self.env.get_method_impl(&parent_ty, method).is_some()
+            {
+                let args = self.analyz_items(args)?;
+                return self.call_method(parent, method, args);
+            }
+            self.member_of(parent, child)?
+        } else {
+            self.analyz(name)?
+        };
 
         let args = self.analyz_items(args)?;
 
-        let args_types: Vec<AtomType> = args.iter().map(|arg| arg.ty.clone()).collect();
-        match name.ty.clone().kind {
+        let args_types: Vec<AtomType> = args.iter().map(|arg| self.ast.ty(*arg).clone()).collect();
+        let name_ty = self.ast.ty(name).clone();
+        match name_ty.clone().kind {
             AtomKind::Blueprint(blueprint_t) => {
                 self.handle_blueprint_call(blueprint_t, args, args_types)
             }
-            AtomKind::Function(func) => self.handle_function_call(name, func, args, args_types),
+            AtomKind::Function(func) => self.handle_function_call(name, func, args),
+            AtomKind::Atom(atom) if name_ty.is_type() && !types::is_builtin(&atom) => {
+                self.analyz_new(atom, args)
+            }
 
             _ => {
                 err!(
                     self,
                     ErrKind::UnexceptedTokenE,
-                    format!("expected symbol to call got {:?}", name.expr)
+                    format!("expected symbol to call got {:?}", self.ast.expr(name))
                 );
             }
         }
     }
 
-    fn handle_blueprint_call(
+    pub fn handle_blueprint_call(
         &mut self,
         blueprint_t: BlueprintType,
-        args: Vec<Node>,
+        args: Vec<NodeId>,
         args_types: Vec<AtomType>,
-    ) -> Result<Node, ErrKind> {
+    ) -> Result<NodeId, ErrKind> {
+        let args_types: Vec<AtomType> = args_types.iter().map(|ty| self.resolve(ty)).collect();
+        if let Some(i) = args_types.iter().position(|ty| ty.has_vars()) {
+            err!(
+                self,
+                ErrKind::InvaildType,
+                format!(
+                    "cannot infer type of argument {i} to {}, got {}",
+                    types::demangle_parts(&blueprint_t.name)
+                        .map_or(blueprint_t.name.clone(), |(name, _)| name),
+                    args_types[i]
+                )
+            );
+        }
+
         let mangle = type_mangle(blueprint_t.name.clone(), args_types.clone());
         // TODO! if its a member call pass parent as first arg and call the child instead
         // if &argc != &(args.len() as u32) {
@@ -464,21 +602,17 @@ impl Analyzer {
         //     return Err(ErrKind::UndeclaredVar);
         // }
 
-        if self.env.has(&mangle) {
-            let id_ty = self.env.get_ty(&mangle).unwrap();
-
+        let id = SymbolId::intern(&mangle);
+        if let Some(id_ty) = self.env.get_ty(id) {
             match id_ty.kind {
                 AtomKind::Function(ref func) => {
-                    return Ok(Node {
-                        expr: Expr::FnCall {
-                            name: Box::new(Node {
-                                expr: Expr::Ident(Ident::UnTagged(mangle)),
-                                ty: id_ty.clone(),
-                            }),
-                            args,
-                        },
-                        ty: *func.return_type.clone(),
-                    })
+                    self.called(&mangle);
+                    let name = self
+                        .ast
+                        .push(Expr::Ident(Ident::UnTagged(id)), id_ty.clone());
+                    return Ok(self
+                        .ast
+                        .push(Expr::FnCall { name, args }, *func.return_type.clone()));
                 }
 
                 AtomKind::Blueprint(_) => (), // continue building blueprint
@@ -487,10 +621,18 @@ impl Analyzer {
         }
 
         // building a function from blueprint
-        let blueprint = self.choose_overload(mangle, blueprint_t, args_types.clone())?;
-
-        let fun = self.analyz_blueprint(blueprint, args_types)?;
-        let ty = self.env.get_ty(&fun).unwrap(); // calling the built function
+        let (blueprint, types) = self.choose_overload(mangle, blueprint_t, args_types.clone())?;
+        let args = args
+            .into_iter()
+            .zip(&types)
+            .map(|(arg, ty)| match self.ast.ty(arg) == ty {
+                true => arg,
+                false => ty_as(&mut self.ast, ty, arg),
+            })
+            .collect();
+
+        let fun = SymbolId::intern(&self.analyz_blueprint(blueprint, types)?);
+        let ty = self.env.get_ty(fun).unwrap(); // calling the built function
 
         let ret = if let AtomKind::Function(func) = ty.clone().kind {
             *func.return_type
@@ -498,195 +640,278 @@ impl Analyzer {
             panic!()
         };
 
-        let expr = Expr::FnCall {
-            name: Box::new(Node {
-                ty: ty.clone(),
-                expr: Expr::Ident(Ident::UnTagged(fun)),
-            }),
-            args,
-        };
+        let name = self.ast.push(Expr::Ident(Ident::UnTagged(fun)), ty);
+        let expr = Expr::FnCall { name, args };
 
-        Ok(Node { expr, ty: ret })
+        Ok(self.ast.push(expr, ret))
     }
 
+    // picks the overload of blueprint_t that best matches args_types, returns it with the types to build it with
+    // every arg is ranked by how it matches its param (see ArgMatch), an overload is chosen over another one
+    // if none of its args match worse and one matches better, no single best overload is an ambiguity error
     pub fn choose_overload(
         &mut self,
         mangle: String,
         blueprint_t: BlueprintType,
         args_types: Vec<AtomType>,
-    ) -> Result<Blueprint, ErrKind> {
-        let mut blueprint = None;
-        let mut possible = Vec::new();
-
-        for overload in blueprint_t.overloads {
-            // if we got an exact overload no need to check for the best possible one to use
-            if overload == mangle {
-                blueprint = Some(self.env.get_blueprint(&overload).unwrap());
-
-                possible.clear(); // uneeded memory
-                break;
+    ) -> Result<(Blueprint, Vec<AtomType>), ErrKind> {
+        let name = types::demangle_parts(&blueprint_t.name)
+            .map_or(blueprint_t.name.clone(), |(name, _)| name);
+
+        let mut candidates: Vec<(Blueprint, Vec<ArgMatch>)> = Vec::new();
+        let mut rejected = Vec::new(); // overload => why it doesn't match
+
+        let id = SymbolId::intern(&mangle);
+        for overload in &blueprint_t.overloads {
+            let blueprint = self.env.get_blueprint(*overload).unwrap();
+            if *overload == id {
+                return Ok((blueprint, args_types));
             }
 
-            // make a list of possible overload that mangle could be from
-            let mangle = mangle_types(mangle.clone());
+            match self.match_overload(&blueprint, &args_types) {
+                Ok(matches) => candidates.push((blueprint, matches)),
+                Err(why) => rejected.push(format!("\n\t{}: {why}", demangle(overload.as_str()))),
+            }
+        }
 
-            let mut found = true;
-            for (i, ty) in mangle_types(overload.clone()).iter().enumerate() {
-                let m_ty = &mangle[i];
+        let best: Vec<&(Blueprint, Vec<ArgMatch>)> = candidates
+            .iter()
+            .filter(|(_, matches)| {
+                !candidates
+                    .iter()
+                    .any(|(_, other)| ArgMatch::better(other, matches))
+            })
+            .collect();
+
+        match best.as_slice() {
+            [(blueprint, _)] => {
+                // params with a concrete type build the function with that type, args are converted into it
+                let types = blueprint
+                    .args
+                    .iter()
+                    .zip(args_types)
+                    .map(|(param, arg)| match param.ty().kind {
+                        AtomKind::Any | AtomKind::Iface(_) => arg,
+                        _ => param.ty().clone(),
+                    })
+                    .collect();
+                Ok((blueprint.clone(), types))
+            }
 
-                if !(ty == m_ty || ty == &String::from("any")) {
-                    found = false;
-                    break;
-                }
+            [] => {
+                err!(
+                    self,
+                    ErrKind::UnexceptedArgs,
+                    format!(
+                        "no overload of {name} matches {}, overloads are:{}",
+                        demangle(&mangle),
+                        rejected.concat()
+                    )
+                );
             }
 
-            if found {
-                possible.push(overload);
+            ambiguous => {
+                let candidates: Vec<String> = ambiguous
+                    .iter()
+                    .map(|(blueprint, _)| format!("\n\t{}", demangle(blueprint.name.val())))
+                    .collect();
+                err!(
+                    self,
+                    ErrKind::UnexceptedArgs,
+                    format!(
+                        "call to {} is ambiguous, it matches these overloads equally well:{}\ntag a param or convert an arg with as to pick one",
+                        demangle(&mangle),
+                        candidates.concat()
+                    )
+                );
             }
         }
+    }
 
-        if blueprint.is_none() {
-            // choose the name with least possible any
-            let mut least_count = args_types.len(); // least possible count where all types are any;
-
-            let mut choosen = String::new();
-            for name in possible {
-                let types = mangle_types(name.clone());
-                let mut count = 0;
-
-                types.iter().for_each(|ty| {
-                    if ty == "any" {
-                        count += 1
-                    }
-                });
+    // how each arg matches its param in blueprint, or why the blueprint can't take args_types
+    fn match_overload(
+        &self,
+        blueprint: &Blueprint,
+        args_types: &[AtomType],
+    ) -> Result<Vec<ArgMatch>, String> {
+        let params = &blueprint.args;
+        if params.len() != args_types.len() {
+            return Err(format!(
+                "takes {} args, got {}",
+                params.len(),
+                args_types.len()
+            ));
+        }
 
-                if count <= least_count {
-                    least_count = count;
-                    choosen = name;
+        let mut matches = Vec::new();
+        for (param, arg) in params.iter().zip(args_types) {
+            let ty = param.ty();
+            let rank = match &ty.kind {
+                AtomKind::Any => ArgMatch::Any,
+                AtomKind::Iface(_) if self.accepts(ty, arg) => ArgMatch::Iface,
+                AtomKind::Iface(iface) => {
+                    return Err(format!(
+                        "{arg} does not implement {iface}, required by param {}",
+                        param.val()
+                    ))
                 }
-            }
-            blueprint = Some(self.env.get_blueprint(&choosen).unwrap());
+                _ if ty == arg => ArgMatch::Exact,
+                _ if assignable(ty, arg) => ArgMatch::Implicit,
+                _ => return Err(format!("param {} takes {ty}, got {arg}", param.val())),
+            };
+            matches.push(rank);
         }
-
-        Ok(blueprint.unwrap())
+        Ok(matches)
     }
 
     pub fn handle_function_call(
         &mut self,
-        name: Box<Node>,
+        name: NodeId,
         func: FunctionType,
-        mut args: Vec<Node>,
-        args_types: Vec<AtomType>,
-    ) -> Result<Node, ErrKind> {
+        mut args: Vec<NodeId>,
+    ) -> Result<NodeId, ErrKind> {
         if func.params.len() != args.len() {
-            err!(self, ErrKind::UndeclaredVar, format!("not enough arguments got {} arguments, expected {} arguments for function {:?}", args.len(), args_types.len(), name));
+            let of = match self.ast.expr(name) {
+                Expr::Ident(id) => id.val().to_string(),
+                _ => format!("of type {}", self.ast.ty(name)),
+            };
+            err!(
+                self,
+                ErrKind::UnexceptedArgs,
+                format!(
+                    "expected {} arguments got {}, for function {of}",
+                    func.params.len(),
+                    args.len()
+                )
+            );
         }
 
-        for (i, arg) in (&mut args).iter_mut().enumerate() {
-            if &arg.ty != &func.params[i] {
-                if can_implicitly_convert(&arg.ty.kind, &func.params[i].kind) {
-                    *arg = self.type_cast(arg.clone(), func.params[i].clone()).unwrap();
+        for (i, arg) in args.iter_mut().enumerate() {
+            let arg_ty = self.ast.ty(*arg).clone();
+            if self.solves(&func.params[i], &arg_ty) {
+                self.unify(&func.params[i], &arg_ty)?;
+            } else if arg_ty != func.params[i] {
+                if can_implicitly_convert(&arg_ty.kind, &func.params[i].kind) {
+                    *arg = self.type_cast(*arg, func.params[i].clone()).unwrap();
                 } else {
                     err!(
                         self,
                         ErrKind::UnexceptedArgs,
                         format!(
                             "unexpected argument type, at arg {}, expected {}, got {}",
-                            i, func.params[i], arg.ty
+                            i, func.params[i], arg_ty
                         )
                     );
                 }
             }
         }
 
+        let ty = self.resolve(&func.return_type);
         let expr = Expr::FnCall { name, args };
 
-        Ok(Node {
-            expr,
-            ty: *func.return_type,
-        })
+        Ok(self.ast.push(expr, ty))
     }
 
-    pub fn analyz_index(&mut self, parent: Node, index: Node) -> Result<Node, ErrKind> {
+    pub fn analyz_index(&mut self, parent: NodeId, index: NodeId) -> Result<NodeId, ErrKind> {
         let parent = self.analyz(parent)?;
         let index = self.analyz(index)?;
+        let parent_ty = self.ast.ty(parent).clone();
 
-        if index.ty.kind != AtomKind::Basic(BasicType::Int) {
+        if self.env.get_impl("Index", &parent_ty).is_some() {
+            return self.call_method(parent, "index", vec![index]);
+        }
+
+        let int = AtomType {
+            kind: AtomKind::Basic(BasicType::Int),
+            details: None,
+        };
+        let index_ty = self.ast.ty(index).clone();
+        if index_ty.has_vars() {
+            self.unify(&index_ty, &int)?;
+        } else if index_ty != int {
             err!(self, ErrKind::InvaildType, format!("index is not an int"));
         }
 
-        let ty = match parent.ty.clone().kind {
-            // until i add interfaces(traits) i have to do it manually
-            AtomKind::Atom(ref atom) if atom.name == types::Str.name => parent.ty.clone(), // str indexs into str not char for now
-            AtomKind::Atom(ref atom) if atom.name == types::List.name => atom.generics[0].clone(),
+        let ty = match parent_ty.kind {
+            AtomKind::Atom(ref atom) if atom.name == types::Str.name => parent_ty.clone(), // str indexs into str not char for now
+            AtomKind::Atom(ref atom) if atom.name == types::List.name => {
+                // indexes known at compile time are checked against the size of a List(T, N)
+                if let (Some(len), Expr::Literal(Literal::Int(idx))) =
+                    (atom.len, self.ast.expr(index))
+                {
+                    let idx = *idx;
+                    if idx < 0 || idx as u32 >= len {
+                        err!(
+                            self,
+                            ErrKind::InvaildType,
+                            format!("index {idx} is out of bounds for {parent_ty}")
+                        );
+                    }
+                }
+                atom.generics[0].clone()
+            }
             _ => {
                 err!(
                     self,
                     ErrKind::InvaildType,
-                    format!("cannot index {:?}", parent.ty)
+                    format!("cannot index {}, it does not implement Index", parent_ty)
                 );
             }
         };
 
-        let expr = Expr::IndexExpr {
-            parent: Box::new(parent),
-            index: Box::new(index),
-        };
-        Ok(Node { expr, ty })
+        let expr = Expr::IndexExpr { parent, index };
+        Ok(self.ast.push(expr, ty))
     }
 
-    pub fn analyz_member(&mut self, parent: Node, child: String) -> Result<Node, ErrKind> {
+    pub fn analyz_member(&mut self, parent: NodeId, child: SymbolId) -> Result<NodeId, ErrKind> {
         let parent = self.analyz(parent)?;
+        self.member_of(parent, child)
+    }
 
-        let ty = parent.ty.get(&child);
-        let ty = if ty.is_none() {
-            let func = self.env.ty_parent_fn(&parent.ty, &child);
-            if func.is_none() {
-                return Err(ErrKind::UndeclaredVar);
-            }
-
-            func.unwrap()
-        } else {
-            ty.unwrap().clone()
+    // member expr of an already analyzed parent
+    pub fn member_of(&mut self, parent: NodeId, child: SymbolId) -> Result<NodeId, ErrKind> {
+        let parent_ty = self.ast.ty(parent);
+        let ty = match parent_ty.get(child.as_str()) {
+            Some(ty) => ty.clone(),
+            None => match self.env.ty_parent_fn(parent_ty, child) {
+                Some(func) => func,
+                None => {
+                    err!(
+                        self,
+                        ErrKind::UndeclaredVar,
+                        format!("{parent_ty} has no field {child}")
+                    );
+                }
+            },
         };
 
-        let expr = Expr::MemberExpr {
-            parent: Box::new(parent),
-            child,
-        };
+        let expr = Expr::MemberExpr { parent, child };
 
-        Ok(Node { ty, expr })
+        Ok(self.ast.push(expr, ty))
     }
 
     pub fn analyz_unknown_id(&mut self, id: Ident) -> Result<Ident, ErrKind> {
-        match &id {
-            &Ident::Tagged(ref tag, ref id) => {
-                let tag = self.analyz(*tag.clone())?;
-                let expr = tag.expr;
-                let tag = tag.ty;
+        match id {
+            Ident::Tagged(tag, id) => {
+                let tag = self.analyz(tag)?;
+                let ty = self.ast.ty(tag);
 
                 // if tag has type details then it is a type, return tag type without the type details
-                if tag.is_type() {
-                    return Ok(Ident::Typed(
-                        AtomType {
-                            kind: tag.kind,
-                            details: None,
-                        },
-                        id.clone(),
-                    ));
+                if ty.is_type() {
+                    Ok(Ident::Typed(Box::new(ty.instance()), id))
                 } else {
                     err!(
                         self,
                         ErrKind::InvaildType,
-                        format!("{:?} is not an Atom", expr)
+                        format!("{:?} is not an Atom", self.ast.expr(tag))
                     );
                 }
             }
-            &Ident::Typed(_, _) | &Ident::UnTagged(_) => Ok(id),
+            Ident::Typed(_, _) | Ident::UnTagged(_) => Ok(id),
         }
     }
 
-    pub fn analyz_id(&mut self, id: Ident) -> Result<Node, ErrKind> {
+    pub fn analyz_id(&mut self, id: Ident) -> Result<NodeId, ErrKind> {
         if let &Ident::Tagged(_, _) = &id {
             err!(
                 self,
@@ -698,142 +923,273 @@ impl Analyzer {
             );
         }
 
-        if !self.env.has(&id.val()) {
-            dbg!(id);
-            return Err(ErrKind::UndeclaredVar);
+        let (name, sym) = (id.val(), id.sym());
+        if !self.env.has(sym) {
+            let msg = if self.ended.contains(&sym) {
+                format!(
+                    "{name} is used outside of the block it was declared in, declare it before the block to use it here"
+                )
+            } else if let Some(module) = self.modules.private_to(name) {
+                format!("{name} is private to module {module}, declare it with pub to use it here")
+            } else {
+                format!("{name} is not declared")
+            };
+            err!(self, ErrKind::UndeclaredVar, msg);
+        }
+
+        // blueprints are built where they are called, but only see their own variables like the C functions they become
+        if let Some(base) = self.instance_depth() {
+            let depth = self.env.depth() - self.env.scope_of(sym).unwrap();
+            if self.env.get(sym).unwrap().is_variable() && depth < base {
+                err!(
+                    self,
+                    ErrKind::UndeclaredVar,
+                    format!(
+                        "{name} is declared outside of the blueprint using it, pass it as an arg or make it a const"
+                    )
+                );
+            }
+        }
+
+        if let Some(alias) = self.env.get_alias(name) {
+            err!(
+                self,
+                ErrKind::InvaildType,
+                format!(
+                    "type {} takes {} generics, use {}({})",
+                    alias.name,
+                    alias.generics.len(),
+                    alias.name,
+                    alias.generics.join(", ")
+                )
+            );
+        }
+
+        let sym = self.env.get(sym).unwrap();
+        if let Some(value) = &sym.value {
+            return Ok(self.ast.push(Expr::Literal(value.clone()), sym.ty.clone()));
         }
 
-        let ty = self.env.get_ty(&id.val()).unwrap();
+        let ty = self.resolve(&sym.ty.clone());
 
         let expr = Expr::Ident(id);
-        Ok(Node { expr, ty })
+        Ok(self.ast.push(expr, ty))
     }
 
-    pub fn analyz_var_declare(&mut self, name: Ident, val: Node) -> Result<Node, ErrKind> {
-        let val = self.analyz(val)?;
-
-        if self.env.has(&name.val()) {
-            return Err(ErrKind::VarAlreadyDeclared);
+    pub fn analyz_var_declare(
+        &mut self,
+        name: Ident,
+        val: NodeId,
+        mutable: bool,
+    ) -> Result<NodeId, ErrKind> {
+        let mut val = self.analyz(val)?;
+
+        // variables can be shadowed in inner blocks, everything else can't be
+        if let Some(sym) = self.env.get(name.sym()) {
+            if self.env.local(name.sym()).is_some() || !sym.is_variable() {
+                err!(
+                    self,
+                    ErrKind::VarAlreadyDeclared,
+                    format!("{} is already declared", name.val())
+                );
+            }
         }
         self.env.add(Symbol {
-            name: name.val().clone(),
+            name: name.sym(),
             ty: AtomType {
                 kind: AtomKind::Unknown,
                 details: None,
             },
             value: None,
             expected: None,
+            mutable,
         });
 
         self.expect(&name)?;
-        let ty = val.ty.clone();
+        // [] declared as List(int) is a List(int), ids declared with an alias keep its name
+        let expected = self.env.get(name.sym()).unwrap().expected.clone();
+        if let Some(ref expected) = expected {
+            self.fit_list(val, expected)?;
+        }
 
-        if !self.env.is_expected(&name.val(), &ty) {
+        let mut ty = self.ast.ty(val).clone();
+        if let Some(ref expected) = expected {
+            if self.solves(expected, &ty) {
+                ty = self.unify(expected, &ty)?;
+            }
+            if ty != *expected && assignable(expected, &ty) {
+                val = ty_as(&mut self.ast, expected, val);
+                ty = expected.clone();
+            }
+            if ty == *expected {
+                ty.details = expected.details.clone();
+            }
+        }
+
+        if !self.env.is_expected(name.sym(), &ty) {
             err!(
                 self,
                 ErrKind::InvaildType,
                 format!(
                     "unexpected type {ty}, for id {}, expected {}",
                     name.val(),
-                    self.env.get(name.val()).unwrap().expected.as_ref().unwrap()
+                    self.env.get(name.sym()).unwrap().expected.as_ref().unwrap()
                 )
             );
         }
-        self.env.modify(
-            name.val(),
-            Symbol {
-                name: name.val().clone(),
-                ty: ty.clone(),
-                value: None,
-                expected: None,
-            },
-        );
+        self.env.modify(Symbol {
+            name: name.sym(),
+            ty: ty.clone(),
+            value: None,
+            expected,
+            mutable,
+        });
 
-        let expr = Expr::VarDeclare {
-            name,
-            val: Box::new(val),
-        };
-        Ok(Node { expr, ty })
+        let expr = Expr::VarDeclare { name, val, mutable };
+        Ok(self.ast.push(expr, ty))
     }
 
-    pub fn analyz_var_assign(&mut self, id: Node, val: Node) -> Result<Node, ErrKind> {
-        let val = self.analyz(val)?;
+    pub fn analyz_var_assign(&mut self, id: NodeId, val: NodeId) -> Result<NodeId, ErrKind> {
+        if let Expr::Ident(id) = self.ast.expr(id) {
+            if self
+                .env
+                .get(id.sym())
+                .is_some_and(|sym| sym.value.is_some())
+            {
+                err!(
+                    self,
+                    ErrKind::ImmutableAssign,
+                    format!(
+                        "cannot assign to const {}, it is evaluated at compile time",
+                        id.val()
+                    )
+                );
+            }
+        }
+
+        let mut val = self.analyz(val)?;
         let name = self.analyz(id)?;
-        let mut ty = val.ty.clone();
+        let name_ty = self.ast.ty(name).clone();
+        self.fit_list(val, &name_ty)?;
+        let val_ty = self.ast.ty(val).clone();
+        let mut ty = val_ty.clone();
+
+        if let Expr::Ident(id) = self.ast.expr(name) {
+            let sym = self.env.get(id.sym()).unwrap();
+            if !sym.mutable {
+                let what = match &sym.ty.kind {
+                    _ if sym.ty.is_type() => "type",
+                    AtomKind::Blueprint(_) => "blueprint",
+                    AtomKind::Function(_) => "function",
+                    _ => "part",
+                };
+                err!(
+                    self,
+                    ErrKind::ImmutableAssign,
+                    format!(
+                        "cannot assign to {what} {}, it is immutable{}",
+                        id.val(),
+                        if what == "part" {
+                            ", declare it with set or state to reassign it"
+                        } else {
+                            ""
+                        }
+                    )
+                );
+            }
+        }
 
-        if let Expr::Ident(ref name) = name.expr {
-            self.env.modify_ty(&name.val(), ty.clone());
-        } else if val.ty != name.ty {
-            if name.ty.kind == AtomKind::Unknown {
-                ty = name.ty.clone();
-            } else {
-                err!(self, ErrKind::InvaildType, format!("cannot set the value of an Obj property to a value of different type, got type {} expected {}, in expr {:?} = {:?}", val.ty, name.ty, name, val));
+        // variables keep the type they were declared with, their tag or the type of their first value
+        if self.solves(&name_ty, &val_ty) {
+            ty = self.unify(&name_ty, &val_ty)?;
+        } else if assignable(&name_ty, &val_ty) {
+            if val_ty != name_ty {
+                val = ty_as(&mut self.ast, &name_ty, val);
+                ty = name_ty.clone();
             }
+        } else if let Expr::Ident(id) = self.ast.expr(name) {
+            err!(
+                self,
+                ErrKind::InvaildType,
+                format!(
+                    "cannot assign {} to {}, it was declared as {}, declare a new variable or tag it @Dynamic to hold any value",
+                    val_ty,
+                    id.val(),
+                    name_ty
+                )
+            );
+        } else {
+            err!(
+                self,
+                ErrKind::InvaildType,
+                format!("cannot assign {} to a property of type {}", val_ty, name_ty)
+            );
         }
 
-        let expr = Expr::VarAssign {
-            name: Box::new(name),
-            val: Box::new(val),
-        };
-        Ok(Node { expr, ty })
+        let expr = Expr::VarAssign { name, val };
+        Ok(self.ast.push(expr, ty))
     }
 
     pub fn analyz_if_expr(
         &mut self,
-        condition: Node,
-        body: Vec<Node>,
-        alt: Option<Node>,
-    ) -> Result<Node, ErrKind> {
-        let condition = Box::new(self.analyz(condition)?);
-
-        if condition.ty.kind != AtomKind::Basic(BasicType::Bool) {
+        condition: NodeId,
+        body: Vec<NodeId>,
+        alt: Option<NodeId>,
+    ) -> Result<NodeId, ErrKind> {
+        let condition = self.analyz(condition)?;
+        self.expect_bool(condition)?;
+
+        let condition_ty = self.ast.ty(condition);
+        if condition_ty.kind != AtomKind::Basic(BasicType::Bool) {
             err!(
                 self,
                 ErrKind::InvaildType,
                 format!(
                     "invaild condition for while loop expected Bool got {:?}",
-                    condition.ty
+                    condition_ty
                 )
             );
         }
         let body = self.analyz_body(body, false)?;
 
-        let analyzed_alt = if alt.is_none() {
-            None
-        } else {
-            Some(Box::new(self.analyz(alt.unwrap())?))
+        let alt = match alt {
+            Some(alt) => Some(self.analyz(alt)?),
+            None => None,
         };
 
-        let last = body.last();
-
-        let ty = if last.is_none() {
-            AtomType {
+        let ty = match body.last() {
+            None => AtomType {
                 kind: AtomKind::Basic(BasicType::Void),
                 details: None,
-            }
-        } else {
-            last.unwrap().ty.clone()
+            },
+            Some(last) => self.ast.ty(*last).clone(),
         };
 
         let expr = Expr::IfExpr {
             condition,
             body,
-            alt: analyzed_alt,
+            alt,
         };
 
-        Ok(Node { expr, ty })
+        Ok(self.ast.push(expr, ty))
     }
 
-    pub fn analyz_while_expr(&mut self, condition: Node, body: Vec<Node>) -> Result<Node, ErrKind> {
-        let condition = Box::new(self.analyz(condition)?);
-
-        if condition.ty.kind != AtomKind::Basic(BasicType::Bool) {
+    pub fn analyz_while_expr(
+        &mut self,
+        condition: NodeId,
+        body: Vec<NodeId>,
+    ) -> Result<NodeId, ErrKind> {
+        let condition = self.analyz(condition)?;
+        self.expect_bool(condition)?;
+
+        let condition_ty = self.ast.ty(condition);
+        if condition_ty.kind != AtomKind::Basic(BasicType::Bool) {
             err!(
                 self,
                 ErrKind::InvaildType,
                 format!(
                     "invaild condition for while loop expected Bool got {:?}",
-                    condition.ty
+                    condition_ty
                 )
             );
         }
@@ -845,47 +1201,213 @@ impl Analyzer {
             details: None,
         };
 
-        Ok(Node { expr, ty })
+        Ok(self.ast.push(expr, ty))
     }
 
-    pub fn unify_types(&mut self, left: Node, right: Node) -> Result<(Node, Node), ErrKind> {
-        // skip unifying if one of the types is unknown and is assumed to the other
-        if left.ty.details == Some(AtomDetails::Unknown(Box::new(right.ty.clone())))
-            || right.ty.details == Some(AtomDetails::Unknown(Box::new(left.ty.clone())))
-        {
-            return Ok((left, right));
+    // conditions that aren't known yet are bools
+    fn expect_bool(&mut self, condition: NodeId) -> Result<(), ErrKind> {
+        let ty = self.ast.ty(condition).clone();
+        if ty.has_vars() {
+            self.unify(
+                &ty,
+                &AtomType {
+                    kind: AtomKind::Basic(BasicType::Bool),
+                    details: None,
+                },
+            )?;
         }
+        Ok(())
+    }
+
+    // list.push: item and list.pop!, the list item type is solved from pushed items
+    pub fn list_method(
+        &mut self,
+        parent: NodeId,
+        method: &str,
+        mut args: Vec<NodeId>,
+    ) -> Result<NodeId, ErrKind> {
+        let parent_ty = self.ast.ty(parent).clone();
+        let item_ty = match &parent_ty.kind {
+            AtomKind::Atom(list) if list.len.is_some() => {
+                err!(
+                    self,
+                    ErrKind::InvaildType,
+                    format!("cannot {method} {parent_ty}, its size is fixed")
+                );
+            }
+            AtomKind::Atom(list) => list.generics[0].clone(),
+            _ => unreachable!(),
+        };
+
+        let expected = if method == "push" { 1 } else { 0 };
+        if args.len() != expected {
+            err!(
+                self,
+                ErrKind::UnexceptedArgs,
+                format!("{method} takes {expected} arguments got {}", args.len())
+            );
+        }
+
+        let dynamic = AtomType {
+            kind: AtomKind::Dynamic,
+            details: None,
+        };
+
+        let mut params = vec![parent_ty.clone()];
+        if let Some(item) = args.pop() {
+            let pushed = self.ast.ty(item).clone();
+            // List(Dynamic) boxes whatever is pushed into it
+            if self.infer.unify(&item_ty, &pushed).is_err()
+                && self.resolve(&item_ty).kind != AtomKind::Dynamic
+            {
+                err!(
+                    self,
+                    ErrKind::InvaildType,
+                    format!("cannot push {} into {}", pushed, self.resolve(&parent_ty))
+                );
+            }
+            if self.resolve(&pushed).kind == AtomKind::Dynamic {
+                args.push(item);
+            } else {
+                args.push(ty_as(&mut self.ast, &dynamic, item));
+            }
+            params.push(dynamic);
+        }
+
+        let func = AtomType {
+            kind: AtomKind::Function(FunctionType {
+                params,
+                return_type: Box::new(parent_ty.clone()),
+            }),
+            details: None,
+        };
+
+        let method = SymbolId::intern(&types::method_name(&parent_ty, method));
+        let name = self.ast.push(Expr::Ident(Ident::UnTagged(method)), func);
+
+        Ok(self.ast.push(
+            Expr::FnCall {
+                name,
+                args: [vec![parent], args].concat(),
+            },
+            parent_ty,
+        ))
+    }
 
-        if left.ty == right.ty {
+    pub fn unify_types(
+        &mut self,
+        left: NodeId,
+        right: NodeId,
+        op: BinOp,
+    ) -> Result<(NodeId, NodeId), ErrKind> {
+        let left_ty = self.ast.ty(left).clone();
+        let right_ty = self.ast.ty(right).clone();
+        if left_ty == right_ty {
             return Ok((left, right));
         }
 
-        if can_implicitly_convert(&left.ty.kind, &right.ty.kind) {
-            let converted_left = self.type_cast(left, right.ty.clone())?;
+        if can_implicitly_convert(&left_ty.kind, &right_ty.kind) {
+            let converted_left = self.type_cast(left, right_ty)?;
             return Ok((converted_left, right));
         }
 
-        if can_implicitly_convert(&right.ty.kind, &left.ty.kind) {
-            let converted_right = self.type_cast(right, left.ty.clone())?;
+        if can_implicitly_convert(&right_ty.kind, &left_ty.kind) {
+            let converted_right = self.type_cast(right, left_ty)?;
             return Ok((left, converted_right));
         }
 
         err!(
             self,
             ErrKind::InvaildType,
-            format!("cannot unify types {} and {}", left.ty, right.ty)
+            format!("operator {op} cannot be applied to {left_ty} and {right_ty}")
         );
     }
 
-    pub fn type_cast(&mut self, from: Node, into: AtomType) -> Result<Node, ErrKind> {
-        if !can_implicitly_convert(&from.ty.kind, &into.kind) {
+    pub fn type_cast(&mut self, from: NodeId, into: AtomType) -> Result<NodeId, ErrKind> {
+        let from_ty = self.ast.ty(from);
+        if !can_implicitly_convert(&from_ty.kind, &into.kind) {
+            err!(
+                self,
+                ErrKind::InvaildType,
+                format!("cannot convert from {} into {}", from_ty, into)
+            );
+        }
+
+        Ok(ty_as(&mut self.ast, &into, from))
+    }
+
+    pub fn analyz_cast(&mut self, expr: NodeId, into: NodeId) -> Result<NodeId, ErrKind> {
+        let expr = self.analyz(expr)?;
+        let into = self.analyz(into)?;
+        let into_ty = self.ast.ty(into);
+
+        if !into_ty.is_type() {
+            err!(
+                self,
+                ErrKind::InvaildType,
+                format!("cannot cast into {}, it is not a type", into_ty)
+            );
+        }
+
+        let into = into_ty.instance();
+        let expr_ty = self.ast.ty(expr);
+
+        if *expr_ty == into {
+            return Ok(expr);
+        }
+
+        if conversion(&expr_ty.kind, &into.kind).is_none() {
             err!(
                 self,
                 ErrKind::InvaildType,
-                format!("cannot convert from {} into {}", from.ty, into)
+                format!("cannot convert from {} into {}", expr_ty, into)
             );
         }
 
-        Ok(ty_as(&into, from))
+        Ok(ty_as(&mut self.ast, &into, expr))
+    }
+
+    // expr is T, only checked at runtime for Dynamic values, everything else is known at compile time
+    pub fn analyz_is(&mut self, expr: NodeId, ty: NodeId) -> Result<NodeId, ErrKind> {
+        let expr = self.analyz(expr)?;
+        let ty = self.analyz(ty)?;
+        let expr_ty = self.ast.ty(expr);
+        let is_ty = self.ast.ty(ty);
+
+        if !is_ty.is_type() {
+            err!(
+                self,
+                ErrKind::InvaildType,
+                format!("cannot check if {} is {}, it is not a type", expr_ty, is_ty)
+            );
+        }
+
+        let bool = AtomType {
+            kind: AtomKind::Basic(BasicType::Bool),
+            details: None,
+        };
+        if expr_ty.kind != AtomKind::Dynamic {
+            let is = expr_ty.kind == is_ty.kind;
+            return Ok(self.ast.push(Expr::Literal(Literal::Bool(is)), bool));
+        }
+
+        Ok(self.ast.push(Expr::Is(expr, ty), bool))
+    }
+
+    // typeof expr, the name of the type of expr, the type held for Dynamic values
+    pub fn analyz_typeof(&mut self, expr: NodeId) -> Result<NodeId, ErrKind> {
+        let expr = self.analyz(expr)?;
+        let expr_ty = self.ast.ty(expr);
+        let str = AtomType {
+            kind: AtomKind::Atom(types::Str.clone()),
+            details: None,
+        };
+
+        if expr_ty.kind != AtomKind::Dynamic {
+            let name = expr_ty.kind.to_string();
+            return Ok(self.ast.push(Expr::Literal(Literal::Str(name)), str));
+        }
+
+        Ok(self.ast.push(Expr::TypeOf(expr), str))
     }
 }
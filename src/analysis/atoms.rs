@@ -0,0 +1,525 @@
+// user atoms, interfaces and their implementations
+use indexmap::IndexMap;
+
+use crate::enviroment::{Alias, Impl};
+use crate::err;
+use crate::err::{ATErr, ErrKind};
+use crate::parser::ast::{Ast, Blueprint, Expr, Ident, Literal, NodeId};
+use crate::types::{can_implicitly_convert, method_name, Atom, Iface};
+
+use super::*;
+
+impl Analyzer {
+    fn declare_type(&mut self, name: SymbolId, ty: AtomType) -> Result<(), ErrKind> {
+        if self.env.has(name) {
+            err!(
+                self,
+                ErrKind::VarAlreadyDeclared,
+                format!("type {name} is already declared")
+            );
+        }
+
+        self.env.add(Symbol {
+            name,
+            ty,
+            value: None,
+            expected: None,
+            mutable: false,
+        });
+        Ok(())
+    }
+
+    pub fn analyz_atom(
+        &mut self,
+        name: SymbolId,
+        fields: Vec<Ident>,
+        public: bool,
+    ) -> Result<NodeId, ErrKind> {
+        let mut typed_fields = Vec::new();
+        let mut field_types = IndexMap::new();
+
+        for field in fields {
+            let field = self.analyz_unknown_id(field)?;
+            if field_types
+                .insert(field.val().to_string(), field.ty().clone())
+                .is_some()
+            {
+                err!(
+                    self,
+                    ErrKind::VarAlreadyDeclared,
+                    format!("field {} of atom {name} is declared twice", field.val())
+                );
+            }
+            typed_fields.push(field);
+        }
+
+        let atom = Atom::new(name.to_string(), field_types, IndexMap::new());
+        self.declare_type(
+            name,
+            AtomType {
+                kind: AtomKind::Atom(atom),
+                details: Some(AtomDetails::Type),
+            },
+        )?;
+
+        Ok(self.ast.push(
+            Expr::Atom {
+                name,
+                fields: typed_fields,
+                public,
+            },
+            AtomType {
+                kind: AtomKind::Basic(BasicType::Void),
+                details: None,
+            },
+        ))
+    }
+
+    pub fn analyz_iface(
+        &mut self,
+        name: SymbolId,
+        methods: Vec<Blueprint>,
+    ) -> Result<NodeId, ErrKind> {
+        let mut signatures = IndexMap::new();
+
+        for method in &methods {
+            let mut params = Vec::new();
+            for param in method.args.clone() {
+                params.push(self.analyz_unknown_id(param)?.ty().clone());
+            }
+
+            let return_type = match self.analyz_unknown_id(method.name.clone())? {
+                Ident::Typed(ty, _) => *ty,
+                _ => AtomType {
+                    kind: AtomKind::Any,
+                    details: None,
+                },
+            };
+
+            signatures.insert(
+                method.name.val().to_string(),
+                FunctionType {
+                    params,
+                    return_type: Box::new(return_type),
+                },
+            );
+        }
+
+        let iface = Iface {
+            name: name.to_string(),
+            methods: signatures,
+        };
+        self.declare_type(
+            name,
+            AtomType {
+                kind: AtomKind::Iface(iface),
+                details: Some(AtomDetails::Type),
+            },
+        )?;
+
+        Ok(self.ast.push(
+            Expr::Iface { name, methods },
+            AtomType {
+                kind: AtomKind::Basic(BasicType::Void),
+                details: None,
+            },
+        ))
+    }
+
+    pub fn analyz_alias(
+        &mut self,
+        name: SymbolId,
+        generics: Vec<SymbolId>,
+        ty: NodeId,
+    ) -> Result<NodeId, ErrKind> {
+        let void = AtomType {
+            kind: AtomKind::Basic(BasicType::Void),
+            details: None,
+        };
+
+        // generic aliases are checked on use, once their generics are known
+        if !generics.is_empty() {
+            self.declare_type(
+                name,
+                AtomType {
+                    kind: AtomKind::Unknown,
+                    details: Some(AtomDetails::AliasType(name.to_string())),
+                },
+            )?;
+            self.env.add_alias(Alias {
+                name: name.to_string(),
+                generics: generics.iter().map(|generic| generic.to_string()).collect(),
+                ty,
+            });
+
+            return Ok(self.ast.push(Expr::Alias { name, generics, ty }, void));
+        }
+
+        let ty = self.analyz(ty)?;
+        let aliased = self.ast.ty(ty).clone();
+        if !aliased.is_type() {
+            err!(
+                self,
+                ErrKind::InvaildType,
+                format!("cannot alias {aliased} as {name}, it is not a type")
+            );
+        }
+
+        self.declare_type(
+            name,
+            AtomType {
+                kind: aliased.kind,
+                details: Some(AtomDetails::AliasType(name.to_string())),
+            },
+        )?;
+
+        Ok(self.ast.push(Expr::Alias { name, generics, ty }, void))
+    }
+
+    // generic args are types, aliases also take int consts, a const is replaced by its value
+    // when analyzed so args are the parsed nodes to name it
+    pub fn check_generic_args(
+        &mut self,
+        of: &str,
+        args: &[NodeId],
+        spec: &[NodeId],
+        consts: bool,
+    ) -> Result<(), ErrKind> {
+        for (arg, spec) in args.iter().zip(spec) {
+            let ty = self.ast.ty(*spec);
+            if ty.is_type() || (consts && int_const(&self.ast, *spec).is_some()) {
+                continue;
+            }
+
+            let arg = match self.ast.expr(*arg) {
+                Expr::Ident(id)
+                    if self
+                        .env
+                        .get(id.sym())
+                        .is_some_and(|sym| sym.value.is_some()) =>
+                {
+                    format!("const {}", id.val())
+                }
+                _ => format!("a value of type {ty}"),
+            };
+            err!(
+                self,
+                ErrKind::InvaildType,
+                format!(
+                    "cannot use {arg} as a generic arg of {of}, generic args are types{}",
+                    if consts { " or int consts" } else { "" }
+                )
+            );
+        }
+        Ok(())
+    }
+
+    // the N of List(T, N), an int known at compile time
+    pub fn list_size(&mut self, node: NodeId) -> Result<u32, ErrKind> {
+        let size = match consteval::eval(&self.ast, node) {
+            Ok(Literal::Int(size)) => size,
+            Ok(_) => {
+                let ty = self.ast.ty(node).clone();
+                err!(
+                    self,
+                    ErrKind::InvaildType,
+                    format!("the size of List(T, N) is an int, got {ty}")
+                );
+            }
+            Err(_) => {
+                err!(
+                    self,
+                    ErrKind::InvaildType,
+                    "the size of List(T, N) has to be known at compile time, use a literal or a const"
+                );
+            }
+        };
+        if size < 0 {
+            err!(
+                self,
+                ErrKind::InvaildType,
+                format!("the size of List(T, N) cannot be negative, got {size}")
+            );
+        }
+        Ok(size as u32)
+    }
+
+    // a list literal with N items is a List(T, N) where one is expected
+    pub fn fit_list(&mut self, node: NodeId, into: &AtomType) -> Result<(), ErrKind> {
+        let (AtomKind::Atom(into_list), Expr::ListExpr(items)) = (&into.kind, self.ast.expr(node))
+        else {
+            return Ok(());
+        };
+        let Some(len) = into_list.len else {
+            return Ok(());
+        };
+        let count = items.len();
+        let ty = self.resolve(self.ast.ty(node));
+        if !matches!(&ty.kind, AtomKind::Atom(list) if list.sized(None) == into_list.sized(None)) {
+            return Ok(());
+        }
+
+        if count != len as usize {
+            err!(
+                self,
+                ErrKind::InvaildType,
+                format!("expected {len} items for {into}, got {count}")
+            );
+        }
+        self.ast.set_ty(node, into.clone());
+        Ok(())
+    }
+
+    // Pair(int) of type Pair(T) = ..., the aliased type is analyzed with T as int
+    pub fn spec_alias(&mut self, alias: Alias, args: Vec<NodeId>) -> Result<NodeId, ErrKind> {
+        let spec = self.analyz_items(args.clone())?;
+
+        if spec.len() != alias.generics.len() {
+            err!(
+                self,
+                ErrKind::InvaildType,
+                format!(
+                    "expected {} generics got {}, for type {}",
+                    alias.generics.len(),
+                    spec.len(),
+                    alias.name
+                )
+            );
+        }
+
+        self.check_generic_args(&alias.name, &args, &spec, true)?;
+        self.env.child();
+        for (generic, spec) in alias.generics.iter().zip(&spec) {
+            let value = int_const(&self.ast, *spec).map(Literal::Int);
+            let spec = self.ast.ty(*spec).clone();
+
+            // a const generic is a const in the aliased type, like N in List(T, N)
+            self.env.add(Symbol {
+                name: SymbolId::intern(generic),
+                ty: spec,
+                value,
+                expected: None,
+                mutable: false,
+            });
+        }
+        let ty = self.analyz(alias.ty);
+        self.env.parent();
+        let ty = self.ast.ty(ty?).clone();
+
+        if !ty.is_type() {
+            err!(
+                self,
+                ErrKind::InvaildType,
+                format!("cannot alias {ty} as {}, it is not a type", alias.name)
+            );
+        }
+
+        let names: Vec<String> = spec
+            .iter()
+            .map(|spec| match int_const(&self.ast, *spec) {
+                Some(value) => value.to_string(),
+                None => self.ast.ty(*spec).to_string(),
+            })
+            .collect();
+        let ty = AtomType {
+            kind: ty.kind,
+            details: Some(AtomDetails::AliasType(format!(
+                "{}({})",
+                alias.name,
+                names.join(", ")
+            ))),
+        };
+
+        let parent = self.ast.push(
+            Expr::Ident(Ident::UnTagged(SymbolId::intern(&alias.name))),
+            ty.clone(),
+        );
+        Ok(self.ast.push(Expr::SpecExpr { parent, spec }, ty))
+    }
+
+    pub fn analyz_impl(
+        &mut self,
+        iface_name: SymbolId,
+        target: NodeId,
+        mut methods: Vec<Blueprint>,
+    ) -> Result<NodeId, ErrKind> {
+        let iface = match self.env.get_ty(iface_name).map(|ty| ty.kind) {
+            Some(AtomKind::Iface(iface)) => iface,
+            _ => {
+                err!(
+                    self,
+                    ErrKind::InvaildType,
+                    format!("{iface_name} is not an interface")
+                );
+            }
+        };
+
+        let target = self.analyz(target)?;
+        let target_ty = self.ast.ty(target);
+        if !target_ty.is_type() {
+            err!(
+                self,
+                ErrKind::InvaildType,
+                format!("cannot implement {iface_name} for {target_ty}, it is not a type")
+            );
+        }
+        let ty = AtomType {
+            kind: target_ty.kind.clone(),
+            details: None,
+        };
+
+        if self.env.get_impl(iface_name.as_str(), &ty).is_some() {
+            err!(
+                self,
+                ErrKind::VarAlreadyDeclared,
+                format!("{iface_name} is already implemented for {ty}")
+            );
+        }
+
+        for (name, sig) in &iface.methods {
+            let method = methods.iter().find(|method| method.name.val() == name);
+
+            match method {
+                None => {
+                    err!(
+                        self,
+                        ErrKind::UndeclaredVar,
+                        format!("{ty} is missing method {name} required by {iface_name}")
+                    );
+                }
+                Some(method) if method.args.len() != sig.params.len() => {
+                    err!(
+                        self,
+                        ErrKind::UnexceptedArgs,
+                        format!(
+                            "method {name} of {iface_name} takes {} params, implementation for {ty} takes {}",
+                            sig.params.len(),
+                            method.args.len()
+                        )
+                    );
+                }
+                _ => (),
+            }
+        }
+
+        let mut names = Vec::new();
+        for method in &mut methods {
+            let name = method.name.val().to_string();
+            if !iface.methods.contains_key(&name) {
+                err!(
+                    self,
+                    ErrKind::UnexceptedArgs,
+                    format!("method {name} is not a member of {iface_name}")
+                );
+            }
+
+            method.name.rename(&method_name(&ty, &name));
+            names.push(name);
+        }
+
+        self.blueprints(methods.clone())?;
+        self.env.add_impl(Impl {
+            iface: iface_name.to_string(),
+            ty,
+            methods: names,
+        });
+
+        Ok(self.ast.push(
+            Expr::Impl {
+                iface: iface_name,
+                target,
+                methods,
+            },
+            AtomType {
+                kind: AtomKind::Basic(BasicType::Void),
+                details: None,
+            },
+        ))
+    }
+
+    // constructs a user atom, args are given in field order
+    pub fn analyz_new(&mut self, atom: Atom, mut args: Vec<NodeId>) -> Result<NodeId, ErrKind> {
+        if args.len() != atom.fields.len() {
+            err!(
+                self,
+                ErrKind::UnexceptedArgs,
+                format!(
+                    "atom {} has {} fields got {} arguments",
+                    atom.name,
+                    atom.fields.len(),
+                    args.len()
+                )
+            );
+        }
+
+        for (arg, (field, ty)) in args.iter_mut().zip(&atom.fields) {
+            let arg_ty = self.ast.ty(*arg);
+            if arg_ty == ty {
+                continue;
+            }
+
+            if !can_implicitly_convert(&arg_ty.kind, &ty.kind) {
+                err!(
+                    self,
+                    ErrKind::UnexceptedArgs,
+                    format!(
+                        "field {field} of atom {} expects {ty}, got {arg_ty}",
+                        atom.name
+                    )
+                );
+            }
+            *arg = self.type_cast(*arg, ty.clone())?;
+        }
+
+        Ok(self.ast.push(
+            Expr::New(args),
+            AtomType {
+                kind: AtomKind::Atom(atom),
+                details: None,
+            },
+        ))
+    }
+
+    // calls an interface method on parent, parent is passed as the first arg (self)
+    pub fn call_method(
+        &mut self,
+        parent: NodeId,
+        method: &str,
+        args: Vec<NodeId>,
+    ) -> Result<NodeId, ErrKind> {
+        let parent_ty = self.ast.ty(parent).clone();
+        let name = method_name(&parent_ty, method);
+
+        let blueprint_t = match self.env.get_ty(SymbolId::intern(&name)).map(|ty| ty.kind) {
+            Some(AtomKind::Blueprint(blueprint_t)) => blueprint_t,
+            _ => {
+                err!(
+                    self,
+                    ErrKind::UndeclaredVar,
+                    format!("{parent_ty} has no method {method}")
+                );
+            }
+        };
+
+        let args = [vec![parent], args].concat();
+        let args_types = args.iter().map(|arg| self.ast.ty(*arg).clone()).collect();
+        self.handle_blueprint_call(blueprint_t, args, args_types)
+    }
+
+    // if a blueprint param of type param accepts an arg of type arg
+    pub fn accepts(&self, param: &AtomType, arg: &AtomType) -> bool {
+        match &param.kind {
+            AtomKind::Any => true,
+            AtomKind::Iface(iface) => self.env.get_impl(&iface.name, arg).is_some(),
+            _ => param == arg,
+        }
+    }
+}
+
+// the value of a node that is an int known at compile time
+fn int_const(ast: &Ast, node: NodeId) -> Option<i32> {
+    match consteval::eval(ast, node) {
+        Ok(Literal::Int(value)) if !ast.ty(node).is_type() => Some(value),
+        _ => None,
+    }
+}
@@ -0,0 +1,221 @@
+// compile time evaluation of analyzed nodes, for const declarations and folding operators on literals
+use crate::err;
+use crate::err::{ATErr, ErrKind};
+use crate::parser::ast::{Ast, BinOp, Expr, Ident, Literal, NodeId};
+
+use super::*;
+
+pub enum EvalErr {
+    NotConst(String), // the node needs runtime values, folding just skips it
+    Failed(String),   // the node always fails, like a division by zero
+}
+
+impl EvalErr {
+    fn msg(self) -> String {
+        match self {
+            EvalErr::NotConst(msg) | EvalErr::Failed(msg) => msg,
+        }
+    }
+}
+
+type EvalRes = Result<Literal, EvalErr>;
+
+pub fn eval(ast: &Ast, node: NodeId) -> EvalRes {
+    match ast.expr(node) {
+        Expr::Literal(lit) => Ok(lit.clone()),
+        Expr::BinaryExpr { op, left, right } => {
+            eval_binary(*op, eval(ast, *left)?, eval(ast, *right)?)
+        }
+        Expr::As(inner) => eval_as(eval(ast, *inner)?, ast.ty(node)),
+        Expr::Ident(id) => Err(EvalErr::NotConst(format!("{} is not a const", id.val()))),
+        Expr::FnCall { .. } => Err(EvalErr::NotConst(
+            "function calls are evaluated at runtime".to_string(),
+        )),
+        _ => Err(EvalErr::NotConst(
+            "only literals, consts, operators and conversions are evaluated at compile time"
+                .to_string(),
+        )),
+    }
+}
+
+fn eval_binary(op: BinOp, left: Literal, right: Literal) -> EvalRes {
+    use Literal::*;
+
+    let zero = matches!(right, Int(0)) || matches!(right, Float(f) if f == 0.0);
+    if zero && matches!(op, BinOp::Div | BinOp::Mod) {
+        return Err(EvalErr::Failed("division by zero".to_string()));
+    }
+
+    let overflow = || EvalErr::Failed(format!("{op} overflows int"));
+    let res = match (&left, &right) {
+        (Int(a), Int(b)) => match op {
+            BinOp::Add => Int(a.checked_add(*b).ok_or_else(overflow)?),
+            BinOp::Sub => Int(a.checked_sub(*b).ok_or_else(overflow)?),
+            BinOp::Mul => Int(a.checked_mul(*b).ok_or_else(overflow)?),
+            BinOp::Div => Int(a.checked_div(*b).ok_or_else(overflow)?),
+            BinOp::Mod => Int(a.checked_rem(*b).ok_or_else(overflow)?),
+            _ => Bool(compare(op, a.cmp(b))?),
+        },
+
+        (Float(a), Float(b)) => match op {
+            BinOp::Add => Float(a + b),
+            BinOp::Sub => Float(a - b),
+            BinOp::Mul => Float(a * b),
+            BinOp::Div => Float(a / b),
+            BinOp::Mod => Float(a % b),
+            _ => match a.partial_cmp(b) {
+                Some(ord) => Bool(compare(op, ord)?),
+                None => return Err(EvalErr::NotConst("NaN can't be compared".to_string())),
+            },
+        },
+
+        (Bool(a), Bool(b)) => match op {
+            BinOp::And => Bool(*a && *b),
+            BinOp::Or => Bool(*a || *b),
+            BinOp::Eq => Bool(a == b),
+            _ => return Err(unsupported(op, &left)),
+        },
+
+        (Str(a), Str(b)) => match op {
+            BinOp::Add => Str(format!("{a}{b}")),
+            BinOp::Eq => Bool(a == b),
+            _ => return Err(unsupported(op, &left)),
+        },
+
+        _ => return Err(unsupported(op, &left)),
+    };
+    Ok(res)
+}
+
+fn compare(op: BinOp, ord: std::cmp::Ordering) -> Result<bool, EvalErr> {
+    use std::cmp::Ordering::*;
+
+    Ok(match op {
+        BinOp::Eq => ord == Equal,
+        BinOp::Gt => ord == Greater,
+        BinOp::Lt => ord == Less,
+        BinOp::Ge => ord != Less,
+        BinOp::Le => ord != Greater,
+        _ => return Err(EvalErr::NotConst(format!("{op} is not a comparison"))),
+    })
+}
+
+fn unsupported(op: BinOp, lit: &Literal) -> EvalErr {
+    EvalErr::NotConst(format!(
+        "operator {op} on {} is evaluated at runtime",
+        lit.get_ty()
+    ))
+}
+
+// conversions between int, float and bool, the same as the runtime ones in types::conversion
+fn eval_as(lit: Literal, into: &AtomType) -> EvalRes {
+    use Literal::*;
+
+    let res = match (&lit, &into.kind) {
+        (Int(i), AtomKind::Basic(BasicType::Float)) => Float(*i as f32),
+        (Float(f), AtomKind::Basic(BasicType::Int)) => Int(*f as i32),
+        (Int(i), AtomKind::Basic(BasicType::Bool)) => Bool(*i != 0),
+        (Float(f), AtomKind::Basic(BasicType::Bool)) => Bool(*f != 0.0),
+        (Bool(b), AtomKind::Basic(BasicType::Int)) => Int(*b as i32),
+        (Bool(b), AtomKind::Basic(BasicType::Float)) => Float(*b as i32 as f32),
+        _ if lit.get_ty() == *into => lit,
+        _ => {
+            return Err(EvalErr::NotConst(format!(
+                "converting {} into {into} is done at runtime",
+                lit.get_ty()
+            )))
+        }
+    };
+    Ok(res)
+}
+
+impl Analyzer {
+    // const name = val, val has to evaluate at compile time
+    pub fn analyz_const(
+        &mut self,
+        name: Ident,
+        val: NodeId,
+        public: bool,
+    ) -> Result<NodeId, ErrKind> {
+        self.evaluating = Some(name.val().to_string());
+        let val = self.analyz(val);
+        self.evaluating = None;
+
+        let mut val = val?;
+        if self.env.has(name.sym()) {
+            err!(
+                self,
+                ErrKind::VarAlreadyDeclared,
+                format!("{} is already declared", name.val())
+            );
+        }
+
+        // const name@T = val converts val like a tagged variable would
+        if let Ident::Tagged(..) = name {
+            let into = self.analyz_unknown_id(name.clone())?.ty().clone();
+            let val_ty = self.ast.ty(val);
+            if !assignable(&into, val_ty) {
+                err!(
+                    self,
+                    ErrKind::InvaildType,
+                    format!(
+                        "cannot declare const {} as {into}, its value is {val_ty}",
+                        name.val()
+                    )
+                );
+            }
+            if into != *val_ty {
+                val = ty_as(&mut self.ast, &into, val);
+            }
+        }
+
+        let value = match eval(&self.ast, val) {
+            Ok(value) => value,
+            Err(e) => {
+                err!(
+                    self,
+                    ErrKind::ConstEval,
+                    format!("cannot evaluate const {}, {}", name.val(), e.msg())
+                );
+            }
+        };
+
+        let ty = value.get_ty();
+        self.env.add(Symbol {
+            name: name.sym(),
+            ty: ty.clone(),
+            value: Some(value.clone()),
+            expected: None,
+            mutable: false,
+        });
+
+        let val = self.ast.push(Expr::Literal(value), ty.clone());
+        Ok(self.ast.push(
+            Expr::Const {
+                name: Ident::Typed(Box::new(ty), name.sym()),
+                val,
+                public,
+            },
+            AtomType {
+                kind: AtomKind::Basic(BasicType::Void),
+                details: None,
+            },
+        ))
+    }
+
+    // replaces an operator on literals with its result, one that fails is only an error in a
+    // const, anywhere else it is left to fail at runtime if it ever runs
+    pub fn fold(&mut self, node: NodeId) -> Result<NodeId, ErrKind> {
+        match (eval(&self.ast, node), &self.evaluating) {
+            (Ok(lit), _) => {
+                let ty = self.ast.ty(node).clone();
+                Ok(self.ast.push(Expr::Literal(lit), ty))
+            }
+            (Err(EvalErr::Failed(msg)), Some(name)) => {
+                let msg = format!("cannot evaluate const {name}, {msg}");
+                err!(self, ErrKind::ConstEval, msg);
+            }
+            (Err(_), _) => Ok(node),
+        }
+    }
+}
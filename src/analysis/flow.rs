@@ -0,0 +1,50 @@
+// control flow checks on analyzed blueprint bodies, unreachable code is reported by lint::UnreachableCode
+
+use super::*;
+use crate::parser::ast::Literal;
+
+// if running node always ends in a ret, an if only does when all of its branches do
+pub fn terminates(ast: &Ast, node: NodeId) -> bool {
+    match ast.expr(node) {
+        Expr::RetExpr(_) => true,
+        Expr::Discard(node) => terminates(ast, *node),
+        Expr::Block(body) => body_terminates(ast, body),
+        Expr::IfExpr {
+            body,
+            alt: Some(alt),
+            ..
+        } => body_terminates(ast, body) && terminates(ast, *alt),
+
+        // while true never falls through, there is no break
+        Expr::WhileExpr { condition, .. } => {
+            matches!(ast.expr(*condition), Expr::Literal(Literal::Bool(true)))
+        }
+        _ => false,
+    }
+}
+
+pub fn body_terminates(ast: &Ast, body: &[NodeId]) -> bool {
+    body.iter().any(|node| terminates(ast, *node))
+}
+
+impl Analyzer {
+    // errors if a non void function can fall off its end
+    pub fn check_flow(
+        &mut self,
+        name: &str,
+        body: &[NodeId],
+        ret: &AtomType,
+    ) -> Result<(), ErrKind> {
+        if ret.kind != AtomKind::Basic(BasicType::Void) && !body_terminates(&self.ast, body) {
+            err!(
+                self,
+                ErrKind::MissingReturn,
+                format!(
+                    "function {} doesn't return {ret} on every path, add a ret at its end",
+                    demangle(name)
+                )
+            );
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,190 @@
+// unification based type inference, type variables (AtomKind::Var) are created for
+// types that are only known later on (empty lists, recursive blueprint returns)
+// and solved as constraints are met
+use crate::err;
+use crate::err::{ATErr, ErrKind};
+use crate::parser::ast::{Expr, Ident, NodeId};
+use crate::types::Atom;
+
+use super::*;
+
+#[derive(Debug, Default, Clone)]
+pub struct Infer {
+    bindings: Vec<Option<AtomType>>, // var => what it was solved to
+}
+
+impl Infer {
+    pub fn fresh(&mut self) -> AtomType {
+        self.bindings.push(None);
+        AtomType {
+            kind: AtomKind::Var(self.bindings.len() - 1),
+            details: None,
+        }
+    }
+
+    // replaces every solved var in ty
+    pub fn resolve(&self, ty: &AtomType) -> AtomType {
+        let kind = match &ty.kind {
+            AtomKind::Var(var) => match &self.bindings[*var] {
+                Some(bound) => return self.resolve(bound),
+                None => return ty.clone(),
+            },
+
+            AtomKind::Atom(atom) if atom.generics.values().any(|gen| gen.has_vars()) => {
+                let generics = atom
+                    .generics
+                    .iter()
+                    .map(|(name, gen)| (name.clone(), self.resolve(gen)))
+                    .collect();
+                AtomKind::Atom(Atom {
+                    generics,
+                    ..atom.clone()
+                })
+            }
+
+            AtomKind::Function(func) if ty.has_vars() => AtomKind::Function(FunctionType {
+                params: func
+                    .params
+                    .iter()
+                    .map(|param| self.resolve(param))
+                    .collect(),
+                return_type: Box::new(self.resolve(&func.return_type)),
+            }),
+
+            _ => return ty.clone(),
+        };
+
+        AtomType {
+            kind,
+            details: ty.details.clone(),
+        }
+    }
+
+    fn occurs(&self, var: usize, ty: &AtomType) -> bool {
+        match &self.resolve(ty).kind {
+            AtomKind::Var(other) => *other == var,
+            AtomKind::Atom(atom) => atom.generics.values().any(|gen| self.occurs(var, gen)),
+            AtomKind::Function(func) => {
+                self.occurs(var, &func.return_type)
+                    || func.params.iter().any(|param| self.occurs(var, param))
+            }
+            _ => false,
+        }
+    }
+
+    fn bind(&mut self, var: usize, ty: AtomType) -> Result<AtomType, String> {
+        if self.occurs(var, &ty) {
+            return Err(format!("type ?{var} cannot contain itself, got {ty}"));
+        }
+
+        self.bindings[var] = Some(ty.clone());
+        Ok(ty)
+    }
+
+    // makes a and b the same type, returns the type they were unified into
+    pub fn unify(&mut self, a: &AtomType, b: &AtomType) -> Result<AtomType, String> {
+        let a = self.resolve(a);
+        let b = self.resolve(b);
+
+        match (&a.kind, &b.kind) {
+            (AtomKind::Var(x), AtomKind::Var(y)) if x == y => Ok(a),
+            (AtomKind::Var(var), _) => self.bind(*var, b),
+            (_, AtomKind::Var(var)) => self.bind(*var, a),
+
+            (AtomKind::Atom(x), AtomKind::Atom(y))
+                if x.name == y.name && x.generics.len() == y.generics.len() =>
+            {
+                for (gen_x, gen_y) in x.generics.values().zip(y.generics.values()) {
+                    self.unify(gen_x, gen_y)
+                        .map_err(|_| format!("cannot unify types {a} and {b}"))?;
+                }
+                Ok(self.resolve(&a))
+            }
+
+            (AtomKind::Function(x), AtomKind::Function(y)) if x.params.len() == y.params.len() => {
+                for (param_x, param_y) in x.params.iter().zip(&y.params) {
+                    self.unify(param_x, param_y)
+                        .map_err(|_| format!("cannot unify types {a} and {b}"))?;
+                }
+                self.unify(&x.return_type, &y.return_type)
+                    .map_err(|_| format!("cannot unify types {a} and {b}"))?;
+                Ok(self.resolve(&a))
+            }
+
+            _ if a.kind == b.kind => Ok(a),
+            _ => Err(format!("cannot unify types {a} and {b}")),
+        }
+    }
+}
+
+impl Analyzer {
+    pub fn unify(&mut self, a: &AtomType, b: &AtomType) -> Result<AtomType, ErrKind> {
+        match self.infer.unify(a, b) {
+            Ok(ty) => Ok(ty),
+            Err(msg) => {
+                err!(self, ErrKind::InvaildType, msg);
+            }
+        }
+    }
+
+    #[inline]
+    pub fn resolve(&self, ty: &AtomType) -> AtomType {
+        self.infer.resolve(ty)
+    }
+
+    // if a value of type ty used as into is unified with it, a Dynamic holds any value so only a
+    // bare var is solved as Dynamic, List(?0) is boxed as whatever List it turns out to be
+    pub fn solves(&self, into: &AtomType, ty: &AtomType) -> bool {
+        if into.kind == AtomKind::Dynamic {
+            return matches!(self.resolve(ty).kind, AtomKind::Var(_));
+        }
+        into.has_vars() || ty.has_vars()
+    }
+
+    // replaces the type variables in body with what they were solved to, errors on the ones that weren't
+    // nodes are resolved before their children, in the order they were written
+    pub fn resolve_body(&mut self, body: &[NodeId]) -> Result<(), ErrKind> {
+        let mut stack: Vec<NodeId> = body.iter().rev().copied().collect();
+        while let Some(node) = stack.pop() {
+            self.resolve_node(node)?;
+            stack.extend(self.ast.children(node).rev());
+        }
+        Ok(())
+    }
+
+    fn resolve_node(&mut self, node: NodeId) -> Result<(), ErrKind> {
+        (self.line, self.column) = self.ast.pos(node);
+        let infer = &self.infer;
+        match self.ast.expr_mut(node) {
+            Expr::PosInfo(_, line, column) => {
+                self.line = *line;
+                self.column = *column;
+            }
+
+            Expr::Func { ret, args, .. } => {
+                **ret = infer.resolve(ret);
+                for arg in args {
+                    if let Ident::Typed(ty, _) = arg {
+                        **ty = infer.resolve(ty);
+                    }
+                }
+            }
+            _ => (),
+        }
+
+        let ty = self.resolve(self.ast.ty(node));
+        if ty.has_vars() {
+            let of = match self.ast.expr(node) {
+                Expr::Ident(id) | Expr::VarDeclare { name: id, .. } => format!(" of {}", id.val()),
+                _ => String::new(),
+            };
+            err!(
+                self,
+                ErrKind::InvaildType,
+                format!("cannot infer type {ty}{of}, add a type tag")
+            );
+        }
+        self.ast.set_ty(node, ty);
+        Ok(())
+    }
+}
@@ -0,0 +1,541 @@
+// lints over the analyzed program, a lint reports hits and the Linter decides their severity from the lint level
+//
+// levels are set with --warn=lint, --deny=lint and --allow=lint (all sets every lint),
+// #allow(lint, ...) on a blueprint declaration allows lints in it, anywhere else in the whole file
+
+use std::collections::HashMap;
+
+use crate::err::{ATErr, ErrKind, Severity};
+use crate::parser::ast::{Ast, BinOp, Blueprint, Expr, Ident, NodeId};
+use crate::types::{demangle, demangle_parts, AtomKind};
+
+use super::flow::terminates;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+// the name top level code is linted under, it isn't an identifier so no blueprint's #allow
+// applies to it
+pub const TOP_LEVEL: &str = "top level code";
+
+// a function body to lint, top level code is linted as a body named TOP_LEVEL without params
+pub struct Body<'a> {
+    pub name: &'a str,
+    pub params: &'a [Ident],
+    pub body: Vec<NodeId>,
+    pub at: (u16, u16), // line and column of the function
+}
+
+pub struct LintCx<'a> {
+    pub ast: &'a Ast,
+    pub bodies: Vec<Body<'a>>,
+    pub blueprints: &'a [Blueprint],
+}
+
+pub struct Hit {
+    pub func: String, // the function the hit is in, used for #allow on blueprints
+    pub msg: String,
+    pub at: (u16, u16), // line and column the hit is reported at
+}
+
+pub trait Lint {
+    fn name(&self) -> &'static str;
+    fn check(&self, cx: &LintCx, hits: &mut Vec<Hit>);
+}
+
+pub struct Linter {
+    lints: Vec<Box<dyn Lint>>,
+    levels: HashMap<&'static str, Level>,
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Linter {
+    // every built-in lint at warn level
+    pub fn new() -> Self {
+        let mut linter = Self {
+            lints: Vec::new(),
+            levels: HashMap::new(),
+        };
+        linter.add(Box::new(UnusedVariables));
+        linter.add(Box::new(UnusedBlueprints));
+        linter.add(Box::new(Shadowing));
+        linter.add(Box::new(AlwaysTrue));
+        linter.add(Box::new(NeedlessDynamic));
+        linter.add(Box::new(UnreachableCode));
+        linter
+    }
+
+    pub fn add(&mut self, lint: Box<dyn Lint>) {
+        self.levels.insert(lint.name(), Level::Warn);
+        self.lints.push(lint);
+    }
+
+    pub fn set_level(&mut self, name: &str, level: Level) -> Result<(), String> {
+        self.check(name)?;
+        for (lint, lint_level) in self.levels.iter_mut() {
+            if name == "all" || *lint == name {
+                *lint_level = level;
+            }
+        }
+        Ok(())
+    }
+
+    // errors on a name that isn't a lint or all, used for flags and #allow
+    pub fn check(&self, name: &str) -> Result<(), String> {
+        if name == "all" || self.levels.contains_key(name) {
+            return Ok(());
+        }
+        let mut lints: Vec<&str> = self.levels.keys().copied().collect();
+        lints.sort();
+        Err(format!(
+            "unknown lint {name}, lints are: all, {}",
+            lints.join(", ")
+        ))
+    }
+
+    // lints prog, allows are the lints allowed in the whole file
+    pub fn run(
+        &self,
+        ast: &Ast,
+        prog: &[NodeId],
+        blueprints: &[Blueprint],
+        allows: &[String],
+    ) -> Vec<ATErr> {
+        let mut top = Body {
+            name: TOP_LEVEL,
+            params: &[],
+            body: Vec::new(),
+            at: (1, 0),
+        };
+        let mut bodies = Vec::new();
+        for node in prog {
+            match ast.expr(*node) {
+                Expr::Func {
+                    name, args, body, ..
+                } => bodies.push(Body {
+                    name: name.as_str(),
+                    params: args,
+                    body: body.clone(),
+                    at: ast.pos(*node),
+                }),
+                Expr::Import { .. } => (),
+                _ => top.body.push(*node),
+            }
+        }
+        bodies.push(top);
+        let cx = LintCx {
+            ast,
+            bodies,
+            blueprints,
+        };
+
+        let mut diagnostics = Vec::new();
+        for lint in &self.lints {
+            let severity = match self.levels[lint.name()] {
+                _ if allows
+                    .iter()
+                    .any(|allow| allow == "all" || allow == lint.name()) =>
+                {
+                    continue
+                }
+                Level::Allow => continue,
+                Level::Warn => Severity::Warning,
+                Level::Deny => Severity::Error,
+            };
+
+            let mut hits = Vec::new();
+            lint.check(&cx, &mut hits);
+            for hit in hits {
+                let blueprint =
+                    demangle_parts(&hit.func).map_or(hit.func.clone(), |(name, _)| name);
+                let allowed = blueprints.iter().any(|b| {
+                    b.name.val() == blueprint
+                        && b.allow
+                            .iter()
+                            .any(|allow| allow == "all" || allow == lint.name())
+                });
+                if allowed {
+                    continue;
+                }
+
+                diagnostics.push(ATErr {
+                    kind: ErrKind::Lint,
+                    msg: format!("{} [{}]", hit.msg, lint.name()),
+                    line: hit.at.0,
+                    column: hit.at.1,
+                    severity,
+                });
+            }
+        }
+        diagnostics
+    }
+}
+
+// calls f on node and everything under it
+fn walk(ast: &Ast, node: NodeId, f: &mut dyn FnMut(NodeId)) {
+    f(node);
+    for child in ast.children(node) {
+        walk(ast, child, f);
+    }
+}
+
+fn ident(ast: &Ast, node: NodeId) -> Option<&'static str> {
+    match ast.expr(node) {
+        Expr::Ident(id) => Some(id.val()),
+        _ => None,
+    }
+}
+
+// the node a statement holds, ifs and loops at the top level are discarded values
+fn statement(ast: &Ast, node: NodeId) -> &Expr {
+    match ast.expr(node) {
+        Expr::Discard(node) => ast.expr(*node),
+        expr => expr,
+    }
+}
+
+fn is_ignored(name: &str) -> bool {
+    name.starts_with('_')
+}
+
+// variables that are declared but never read, assigning to a variable isn't reading it
+struct UnusedVariables;
+
+impl Lint for UnusedVariables {
+    fn name(&self) -> &'static str {
+        "unused_variables"
+    }
+
+    fn check(&self, cx: &LintCx, hits: &mut Vec<Hit>) {
+        for body in &cx.bodies {
+            let mut unused = Vec::new();
+            self.scan_body(cx.ast, &body.body, &mut Vec::new(), &mut unused);
+            unused.sort_by_key(|node| cx.ast.pos(*node));
+
+            for node in unused {
+                let Expr::VarDeclare { name, .. } = cx.ast.expr(node) else {
+                    continue;
+                };
+                let name = name.val();
+                if !is_ignored(name) {
+                    hits.push(Hit {
+                        func: body.name.to_string(),
+                        msg: format!("unused variable {name} in {}", demangle(body.name)),
+                        at: cx.ast.pos(node),
+                    });
+                }
+            }
+        }
+    }
+}
+
+// a declaration in scope and whether it was read
+type Declared = (&'static str, NodeId, bool);
+
+impl UnusedVariables {
+    // a block is a scope, its declarations that were never read go into unused when it ends
+    fn scan_body(
+        &self,
+        ast: &Ast,
+        body: &[NodeId],
+        scopes: &mut Vec<Vec<Declared>>,
+        unused: &mut Vec<NodeId>,
+    ) {
+        scopes.push(Vec::new());
+        for node in body {
+            self.scan(ast, *node, scopes, unused);
+        }
+        let scope = scopes.pop().unwrap();
+        unused.extend(
+            scope
+                .iter()
+                .filter(|(.., used)| !used)
+                .map(|(_, node, _)| *node),
+        );
+    }
+
+    // a use reads the innermost declaration of its name
+    fn scan(
+        &self,
+        ast: &Ast,
+        node: NodeId,
+        scopes: &mut Vec<Vec<Declared>>,
+        unused: &mut Vec<NodeId>,
+    ) {
+        match ast.expr(node) {
+            Expr::VarDeclare { name, val, .. } => {
+                self.scan(ast, *val, scopes, unused);
+                scopes.last_mut().unwrap().push((name.val(), node, false));
+            }
+            Expr::Ident(id) => {
+                let declared = scopes
+                    .iter_mut()
+                    .rev()
+                    .find_map(|scope| scope.iter_mut().rev().find(|(name, ..)| *name == id.val()));
+                if let Some((.., used)) = declared {
+                    *used = true;
+                }
+            }
+            // assigning to a variable isn't reading it
+            Expr::VarAssign { name, val } => {
+                if ident(ast, *name).is_none() {
+                    self.scan(ast, *name, scopes, unused);
+                }
+                self.scan(ast, *val, scopes, unused);
+            }
+            Expr::Block(body) => self.scan_body(ast, body, scopes, unused),
+            Expr::IfExpr {
+                condition,
+                body,
+                alt,
+            } => {
+                self.scan(ast, *condition, scopes, unused);
+                self.scan_body(ast, body, scopes, unused);
+                if let Some(alt) = alt {
+                    self.scan_body(ast, &[*alt], scopes, unused);
+                }
+            }
+            Expr::WhileExpr { condition, body } => {
+                self.scan(ast, *condition, scopes, unused);
+                self.scan_body(ast, body, scopes, unused);
+            }
+            _ => {
+                for child in ast.children(node) {
+                    self.scan(ast, child, scopes, unused);
+                }
+            }
+        }
+    }
+}
+
+// blueprints that are never called, so never built
+struct UnusedBlueprints;
+
+impl Lint for UnusedBlueprints {
+    fn name(&self) -> &'static str {
+        "unused_blueprints"
+    }
+
+    fn check(&self, cx: &LintCx, hits: &mut Vec<Hit>) {
+        for blueprint in cx.blueprints {
+            let name = blueprint.name.val();
+            let built = cx
+                .bodies
+                .iter()
+                .any(|body| demangle_parts(body.name).is_some_and(|(built, _)| built == name));
+
+            if !built && !is_ignored(name) {
+                hits.push(Hit {
+                    func: name.to_string(),
+                    msg: format!("blueprint {name} is never called"),
+                    at: (blueprint.line, blueprint.column),
+                });
+            }
+        }
+    }
+}
+
+// declarations that hide a name from an enclosing block or a blueprint
+struct Shadowing;
+
+impl Shadowing {
+    fn check_body(
+        &self,
+        ast: &Ast,
+        body: &[NodeId],
+        scopes: &mut Vec<Vec<&'static str>>,
+        func: &str,
+        hits: &mut Vec<Hit>,
+    ) {
+        scopes.push(Vec::new());
+        for node in body {
+            match statement(ast, *node) {
+                Expr::VarDeclare { name, .. } => {
+                    let name = name.val();
+                    if scopes.iter().any(|scope| scope.contains(&name)) && !is_ignored(name) {
+                        hits.push(Hit {
+                            func: func.to_string(),
+                            msg: format!("{name} in {} shadows an outer {name}", demangle(func)),
+                            at: ast.pos(*node),
+                        });
+                    }
+                    scopes.last_mut().unwrap().push(name);
+                }
+                Expr::IfExpr { body, alt, .. } => {
+                    self.check_body(ast, body, scopes, func, hits);
+                    if let Some(alt) = alt {
+                        self.check_body(ast, &[*alt], scopes, func, hits);
+                    }
+                }
+                Expr::WhileExpr { body, .. } | Expr::Block(body) => {
+                    self.check_body(ast, body, scopes, func, hits)
+                }
+                _ => (),
+            }
+        }
+        scopes.pop();
+    }
+}
+
+impl Lint for Shadowing {
+    fn name(&self) -> &'static str {
+        "shadowing"
+    }
+
+    fn check(&self, cx: &LintCx, hits: &mut Vec<Hit>) {
+        // top level variables aren't visible in blueprints, only other blueprints are
+        let globals: Vec<&str> = cx.blueprints.iter().map(|b| b.name.val()).collect();
+
+        for body in &cx.bodies {
+            for param in body.params {
+                let name = param.val();
+                if globals.contains(&name) && !is_ignored(name) {
+                    hits.push(Hit {
+                        func: body.name.to_string(),
+                        msg: format!(
+                            "param {name} of {} shadows the blueprint {name}",
+                            demangle(body.name)
+                        ),
+                        at: body.at,
+                    });
+                }
+            }
+            let mut scopes = vec![
+                globals.clone(),
+                body.params.iter().map(|p| p.val()).collect(),
+            ];
+            self.check_body(cx.ast, &body.body, &mut scopes, body.name, hits);
+        }
+    }
+}
+
+// x == x, x >= x, comparisons of literals are folded by the analyzer before linting
+struct AlwaysTrue;
+
+impl Lint for AlwaysTrue {
+    fn name(&self) -> &'static str {
+        "always_true"
+    }
+
+    fn check(&self, cx: &LintCx, hits: &mut Vec<Hit>) {
+        for body in &cx.bodies {
+            for node in &body.body {
+                walk(cx.ast, *node, &mut |node| {
+                    let Expr::BinaryExpr { op, left, right } = cx.ast.expr(node) else {
+                        return;
+                    };
+                    if !matches!(op, BinOp::Eq | BinOp::Ge | BinOp::Le) {
+                        return;
+                    }
+
+                    let same = matches!((cx.ast.expr(*left), cx.ast.expr(*right)),
+                        (Expr::Ident(a), Expr::Ident(b)) if a.val() == b.val());
+                    if same {
+                        hits.push(Hit {
+                            func: body.name.to_string(),
+                            msg: format!(
+                                "comparison with {op} in {} is always true, both sides are the same",
+                                demangle(body.name)
+                            ),
+                            at: cx.ast.pos(node),
+                        });
+                    }
+                });
+            }
+        }
+    }
+}
+
+// converting a value into Dynamic only to convert it back into the type it already was
+struct NeedlessDynamic;
+
+impl Lint for NeedlessDynamic {
+    fn name(&self) -> &'static str {
+        "needless_dynamic"
+    }
+
+    fn check(&self, cx: &LintCx, hits: &mut Vec<Hit>) {
+        for body in &cx.bodies {
+            for node in &body.body {
+                walk(cx.ast, *node, &mut |node| {
+                    let Expr::As(inner) = cx.ast.expr(node) else {
+                        return;
+                    };
+                    let Expr::As(value) = cx.ast.expr(*inner) else {
+                        return;
+                    };
+
+                    let ty = cx.ast.ty(node);
+                    if cx.ast.ty(*inner).kind == AtomKind::Dynamic && cx.ast.ty(*value) == ty {
+                        hits.push(Hit {
+                            func: body.name.to_string(),
+                            msg: format!(
+                                "needless Dynamic conversion in {}, the value is already {ty}",
+                                demangle(body.name)
+                            ),
+                            at: cx.ast.pos(node),
+                        });
+                    }
+                });
+            }
+        }
+    }
+}
+
+// statements after a ret, or after an if where every branch returns
+struct UnreachableCode;
+
+impl UnreachableCode {
+    fn check_body(&self, ast: &Ast, body: &[NodeId], func: &str, hits: &mut Vec<Hit>) {
+        let mut terminated = false;
+        for node in body {
+            if let Expr::PosInfo(..) = ast.expr(*node) {
+                continue;
+            }
+
+            if terminated {
+                hits.push(Hit {
+                    func: func.to_string(),
+                    msg: format!("unreachable code in {}", demangle(func)),
+                    at: ast.pos(*node),
+                });
+                return;
+            }
+
+            match statement(ast, *node) {
+                Expr::IfExpr { body, alt, .. } => {
+                    self.check_body(ast, body, func, hits);
+                    if let Some(alt) = alt {
+                        self.check_body(ast, &[*alt], func, hits);
+                    }
+                }
+                Expr::WhileExpr { body, .. } | Expr::Block(body) => {
+                    self.check_body(ast, body, func, hits)
+                }
+                _ => (),
+            }
+            terminated = terminates(ast, *node);
+        }
+    }
+}
+
+impl Lint for UnreachableCode {
+    fn name(&self) -> &'static str {
+        "unreachable_code"
+    }
+
+    fn check(&self, cx: &LintCx, hits: &mut Vec<Hit>) {
+        for body in &cx.bodies {
+            self.check_body(cx.ast, &body.body, body.name, hits);
+        }
+    }
+}
@@ -1,148 +1,192 @@
 pub mod analysis;
+pub mod atoms;
+pub mod consteval;
+pub mod flow;
+pub mod infer;
+pub mod lint;
+pub mod modules;
+pub mod recursion;
 
 use std::vec;
 
 use crate::enviroment::{Enviroment, Symbol};
 
-use crate::err::ErrKind;
+use crate::err;
+use crate::err::{ATErr, ErrKind};
 
-use crate::parser::ast::{Blueprint, Expr, Ident, Node};
+use crate::parser::ast::{Ast, BinOp, Blueprint, Expr, Ident, NodeId};
+use crate::symbol::SymbolId;
 use crate::types::{
-    self, type_mangle, AtomDetails, AtomKind, AtomType, BasicType, BlueprintType, FunctionType,
+    self, demangle, type_mangle, AtomDetails, AtomKind, AtomType, BasicType, BlueprintType,
+    FunctionType,
 };
 
 pub struct Analyzer {
-    workdir: String,
     pub env: Enviroment,
-    pub imports: Vec<Node>,   // Import nodes
-    pub functions: Vec<Node>, // Func nodes
+    pub ast: Ast,               // parsed nodes and the analyzed nodes built from them
+    pub imports: Vec<NodeId>,   // Import nodes
+    pub functions: Vec<NodeId>, // Func nodes
+    pub infer: infer::Infer,
+    pub recursion: recursion::Recursion,
+    pub modules: modules::Modules,
+    evaluating: Option<String>, // the const being analyzed, for evaluation errors
+    ended: Vec<SymbolId>,       // variables of blocks that ended, for errors on using them after
     line: u16,
     column: u16,
 }
 
 impl AtomType {
-    pub fn get_op(&self) -> &[&str] {
+    pub fn get_op(&self) -> &[BinOp] {
+        use BinOp::*;
+
         match &self.kind {
-            &AtomKind::Basic(BasicType::Bool) => &["==", "||", "&&"],
+            &AtomKind::Basic(BasicType::Bool) => &[Eq, Or, And],
             &AtomKind::Basic(BasicType::Float) | &AtomKind::Basic(BasicType::Int) => {
-                &["+", "-", "*", "/", "%", "<", ">", "<=", ">=", "=="]
+                &[Add, Sub, Mul, Div, Mod, Lt, Gt, Le, Ge, Eq]
             }
             &AtomKind::Atom(ref atom)
                 if atom == &*types::Str || &atom.name == &*types::List.name =>
             {
-                &["<", ">", "==", "<=", ">=", "+", "-"]
+                &[Lt, Gt, Eq, Le, Ge, Add, Sub]
+            }
+            &AtomKind::Dynamic | &AtomKind::Var(_) => {
+                &[And, Or, Eq, Lt, Gt, Le, Ge, Add, Sub, Mul, Div, Mod]
             }
-            &AtomKind::Dynamic | &AtomKind::Unknown => &[
-                "&&", "||", "==", "<", ">", "<=", ">=", "+", "-", "*", "/", "%",
-            ],
             _ => &[],
         }
     }
 }
 
+// operators that resolve through interfaces when the type has no built-in support
+// op => (interface, method)
+pub fn op_iface(op: BinOp) -> Option<(&'static str, &'static str)> {
+    match op {
+        BinOp::Add => Some(("Add", "add")),
+        BinOp::Sub => Some(("Sub", "sub")),
+        BinOp::Mul => Some(("Mul", "mul")),
+        BinOp::Div => Some(("Div", "div")),
+        BinOp::Mod => Some(("Mod", "mod")),
+        BinOp::Eq => Some(("Eq", "eq")),
+        _ => None,
+    }
+}
+
 #[inline]
-pub fn ty_as(ty: &AtomType, expr: Node) -> Node {
-    Node {
-        expr: Expr::As(Box::new(expr)),
-        ty: ty.clone(),
+pub fn ty_as(ast: &mut Ast, ty: &AtomType, expr: NodeId) -> NodeId {
+    ast.push(Expr::As(expr), ty.clone())
+}
+
+// if a value of type from can be stored in a variable of type into, with ty_as when they differ
+pub fn assignable(into: &AtomType, from: &AtomType) -> bool {
+    into == from
+        || (into.kind == AtomKind::Dynamic && types::can_implicitly_convert(&from.kind, &into.kind))
+        || (into.kind == AtomKind::Basic(BasicType::Float)
+            && from.kind == AtomKind::Basic(BasicType::Int))
+}
+
+// how an arg matches a blueprint param, from the best match to the worst
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum ArgMatch {
+    Exact,
+    Iface,    // the arg implements the param interface
+    Implicit, // the arg is converted into the param type, see assignable
+    Any,
+}
+
+impl ArgMatch {
+    // if every arg in a matches at least as well as in b, and one better
+    pub fn better(a: &[ArgMatch], b: &[ArgMatch]) -> bool {
+        a != b && a.iter().zip(b).all(|(a, b)| a <= b)
     }
 }
 
 #[inline]
-pub fn supports_op(ty: &AtomType, op: &String) -> bool {
-    let ops = ty.get_op();
-    ops.contains(&op.as_str())
+pub fn supports_op(ty: &AtomType, op: BinOp) -> bool {
+    ty.get_op().contains(&op)
 }
 
-fn get_ret_ty(node: &Node) -> Vec<AtomType> {
-    match node.expr.clone() {
-        Expr::RetExpr(node) => {
-            if let &Some(AtomDetails::Unknown(ref ty)) = &node.ty.details {
-                return vec![(**ty).clone()];
-            }
-            return vec![node.ty.clone()];
-        }
+fn get_ret_ty(ast: &Ast, node: NodeId) -> Vec<AtomType> {
+    match ast.expr(node) {
+        Expr::RetExpr(node) => vec![ast.ty(*node).clone()],
 
         Expr::IfExpr { body, alt, .. } => {
-            let mut ty = get_body_types(&body);
-            if alt.is_some() {
-                ty = get_ret_ty(&alt.unwrap());
+            let mut ty = get_body_types(ast, body);
+            if let Some(alt) = alt {
+                ty.append(&mut get_ret_ty(ast, *alt));
             }
             ty
         }
 
-        Expr::WhileExpr { body, .. } | Expr::Block(body) => get_body_types(&body),
+        Expr::WhileExpr { body, .. } | Expr::Block(body) => get_body_types(ast, body),
         // get fn ty => Block , ifBody
         _ => Vec::new(),
     }
 }
 
-pub fn get_body_types(body: &Vec<Node>) -> Vec<AtomType> {
-    let mut types = Vec::new();
+// converts the value of every ret in body that isn't a ty into one, see assignable
+pub fn conv_rets(ast: &mut Ast, body: &[NodeId], ty: &AtomType) {
     for node in body {
-        for ty in get_ret_ty(node) {
-            if !types.contains(&ty) {
-                types.push(ty);
-            }
-        }
+        conv_ret(ast, *node, ty);
     }
-    types
 }
 
-pub fn get_fn_type(body: &Vec<Node>) -> AtomType {
-    let possible = get_body_types(body);
-
-    if possible.len() == 0 {
-        return AtomType {
-            kind: AtomKind::Basic(BasicType::Void),
-            details: None,
-        };
-    }
-
-    if possible.len() > 1 {
-        // int | float -> float
-        // otherwise -> dynamic
+fn conv_ret(ast: &mut Ast, node: NodeId, ty: &AtomType) {
+    match ast.expr(node) {
+        Expr::RetExpr(val) => {
+            let val = *val;
+            let from = ast.ty(val);
+            if from != ty && assignable(ty, from) {
+                let conv = ty_as(ast, ty, val);
+                *ast.expr_mut(node) = Expr::RetExpr(conv);
+            }
+        }
 
-        if possible.len() == 2
-            && possible.contains(&AtomType {
-                kind: AtomKind::Basic(BasicType::Int),
-                details: None,
-            })
-            && possible.contains(&AtomType {
-                kind: AtomKind::Basic(BasicType::Float),
-                details: None,
-            })
-        {
-            return AtomType {
-                kind: AtomKind::Basic(BasicType::Float),
-                details: None,
-            };
+        Expr::IfExpr { body, alt, .. } => {
+            let (body, alt) = (body.clone(), *alt);
+            conv_rets(ast, &body, ty);
+            if let Some(alt) = alt {
+                conv_ret(ast, alt, ty);
+            }
         }
 
-        return AtomType {
-            kind: AtomKind::Unknown,
-            details: None,
-        };
+        Expr::WhileExpr { body, .. } | Expr::Block(body) => conv_rets(ast, &body.clone(), ty),
+        _ => {}
     }
+}
 
-    possible[0].clone()
+pub fn get_body_types(ast: &Ast, body: &[NodeId]) -> Vec<AtomType> {
+    let mut types = Vec::new();
+    for node in body {
+        for ty in get_ret_ty(ast, *node) {
+            if !types.contains(&ty) {
+                types.push(ty);
+            }
+        }
+    }
+    types
 }
+
 impl Analyzer {
-    pub fn new(workdir: String) -> Self {
+    pub fn new(ast: Ast, workdir: String) -> Self {
         Self {
             env: Enviroment::init(),
+            ast,
             functions: Vec::new(),
             imports: Vec::new(),
+            infer: infer::Infer::default(),
+            recursion: recursion::Recursion::default(),
+            modules: modules::Modules::new(workdir),
+            evaluating: None,
+            ended: Vec::new(),
             line: 0,
             column: 0,
-            workdir,
         }
     }
 
     #[inline]
     fn import(
         &mut self,
-        body: &mut Vec<Node>,
+        body: &mut Vec<NodeId>,
         ty: AtomType,
         module: &str,
         name: &str,
@@ -152,26 +196,27 @@ impl Analyzer {
             params: params.clone(),
             return_type: Box::new(ty),
         };
-        self.env.push_function(name.to_string(), func.clone());
-        body.push(Node {
-            expr: Expr::Import {
+        let name = SymbolId::intern(name);
+        self.env.push_function(name, func.clone());
+        body.push(self.ast.push(
+            Expr::Import {
                 module: module.to_string(),
-                name: name.to_string(),
+                name,
                 params,
             },
-            ty: AtomType {
+            AtomType {
                 kind: AtomKind::Function(func),
                 details: None,
             },
-        })
+        ))
     }
 
     pub fn expect(&mut self, name: &Ident) -> Result<(), ErrKind> {
-        self.expect_as(name.val(), name)
+        self.expect_as(name.sym(), name)
     }
 
     // expects a name as an ident tag if it has a tag
-    pub fn expect_as(&mut self, name: &String, from: &Ident) -> Result<(), ErrKind> {
+    pub fn expect_as(&mut self, name: SymbolId, from: &Ident) -> Result<(), ErrKind> {
         if let Ident::Tagged(_, _) = from {
             let id = self.analyz_unknown_id(from.clone())?;
 
@@ -182,6 +227,68 @@ impl Analyzer {
         }
     }
 
+    // the return type of a function body, ret is the type the body is assumed to return by recursive calls
+    pub fn get_fn_type(
+        &mut self,
+        name: &str,
+        body: &[NodeId],
+        ret: &AtomType,
+    ) -> Result<AtomType, ErrKind> {
+        let int = AtomKind::Basic(BasicType::Int);
+        let float = AtomKind::Basic(BasicType::Float);
+
+        let mut ty = AtomType {
+            kind: AtomKind::Basic(BasicType::Void),
+            details: None,
+        };
+
+        for (i, possible) in get_body_types(&self.ast, body).iter().enumerate() {
+            let possible = self.resolve(possible);
+            if i == 0 {
+                ty = possible;
+                continue;
+            }
+
+            // int | float -> float
+            if (ty.kind == int && possible.kind == float)
+                || (ty.kind == float && possible.kind == int)
+            {
+                ty.kind = float.clone();
+                continue;
+            }
+
+            ty = match self.infer.unify(&ty, &possible) {
+                Ok(ty) => ty,
+                Err(_) => {
+                    err!(
+                        self,
+                        ErrKind::InvaildType,
+                        format!(
+                            "function {} returns both {ty} and {possible}",
+                            types::demangle(name)
+                        )
+                    );
+                }
+            };
+        }
+
+        // still a type variable if it only returns calls into blueprints being built, see recursion.rs
+        match self.infer.unify(ret, &ty) {
+            Ok(ty) => Ok(ty),
+            Err(_) => {
+                err!(
+                    self,
+                    ErrKind::InvaildType,
+                    format!(
+                        "invaild return type for function {}, expected {} got {ty}",
+                        types::demangle(name),
+                        self.resolve(ret)
+                    )
+                );
+            }
+        }
+    }
+
     pub fn blueprints(&mut self, blueprints: Vec<Blueprint>) -> Result<(), ErrKind> {
         let blueprints = &mut blueprints.clone();
 
@@ -196,20 +303,34 @@ impl Analyzer {
             }
 
             blueprint.args = params;
-            let ref_name = blueprint.name.val().clone();
+            let ref_name = blueprint.name.sym();
+            let qualified = self.own_blueprint(ref_name.as_str());
 
-            *blueprint.name.val_mut() = type_mangle(blueprint.name.val().clone(), types);
+            blueprint.name.rename(&type_mangle(qualified, types));
 
             let blueprint_ty = {
-                let get = self.env.get_ty(&ref_name);
+                let get = self.env.get_ty(ref_name);
+                // overloads are added to a blueprint of this scope, not to one that was imported
+                if let Some(AtomKind::Blueprint(imported)) = get.as_ref().map(|ty| &ty.kind) {
+                    if self.env.local(ref_name).is_none() {
+                        let module = self
+                            .blueprint_owner(&imported.name)
+                            .unwrap_or("another module");
+                        err!(
+                            self,
+                            ErrKind::VarAlreadyDeclared,
+                            format!("blueprint {ref_name} is already declared in {module}")
+                        );
+                    }
+                }
 
-                let name = blueprint.name.val().clone();
+                let name = blueprint.name.sym();
 
                 if get.is_none() {
                     // If the type is not found, create a new Blueprint type with the name
                     AtomType {
                         kind: AtomKind::Blueprint(BlueprintType {
-                            name: name.clone(),
+                            name: name.to_string(),
                             overloads: vec![name],
                         }),
                         details: None,
@@ -236,10 +357,13 @@ impl Analyzer {
                 ty: blueprint_ty,
                 value: None,
                 expected: None,
+                mutable: false,
             });
         }
 
-        self.env.blueprints.append(blueprints);
+        for blueprint in blueprints.drain(..) {
+            self.env.add_blueprint(blueprint);
+        }
 
         // for blueprint in blueprints {
         //     if blueprint.args.len() == 0 {
@@ -0,0 +1,384 @@
+// files loaded by use, every file is parsed and analyzed once and known by its canonical path
+//
+// a module is analyzed in two scopes above the root one: the first uses the namespaces of the modules it
+// imports and the second holds the names it declares. its namespace is a scope of its own with the names
+// it declares with pub, importers look names up in it instead of copying them. the top level code of a
+// module is its initializer, initializers run before the main file in the order their modules finished loading
+use std::collections::HashMap;
+use std::env::{current_exe, split_paths, var_os};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::err;
+use crate::err::{ATErr, ErrKind};
+use crate::parser::ast::{Ast, Blueprint, Expr, NodeId};
+use crate::parser::parse::Parse;
+use crate::parser::Parser;
+
+use super::*;
+use crate::enviroment::{Scope, ScopeId};
+
+#[derive(Debug, Clone)]
+pub struct Module {
+    pub name: String,         // the path the module was first used by
+    pub id: String,           // qualifies the blueprints it declares, see types::type_mangle
+    pub dir: PathBuf,         // uses in the module are searched here first
+    pub scopes: Vec<ScopeId>, // its import scope and its declaration scope
+    pub namespace: ScopeId,   // what importers see
+    pub private: Vec<String>, // blueprints, externs, atoms and consts declared without pub
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Modules {
+    workdir: PathBuf,
+    search: Vec<PathBuf>, // COVALENT_PATH then the bundled std dir
+    loaded: HashMap<PathBuf, usize>,
+    list: Vec<Module>,
+    loading: Vec<usize>,            // the chain of uses being loaded
+    owners: HashMap<String, usize>, // module.blueprint => the module declaring it
+    current: Option<usize>,         // the module being analyzed, None for the main file
+    pub inits: Vec<NodeId>,
+}
+
+impl Modules {
+    pub fn new(workdir: String) -> Self {
+        let mut search: Vec<PathBuf> = match var_os("COVALENT_PATH") {
+            Some(paths) => split_paths(&paths).collect(),
+            None => Vec::new(),
+        };
+        if let Some(exe) = current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(PathBuf::from))
+        {
+            search.push(exe.join("lib").join("std"));
+        }
+
+        let workdir = if workdir.is_empty() { "." } else { &workdir };
+        Self {
+            workdir: PathBuf::from(workdir),
+            search,
+            ..Default::default()
+        }
+    }
+
+    // the dirs a use is searched in, the dir of the module using it first
+    fn dirs(&self) -> Vec<PathBuf> {
+        let dir = match self.current {
+            Some(index) => self.list[index].dir.clone(),
+            None => self.workdir.clone(),
+        };
+
+        let mut dirs = vec![dir];
+        for search in [&self.workdir].into_iter().chain(&self.search) {
+            if !dirs.contains(search) {
+                dirs.push(search.clone());
+            }
+        }
+        dirs
+    }
+
+    // the module that declares name without pub, if it isn't the one being analyzed
+    pub fn private_to(&self, name: &str) -> Option<&str> {
+        self.list
+            .iter()
+            .enumerate()
+            .find(|(index, module)| {
+                Some(*index) != self.current && module.private.iter().any(|p| p == name)
+            })
+            .map(|(_, module)| module.name.as_str())
+    }
+
+    // the file stem as a C identifier, with the index of the module if another module has it
+    fn module_id(&self, file: &Path) -> String {
+        let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+        let id: String = stem
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        match self.list.iter().any(|module| module.id == id) {
+            true => format!("{id}_{}", self.list.len()),
+            false => id,
+        }
+    }
+}
+
+impl Analyzer {
+    // use "path" loads the module once and imports its declarations into the current scope
+    pub fn analyz_use(&mut self, node: NodeId) -> Result<(), ErrKind> {
+        let Expr::Use(path) = self.ast.expr(node).clone() else {
+            unreachable!("{node:?} is not a use")
+        };
+        // errors are reported at the use, the module being loaded moves the position
+        let pos = self.ast.pos(node);
+        (self.line, self.column) = pos;
+
+        let dirs = self.modules.dirs();
+        let Some(file) = dirs
+            .iter()
+            .map(|dir| dir.join(&path))
+            .find(|file| file.is_file())
+        else {
+            let dirs: Vec<String> = dirs
+                .iter()
+                .map(|dir| format!("{}", dir.display()))
+                .collect();
+            err!(
+                self,
+                ErrKind::Module,
+                format!(
+                    "cannot find module {path}, searched in: {}",
+                    dirs.join(", ")
+                )
+            );
+        };
+        let file = fs::canonicalize(&file).unwrap_or(file);
+
+        let index = match self.modules.loaded.get(&file) {
+            Some(&index) => {
+                if let Some(start) = self.modules.loading.iter().position(|m| *m == index) {
+                    let mut chain: Vec<&str> = self.modules.loading[start..]
+                        .iter()
+                        .map(|m| self.modules.list[*m].name.as_str())
+                        .collect();
+                    chain.push(&path);
+                    err!(
+                        self,
+                        ErrKind::Module,
+                        format!("import cycle: {}", chain.join(" -> "))
+                    );
+                }
+                index
+            }
+            None => self.load_module(path, file)?,
+        };
+
+        (self.line, self.column) = pos;
+        self.import_module(index)
+    }
+
+    fn load_module(&mut self, name: String, file: PathBuf) -> Result<usize, ErrKind> {
+        let source = match fs::read_to_string(&file) {
+            Ok(source) => source,
+            Err(e) => {
+                err!(
+                    self,
+                    ErrKind::Module,
+                    format!("cannot read module {name}, {e}")
+                );
+            }
+        };
+        // the module is parsed into the same ast as the main file
+        let mut parser = Parser::with_ast(source, std::mem::take(&mut self.ast));
+        let nodes = parser.parse_prog();
+        self.ast = std::mem::take(&mut parser.ast);
+        let private = private_names(&self.ast, &nodes, &parser.functions);
+
+        let index = self.modules.list.len();
+        let imports = self.env.new_scope();
+        let namespace = self.env.new_scope();
+        let id = self.modules.module_id(&file);
+        self.modules.list.push(Module {
+            name,
+            id,
+            dir: file.parent().map(PathBuf::from).unwrap_or_default(),
+            scopes: vec![imports],
+            namespace,
+            private,
+        });
+        self.modules.loaded.insert(file, index);
+        self.modules.loading.push(index);
+
+        // the blueprints being built belong to the importer
+        let recursion = std::mem::take(&mut self.recursion);
+        let body = self.in_module(index, |analyzer| {
+            let body = analyzer.analyz_module(nodes, parser.functions)?;
+            analyzer.resolve_body(&body)?;
+
+            analyzer.modules.list[index].scopes = analyzer.env.opened();
+            Ok(body)
+        });
+        self.recursion = recursion;
+        self.export_module(index);
+
+        // a block keeps the variables of the initializer to itself
+        self.modules.loading.pop();
+        let init = self.ast.push(
+            Expr::Block(body?),
+            AtomType {
+                kind: AtomKind::Basic(BasicType::Void),
+                details: None,
+            },
+        );
+        self.modules.inits.push(init);
+        Ok(index)
+    }
+
+    // analyzes a file in its import scope, then declares its names in a scope of their own
+    pub fn analyz_module(
+        &mut self,
+        nodes: Vec<NodeId>,
+        functions: Vec<Blueprint>,
+    ) -> Result<Vec<NodeId>, ErrKind> {
+        // uses come first so declarations can refer to what they import
+        let (uses, nodes): (Vec<NodeId>, Vec<NodeId>) = nodes
+            .into_iter()
+            .partition(|node| matches!(self.ast.expr(*node), Expr::Use(_)));
+        for node in uses {
+            self.analyz_use(node)?;
+        }
+
+        self.env.child();
+        let (mut body, nodes) = self.analyz_decls(nodes, functions)?;
+        body.append(&mut self.analyz_body(nodes, true)?);
+        Ok(body)
+    }
+
+    // runs f in the scopes of a module, instances added to the root scope are kept
+    fn in_module<T>(
+        &mut self,
+        index: usize,
+        f: impl FnOnce(&mut Self) -> Result<T, ErrKind>,
+    ) -> Result<T, ErrKind> {
+        let caller = self.env.open(&self.modules.list[index].scopes);
+        let current = self.modules.current.replace(index);
+        let res = f(self);
+
+        self.env.restore(caller);
+        self.modules.current = current;
+        res
+    }
+
+    // fills the namespace of a loaded module with the names it declares with pub
+    fn export_module(&mut self, index: usize) {
+        let module = &self.modules.list[index];
+        let scope = self.env.scope(*module.scopes.last().unwrap());
+        let public = |name: SymbolId| !module.private.iter().any(|p| p == name.as_str());
+
+        // the main of a module isn't the entry point of the files using it, only the one of the
+        // root file is
+        let main = SymbolId::intern("main");
+        let mut namespace = Scope::default();
+        for sym in scope
+            .symbols
+            .values()
+            .filter(|sym| !sym.is_variable() && public(sym.name) && sym.name != main)
+        {
+            // only the overloads declared with pub
+            let mut sym = sym.clone();
+            if let AtomKind::Blueprint(blueprint) = &mut sym.ty.kind {
+                blueprint.overloads.retain(|overload| {
+                    scope
+                        .blueprints
+                        .values()
+                        .any(|b| b.name.sym() == *overload && b.public)
+                });
+            }
+            namespace.symbols.insert(sym.name, sym);
+        }
+        for (id, blueprint) in &scope.blueprints {
+            if blueprint.public {
+                namespace.blueprints.insert(*id, blueprint.clone());
+            }
+        }
+        namespace.impls = scope.impls.clone();
+        namespace.aliases = scope.aliases.clone();
+
+        let id = module.namespace;
+        *self.env.scope_mut(id) = namespace;
+    }
+
+    // makes the namespace of a module visible in the current scope, names are looked up in it
+    // after the ones declared here
+    fn import_module(&mut self, index: usize) -> Result<(), ErrKind> {
+        let module = &self.modules.list[index];
+        let namespace = self.env.scope(module.namespace);
+        let clash = namespace.symbols.values().find(|sym| {
+            self.env
+                .local(sym.name)
+                .is_some_and(|declared| declared != *sym)
+        });
+
+        if let Some(sym) = clash {
+            err!(
+                self,
+                ErrKind::VarAlreadyDeclared,
+                format!("{} from {} is already declared", sym.name, module.name)
+            );
+        }
+        self.env.use_namespace(module.namespace);
+        Ok(())
+    }
+
+    // the name instances of a blueprint are mangled from, a blueprint declared in a module is
+    // qualified by it so its instances don't clash with the ones of another module
+    pub fn own_blueprint(&mut self, name: &str) -> String {
+        let Some(current) = self.modules.current else {
+            return name.to_string();
+        };
+        let qualified = format!("{}.{name}", self.modules.list[current].id);
+        self.modules.owners.insert(qualified.clone(), current);
+        qualified
+    }
+
+    // the module a blueprint was declared in, for the error about declaring an imported one
+    pub fn blueprint_owner(&self, blueprint: &str) -> Option<&str> {
+        let (name, _) = types::demangle_parts(blueprint)?;
+        let owner = *self.modules.owners.get(&name)?;
+        Some(&self.modules.list[owner].name)
+    }
+
+    // instances of a blueprint are analyzed in the scopes of the module declaring it
+    pub fn blueprint_module(&self, blueprint: &Blueprint) -> Option<usize> {
+        let (name, _) = types::demangle_parts(blueprint.name.val())?;
+        match self.modules.owners.get(&name) {
+            Some(owner) if Some(*owner) != self.modules.current => Some(*owner),
+            _ => None,
+        }
+    }
+
+    pub fn analyz_blueprint_in(
+        &mut self,
+        module: usize,
+        blueprint: Blueprint,
+        types: Vec<AtomType>,
+    ) -> Result<String, ErrKind> {
+        self.in_module(module, |analyzer| {
+            analyzer.analyz_blueprint(blueprint, types)
+        })
+    }
+}
+
+// the names of the items a module keeps to itself
+fn private_names(ast: &Ast, nodes: &[NodeId], functions: &[Blueprint]) -> Vec<String> {
+    // a blueprint is private if none of its overloads are pub
+    let mut private: Vec<String> = Vec::new();
+    for blueprint in functions {
+        let name = blueprint.name.val();
+        let public = functions.iter().any(|b| b.name.val() == name && b.public);
+        if !public && !private.iter().any(|p| p == name) {
+            private.push(name.to_string());
+        }
+    }
+
+    for node in nodes {
+        match ast.expr(*node) {
+            Expr::Extern {
+                name,
+                public: false,
+                ..
+            }
+            | Expr::Const {
+                name,
+                public: false,
+                ..
+            } => private.push(name.val().to_string()),
+            Expr::Atom {
+                name,
+                public: false,
+                ..
+            } => private.push(name.to_string()),
+            _ => (),
+        }
+    }
+    private
+}
@@ -0,0 +1,179 @@
+// blueprint instances that call each other are solved together
+//
+// instances being built form a stack, a call to an instance still on the stack (or waiting on one)
+// puts the caller in the same strongly connected component of the call graph, members of a component
+// wait until its first instance (the root) is done, by then every return in the component was unified
+// and the component is finished as a whole
+use crate::err;
+use crate::err::{ATErr, ErrKind};
+use crate::parser::ast::{Expr, Ident, NodeId};
+
+use super::*;
+
+#[derive(Debug, Clone)]
+pub struct Building {
+    mangle: String,
+    low: usize,   // lowest stack index of an instance this one calls into
+    depth: usize, // scope depth of its params, variables above it belong to callers
+}
+
+// an instance whose body and return type are analyzed but not resolved yet
+#[derive(Debug, Clone)]
+pub struct Instance {
+    pub mangle: String,
+    pub params: Vec<Ident>,
+    pub types: Vec<AtomType>,
+    pub ret: AtomType, // the return type tag if there is one
+    pub body: Vec<NodeId>,
+    pub public: bool,
+    pub pos: (u16, u16), // of the blueprint declaration
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Recursion {
+    building: Vec<Building>,
+    waiting: Vec<(usize, Instance)>, // members of a component whose root isn't done, with their low
+}
+
+impl Analyzer {
+    // starts building mangle, returns its stack index
+    pub fn enter_instance(&mut self, mangle: &str) -> usize {
+        let index = self.recursion.building.len();
+        self.recursion.building.push(Building {
+            mangle: mangle.to_string(),
+            low: index,
+            depth: self.env.depth(),
+        });
+        index
+    }
+
+    // scope depth of the instance being built, None in top level code
+    pub fn instance_depth(&self) -> Option<usize> {
+        self.recursion.building.last().map(|b| b.depth)
+    }
+
+    // records a call from the instance being built to an already known mangle
+    pub fn called(&mut self, mangle: &str) {
+        let rec = &mut self.recursion;
+        let low = match rec.building.iter().position(|b| b.mangle == mangle) {
+            Some(index) => index,
+            None => match rec.waiting.iter().find(|(_, inst)| inst.mangle == mangle) {
+                Some((low, _)) => *low,
+                None => return, // finished, or called from top level code
+            },
+        };
+
+        if let Some(caller) = rec.building.last_mut() {
+            caller.low = caller.low.min(low);
+        }
+    }
+
+    // done analyzing the body of the instance at index, finishes its component if it is the root
+    pub fn leave_instance(&mut self, index: usize, instance: Instance) -> Result<(), ErrKind> {
+        let member = self.recursion.building.pop().unwrap();
+        debug_assert_eq!(member.mangle, instance.mangle);
+
+        if member.low < index {
+            // callers see its return type as a type variable until the root is done
+            self.env.push_top_function(
+                SymbolId::intern(&instance.mangle),
+                FunctionType {
+                    return_type: Box::new(instance.ret.clone()),
+                    params: instance.types.clone(),
+                },
+            );
+            if let Some(caller) = self.recursion.building.last_mut() {
+                caller.low = caller.low.min(member.low);
+            }
+            self.recursion.waiting.push((member.low, instance));
+            return Ok(());
+        }
+
+        let mut component = vec![instance];
+        let waiting = std::mem::take(&mut self.recursion.waiting);
+        for (low, inst) in waiting {
+            if low >= index {
+                component.push(inst);
+            } else {
+                self.recursion.waiting.push((low, inst));
+            }
+        }
+
+        let names: Vec<String> = component
+            .iter()
+            .map(|inst| demangle(&inst.mangle))
+            .collect();
+        for instance in component {
+            self.finish_instance(instance, &names)?;
+        }
+        Ok(())
+    }
+
+    fn finish_instance(&mut self, instance: Instance, component: &[String]) -> Result<(), ErrKind> {
+        let Instance {
+            mangle,
+            mut params,
+            types,
+            ret,
+            body,
+            public,
+            pos,
+        } = instance;
+        // errors about the whole instance are reported at its blueprint
+        (self.line, self.column) = pos;
+
+        let ty = self.resolve(&ret);
+        if ty.has_vars() {
+            let name = demangle(&mangle);
+            let through = if component.len() > 1 {
+                let others: Vec<&str> = component
+                    .iter()
+                    .filter(|other| **other != name)
+                    .map(|other| other.as_str())
+                    .collect();
+                format!(" and {}", others.join(", "))
+            } else {
+                String::new()
+            };
+            err!(
+                self,
+                ErrKind::InvaildType,
+                format!("cannot infer return type of {name}, it only depends on itself{through}, add a ret that doesn't recurse or a return type tag")
+            );
+        }
+
+        self.resolve_body(&body)?;
+        conv_rets(&mut self.ast, &body, &ty);
+        self.check_flow(&mangle, &body, &ty)?;
+
+        for param in &mut params {
+            if let Ident::Typed(param_ty, _) = param {
+                **param_ty = self.resolve(param_ty);
+            }
+        }
+
+        let func_type = FunctionType {
+            return_type: Box::new(ty.clone()),
+            params: types,
+        };
+        self.env
+            .push_top_function(SymbolId::intern(&mangle), func_type.clone());
+
+        let func = self.ast.push(
+            Expr::Func {
+                ret: Box::new(ty),
+                name: SymbolId::intern(&mangle),
+                args: params,
+                body,
+                public,
+            },
+            AtomType {
+                kind: AtomKind::Function(func_type),
+                details: None,
+            },
+        );
+        self.ast.set_pos(func, pos);
+        self.functions.push(func);
+        Ok(())
+    }
+}
@@ -7,6 +7,15 @@ use crate::{
     types::{self, AtomKind, AtomType, BasicType},
 };
 
+// items without pub are only used by the program itself
+fn linkage(public: bool) -> &'static str {
+    if public {
+        ""
+    } else {
+        "static "
+    }
+}
+
 impl Codegen {
     #[inline]
     fn call_one(&self, name: &str, arg: String) -> String {
@@ -23,12 +32,13 @@ impl Codegen {
             if let IROp::Import(_, module, _, _) = op {
                 self.module.include(module);
                 ir.remove(0);
-            } else if let IROp::Def(ret, name, args, body) = op {
+            } else if let IROp::Def(ret, name, args, body, public) = op {
                 self.bond_fn(
                     name,
                     args.into_iter().map(|i| i.tuple().clone()).collect(),
                     ret,
                     body,
+                    public,
                 );
 
                 ir.remove(0);
@@ -37,15 +47,18 @@ impl Codegen {
             }
         }
 
-        self.bond_fn(
-            "main".to_string(),
-            Vec::new(),
-            AtomType {
-                kind: AtomKind::Basic(BasicType::Int),
-                details: None,
-            },
-            ir,
-        );
+        // the runtime is initialized before the top level code, which ends by calling the entry point if there is one
+        let mut emiter = self.emiter();
+        emiter.emit_header("int main(int argc, char **argv) {".to_string());
+        emiter.embed(Emit::Line(self.call("__init__", Vec::new())));
+        for op in ir {
+            let emit = self.bond(op);
+
+            emiter.embed(emit);
+        }
+        emiter.embed(Emit::Line("return 0".to_string()));
+        emiter.end();
+        self.module.func(emiter.finish());
 
         self.module.finish()
     }
@@ -55,9 +68,11 @@ impl Codegen {
         args: Vec<(AtomType, String)>,
         ret: AtomType,
         body: Vec<IROp>,
+        public: bool,
     ) {
-        let ty = type_to_c(ret);
+        let ty = format!("{}{}", linkage(public), type_to_c(ret));
         let args = types_to_cnamed(args);
+        self.module.prototype(format!("{ty} {name}({args});"));
         let mut emiter = self.emiter();
         emiter.emit_header(format!("{} {}({}) {{", ty, name, args));
         for op in body {
@@ -76,18 +91,58 @@ impl Codegen {
         Emit::None
     }
 
+    // emits the atom struct and its constructor
+    fn bond_atom(&mut self, name: String, fields: Vec<Ident>, public: bool) {
+        let fields: Vec<(AtomType, String)> = fields.into_iter().map(|f| f.tuple()).collect();
+        let name = types::atom_mangle(&name);
+
+        let mut def = vec![format!("struct {name} {{")];
+        for (ty, field) in &fields {
+            def.push(format!("\t{} {field};", type_to_c(ty.clone())));
+        }
+        def.push("};".to_string());
+
+        let mut emiter = self.emiter();
+        emiter.emit_header(format!(
+            "{}{name}* {name}dotnew({}) {{",
+            linkage(public),
+            types_to_cnamed(fields.clone())
+        ));
+        emiter.emit(format!("{name}* self = GC_malloc(sizeof({name}))"));
+        for (_, field) in &fields {
+            emiter.emit(format!("self->{field} = {field}"));
+        }
+        emiter.emit("return self");
+        emiter.end();
+
+        def.append(&mut emiter.finish());
+        self.module.type_add(&name, def);
+    }
+
     pub fn bond(&mut self, op: IROp) -> Emit {
         match op {
-            IROp::Def(ret, name, args, body) => {
+            IROp::Def(ret, name, args, body, public) => {
                 self.bond_fn(
                     name,
                     args.into_iter().map(|i| i.tuple().clone()).collect(),
                     ret,
                     body,
+                    public,
                 );
             }
 
             IROp::Extern(ret, name, params) => return self.bond_extern(ret, name, params),
+            IROp::Atom(name, fields, public) => self.bond_atom(name, fields, public),
+            IROp::New(ty, count) => {
+                let mut args = self.pop_amount(count);
+                args.reverse();
+
+                let AtomKind::Atom(ref atom) = ty.kind else {
+                    unreachable!("new of {ty}, only atoms are constructed")
+                };
+                let new = self.call(&format!("{}dotnew", types::atom_mangle(&atom.name)), args);
+                self.push(Item::Expr(ty, new));
+            }
 
             IROp::Alloc(_, _) => (),
             IROp::Dealloc(ty, name) => {
@@ -110,15 +165,18 @@ impl Codegen {
 
             IROp::Store(ty, name) => {
                 let val = self.pop_str();
-                let tyc = type_to_c(ty.clone());
+                let tyc = match ty.kind {
+                    // part bindings, T const so pointers themselves are constant
+                    AtomKind::Atom(ref atom) if atom.name == types::Const.name => {
+                        format!("{} const", type_to_c(atom.generics[0].clone()))
+                    }
+                    _ => type_to_c(ty.clone()),
+                };
 
                 return Emit::Line(format!("{} {} = {}", tyc, name, val));
             }
 
-            IROp::Load(ty, name) => {
-                let name = self.get_var(name);
-                self.push(Item::Var(ty, name));
-            }
+            IROp::Load(ty, name) => self.push(Item::Var(ty, name)),
 
             IROp::LoadProp(ty, name) => {
                 let id = self.pop_str();
@@ -138,7 +196,9 @@ impl Codegen {
             IROp::Call(ty, count) => {
                 let arg_count = count;
                 let name = self.pop_str();
-                let args = self.pop_amount(arg_count).join(", ");
+                let mut args = self.pop_amount(arg_count);
+                args.reverse();
+                let args = args.join(", ");
                 let call = format!("{}({})", name, args);
                 if &ty.kind == &AtomKind::Basic(BasicType::Void) {
                     // our compiler only insert a line when the stack is empty, void functions doesnt push anything to the stack
@@ -147,36 +207,42 @@ impl Codegen {
                     self.push(Item::Expr(ty, call));
                 }
             }
-            IROp::While(body) => return self.bond_while(body),
+            IROp::While(cond, body) => return self.bond_while(cond, body),
             IROp::If(_, body, alt) => return self.bond_if(body, alt),
 
             IROp::Conv(into, from) => {
                 self.bond_conv(into, from);
             }
 
+            IROp::Is(ty) => {
+                let item = self.pop_str();
+                let desc = self.desc_ref(&ty);
+                let is = self.call("__is__", vec![item, desc]);
+                self.push(Item::Expr(get_op_type(&IROp::Is(ty)), is));
+            }
+
+            IROp::TypeOf => {
+                let item = self.pop_str();
+                let name = self.call_one("__typename__", item);
+                self.push(Item::Expr(get_op_type(&IROp::TypeOf), name));
+            }
+
+            IROp::Args => {
+                let args = self.call("__args__", vec!["argc".to_string(), "argv".to_string()]);
+                self.push(Item::Expr(get_op_type(&IROp::Args), args));
+            }
+
             IROp::Pop => {
                 if self.stack.len() > 0 {
                     return Emit::Line(self.pop_str());
                 }
             }
 
-            IROp::Set(ty) => {
+            // variables never change type, analysis converts the value into the variable type
+            IROp::Set(_) => {
                 let val = self.pop_str();
-
                 let name = self.pop_str();
-                let tyc = type_to_c(ty.clone());
-
-                let var = self.variables.get(&name);
-                if var.is_some() {
-                    if var.unwrap().1 != ty {
-                        let name = self.var(name, ty);
-
-                        return Emit::Line(format!("{} {} = {}", tyc, name, val));
-                    }
-                } else {
-                    let name = self.get_var(name);
-                    return Emit::Line(format!("{} = {}", name, val));
-                }
+                return Emit::Line(format!("{} = {}", name, val));
             }
             IROp::Ret(_) => {
                 let val = self.pop_str();
@@ -186,8 +252,12 @@ impl Codegen {
         }
         Emit::None
     }
-    fn bond_while(&mut self, body: Vec<IROp>) -> Emit {
+    fn bond_while(&mut self, cond: Vec<IROp>, body: Vec<IROp>) -> Emit {
         let mut emiter = self.emiter();
+        for op in cond {
+            let emit = self.bond(op);
+            emiter.embed(emit);
+        }
         let cond = self.pop_str();
 
         emiter.emit_header(format!("while ({}) {{", cond));
@@ -306,7 +376,17 @@ impl Codegen {
                 ),
             )
         } else {
+            // int divisions go through the runtime to check for division by zero
+            let ty = self.borrow().get_ty();
             match op {
+                IROp::Div(_) | IROp::Mod(_) if ty.kind == AtomKind::Basic(BasicType::Int) => {
+                    let ops = vec![self.pop_str(), self.pop_str()];
+                    let name = match op {
+                        IROp::Div(_) => "__idiv__",
+                        _ => "__imod__",
+                    };
+                    Item::Expr(ty, self.call(name, ops))
+                }
                 IROp::Add(_) => self.binary("+"),
                 IROp::Sub(_) => self.binary("-"),
                 IROp::Mul(_) => self.binary("*"),
@@ -326,36 +406,171 @@ impl Codegen {
 
     fn bond_conv(&mut self, into: AtomType, from: AtomType) {
         let item = self.pop_str();
-        let conv = match &into.kind {
-            &AtomKind::Dynamic => match from.kind {
+        if into == from {
+            return self.push(Item::Expr(into, item));
+        }
+
+        let conv = match (&into.kind, &from.kind) {
+            (AtomKind::Dynamic, from_kind) => match from_kind {
                 AtomKind::Basic(BasicType::Int) => self.call_one("__int__", item),
                 AtomKind::Basic(BasicType::Float) => self.call_one("__float__", item),
                 AtomKind::Basic(BasicType::Bool) => self.call_one("__bool__", item),
 
                 AtomKind::Atom(ref atom) if atom == &*types::Str => self.call_one("__str__", item),
-
-                AtomKind::Dynamic => item,
+                AtomKind::Atom(ref atom) if atom.name == types::List.name => {
+                    let desc = self.desc_ref(&from);
+                    self.call("__list__", vec![item, desc])
+                }
+                AtomKind::Atom(ref atom)
+                    if atom.name != types::Back.name && atom.name != types::Const.name =>
+                {
+                    let desc = self.desc_ref(&from);
+                    self.call("__atom__", vec![item, desc])
+                }
+                AtomKind::Function(_) => {
+                    let desc = self.desc_ref(&from);
+                    self.call("__fn__", vec![item, desc])
+                }
                 _ => todo!("add conv dynamic from {}", from),
             },
 
-            &AtomKind::Basic(BasicType::Float) => match from.kind {
-                AtomKind::Basic(BasicType::Int) => format!("(float){item}"),
-                _ => panic!(),
-            },
-
-            &AtomKind::Atom(ref atom) if atom == &*types::Str => match from.kind {
-                AtomKind::Basic(BasicType::Int) => format!("itos({item})"),
-                _ => panic!(),
-            },
+            // checked downcasts
+            (AtomKind::Basic(BasicType::Int), AtomKind::Dynamic) => {
+                self.call_one("__dyntoi__", item)
+            }
+            (AtomKind::Basic(BasicType::Float), AtomKind::Dynamic) => {
+                self.call_one("__dyntof__", item)
+            }
+            (AtomKind::Basic(BasicType::Bool), AtomKind::Dynamic) => {
+                self.call_one("__dyntob__", item)
+            }
+            (AtomKind::Atom(ref atom), AtomKind::Dynamic) if atom == &*types::Str => {
+                self.call_one("__dyntos__", item)
+            }
+            (AtomKind::Atom(_), AtomKind::Dynamic) => {
+                let desc = self.desc_ref(&into);
+                format!(
+                    "(({}){})",
+                    type_to_c(into.clone()),
+                    self.call("__dyntop__", vec![item, desc])
+                )
+            }
 
-            AtomKind::Atom(ref atom) if atom == &*types::Const => {
-                format!("({item})")
+            (AtomKind::Basic(BasicType::Bool), AtomKind::Basic(_)) => format!("({item} != 0)"),
+            (AtomKind::Basic(basic), AtomKind::Basic(_)) => {
+                format!(
+                    "({}){item}",
+                    type_to_c(AtomType {
+                        kind: AtomKind::Basic(*basic),
+                        details: None
+                    })
+                )
             }
-            _ => match &from.kind {
+
+            (AtomKind::Atom(ref atom), from_kind) if atom == &*types::Str => match from_kind {
+                AtomKind::Basic(BasicType::Int) => self.call_one("itos", item),
+                AtomKind::Basic(BasicType::Float) => self.call_one("ftos", item),
+                AtomKind::Basic(BasicType::Bool) => self.call_one("btos", item),
+                AtomKind::Atom(ref atom) if atom.name == types::Back.name => {
+                    self.call_one("__strnew__", item)
+                }
                 _ => todo!("add conv into {} from {}", into, from),
             },
+
+            (AtomKind::Basic(BasicType::Int), AtomKind::Atom(ref atom)) if atom == &*types::Str => {
+                self.call_one("__stoi__", item)
+            }
+            (AtomKind::Basic(BasicType::Float), AtomKind::Atom(ref atom))
+                if atom == &*types::Str =>
+            {
+                self.call_one("__stof__", item)
+            }
+            (AtomKind::Basic(BasicType::Bool), AtomKind::Atom(ref atom))
+                if atom == &*types::Str =>
+            {
+                self.call_one("__stob__", item)
+            }
+
+            (AtomKind::Atom(ref atom), _) if atom.name == types::Back.name => {
+                self.call_one("strtocstr", item)
+            }
+
+            (AtomKind::Atom(ref atom), _) if atom.name == types::Const.name => format!("({item})"),
+            (_, AtomKind::Atom(ref atom)) if atom.name == types::Const.name => format!("({item})"),
+            // a List(T, N) is a List(T) with a size known at compile time
+            _ if types::is_list(&into) && types::is_list(&from) => item,
+            _ => todo!("add conv into {} from {}", into, from),
         };
 
         self.push(Item::Expr(into, conv));
     }
+
+    // a pointer to the runtime descriptor of ty, declares it and the descriptors it depends on
+    fn desc_ref(&mut self, ty: &AtomType) -> String {
+        let code = types::ty_code(ty);
+        let name = format!("__desc_{code}");
+
+        let builtin = match &ty.kind {
+            AtomKind::Basic(BasicType::Int | BasicType::Float | BasicType::Bool) => true,
+            AtomKind::Dynamic => true,
+            AtomKind::Atom(atom) => atom == &*types::Str,
+            _ => false,
+        };
+        if builtin || !self.module.desc_declare(&name) {
+            return format!("&{name}");
+        }
+
+        let desc = |kind: &str, item: &str| {
+            format!(
+                "const TypeDesc {name} = {{{kind}, \"{}\", \"{code}\", {item}",
+                ty.kind
+            )
+        };
+        let def = match &ty.kind {
+            AtomKind::Atom(atom) if atom.name == types::List.name => {
+                let item = self.desc_ref(&atom.generics[0]);
+                vec![desc("LIST_TYPE", &item) + "};"]
+            }
+
+            AtomKind::Function(func) => {
+                let ret = self.desc_ref(&func.return_type);
+                vec![desc("FN_TYPE", &ret) + "};"]
+            }
+
+            AtomKind::Atom(atom) => {
+                let mut names = Vec::new();
+                let mut fields = Vec::new();
+                let mut offsets = Vec::new();
+                for (field, ty) in &atom.fields {
+                    names.push(format!("\"{field}\""));
+                    fields.push(self.desc_ref(ty));
+                    offsets.push(format!(
+                        "offsetof({}, {field})",
+                        types::atom_mangle(&atom.name)
+                    ));
+                }
+
+                let count = names.len();
+                if count == 0 {
+                    vec![desc("ATOM_TYPE", "NULL") + "};"]
+                } else {
+                    vec![
+                        format!("char *{name}_names[] = {{{}}};", names.join(", ")),
+                        format!(
+                            "const TypeDesc *{name}_fields[] = {{{}}};",
+                            fields.join(", ")
+                        ),
+                        format!("size_t {name}_offsets[] = {{{}}};", offsets.join(", ")),
+                        desc("ATOM_TYPE", "NULL")
+                            + &format!(", {count}, {name}_names, {name}_fields, {name}_offsets}};"),
+                    ]
+                }
+            }
+
+            _ => todo!("no runtime descriptor for {}", ty),
+        };
+
+        self.module.desc_add(def);
+        format!("&{name}")
+    }
 }
@@ -6,8 +6,6 @@ use crate::types::{self, AtomKind, AtomType, BasicType};
 
 use std::cell::RefCell;
 
-use std::collections::HashMap;
-
 use std::{fmt::Display, fs, process::Command};
 
 pub fn compile(config: &CompilerConfig, ir: Vec<IROp>) {
@@ -27,16 +25,19 @@ pub fn compile(config: &CompilerConfig, ir: Vec<IROp>) {
     fs::write(&outpath, code).expect(
         format!("err writing to /tmp/covalent make sure covalent can access that path!").as_str(),
     );
-    let _ = Command::new("gcc")
+    let status = Command::new("gcc")
         .arg("-Wno-implicit-function-declaration")
         .arg(format!("-I{}", &config.libdir))
         .arg(format!("-o{}", &config.output))
         .arg(outpath)
         .arg(format!("{}/runtime.o", &config.libdir))
         .arg(format!("{}/gc.o", &config.libdir))
-        .spawn()
-        .unwrap()
-        .wait();
+        .status()
+        .expect("gcc not installed");
+    if !status.success() {
+        println!("gcc failed to compile {}", config.output);
+        std::process::exit(1);
+    }
 }
 
 pub fn type_to_c(ty: AtomType) -> String {
@@ -64,6 +65,7 @@ pub fn type_to_c(ty: AtomType) -> String {
             let T = &atom.generics[0];
             return format!("const {}", type_to_c(T.clone()));
         }
+        AtomKind::Atom(ref atom) => return format!("{}*", types::atom_mangle(&atom.name)), // user atoms
 
         _ => todo!("{:?}", ty),
     }
@@ -175,7 +177,12 @@ impl Emiter {
 #[derive(Debug, Clone)]
 pub struct Module {
     includes: Vec<String>,
+    typedefs: Vec<String>, // forward declarations so atoms can refer to each other
+    types: Vec<Vec<String>>, // atom structs and their constructors
+    descs: Vec<String>,    // runtime type descriptors of boxed Dynamic values
+    desc_names: Vec<String>,
     externs: Vec<String>,
+    prototypes: Vec<String>, // so functions can call ones defined after them
     functions: Vec<Vec<String>>,
     pub col: RefCell<u32>,
 }
@@ -195,7 +202,12 @@ impl Module {
     pub fn new() -> Self {
         Self {
             includes: Vec::new(),
+            typedefs: Vec::new(),
+            types: Vec::new(),
+            descs: Vec::new(),
+            desc_names: Vec::new(),
             externs: Vec::new(),
+            prototypes: Vec::new(),
             functions: Vec::new(),
             col: RefCell::new(0),
         }
@@ -213,6 +225,29 @@ impl Module {
         }
     }
 
+    pub fn type_add(&mut self, name: &String, def: Vec<String>) {
+        self.typedefs.push(format!("typedef struct {name} {name};"));
+        self.types.push(def);
+    }
+
+    // forward declares a descriptor, returns false if it was already declared
+    pub fn desc_declare(&mut self, name: &str) -> bool {
+        if self.desc_names.iter().any(|desc| desc == name) {
+            return false;
+        }
+        self.desc_names.push(name.to_string());
+        self.descs.push(format!("extern const TypeDesc {name};"));
+        true
+    }
+
+    pub fn desc_add(&mut self, mut def: Vec<String>) {
+        self.descs.append(&mut def);
+    }
+
+    pub fn prototype(&mut self, prototype: String) {
+        self.prototypes.push(prototype);
+    }
+
     pub fn func(&mut self, func: Vec<String>) {
         self.functions.push(func);
     }
@@ -220,10 +255,17 @@ impl Module {
     pub fn finish(&mut self) -> String {
         let mut func_lines: Vec<String> = (&self.functions).join(&String::from("\n\n"));
         self.functions.clear();
+        let mut type_lines: Vec<String> = self.types.join(&String::from("\n"));
+        self.types.clear();
         let mut lines = Vec::new();
         lines.append(&mut self.includes);
 
+        lines.append(&mut self.typedefs);
+        lines.append(&mut type_lines);
+        lines.append(&mut self.descs);
+
         lines.append(&mut self.externs);
+        lines.append(&mut self.prototypes);
 
         lines.append(&mut func_lines);
         let code = lines.join("\n");
@@ -243,8 +285,7 @@ impl Module {
 #[derive(Debug, Clone)]
 pub struct Codegen {
     stack: Vec<Item>,
-    variables: HashMap<String, (i32, AtomType)>, // c doesnt allow redeclaration of vars with different types
-    pub module: Module,                          // code we are generating
+    pub module: Module, // code we are generating
 }
 
 impl Codegen {
@@ -263,7 +304,12 @@ impl Codegen {
         match item {
             Item::Const(con) => match con {
                 Literal::Int(i) => i.to_string(),
-                Literal::Float(f) => f.to_string(),
+                // a float literal keeps its point so 1.0 / 0.0 isn't an int division
+                Literal::Float(f) if f.is_nan() => "NAN".to_string(),
+                Literal::Float(f) if f.is_infinite() => {
+                    format!("{}INFINITY", if f < 0.0 { "-" } else { "" })
+                }
+                Literal::Float(f) => format!("{f:?}"),
                 Literal::Str(s) => format!("__strnew__(\"{}\")", s),
                 Literal::Bool(b) => (b as u8).to_string(),
             },
@@ -272,13 +318,14 @@ impl Codegen {
             Item::List(ty, size) => {
                 let mut items = self.pop_amount(size);
                 items.reverse();
-                let new = items.join(", ");
-                format!(
-                    "__listnew__({}, {}, {})",
-                    format!("sizeof({})", type_to_c(ty)),
-                    size,
-                    new
-                )
+                let boxed = ty.kind == AtomKind::Dynamic;
+                let ty = type_to_c(ty);
+                let items = if size == 0 {
+                    "NULL".to_string()
+                } else {
+                    format!("({ty}[]){{{}}}", items.join(", "))
+                };
+                format!("__listnew__(sizeof({ty}), {boxed}, {size}, {items})")
             }
         }
     }
@@ -297,38 +344,10 @@ impl Codegen {
     pub fn new() -> Self {
         Self {
             stack: Vec::new(),
-            variables: HashMap::new(),
             module: Module::new(),
         }
     }
 
-    pub fn get_var(&mut self, name: String) -> String {
-        let count = self.variables.get(&name);
-        if count.is_none() {
-            return name;
-        }
-        let count = count.unwrap().0;
-
-        if count == 0 {
-            name
-        } else {
-            name + count.to_string().as_str()
-        }
-    }
-
-    pub fn var(&mut self, name: String, ty: AtomType) -> String {
-        let count = self.variables.get(&name);
-        if count.is_none() {
-            self.variables.insert(name.clone(), (0, ty));
-            name
-        } else {
-            let count = count.unwrap().0.to_owned() + 1;
-            self.variables.remove(&name);
-            self.variables.insert(name.clone(), (count, ty));
-            self.get_var(name)
-        }
-    }
-
     pub fn emiter(&self) -> Emiter {
         Emiter::new(self.module.col.clone())
     }
@@ -1,5 +1,8 @@
 #include "std.h"
 #include "stdio.h"
+#include <ctype.h>
+#include <errno.h>
+#include <limits.h>
 #include <stdarg.h>
 #include <stdbool.h>
 #include <stdlib.h>
@@ -10,6 +13,17 @@ void *GC_realloc(void *, unsigned int);
 void GC_free(void *);
 void GC_init();
 
+char *__tyname__(TYPE ty);
+Obj __operr__(char *op, Obj a);
+List *__listadd__(List *a, List *b);
+_Bool __objeq__(Obj a, Obj b);
+_Bool __objcomp__(Obj a, Obj b);
+_Bool __objecomp__(Obj a, Obj b);
+_Bool __unify__(Obj *a, Obj *b);
+_Bool __mixedeq__(Obj a, Obj b);
+_Bool __mixedcomp__(Obj a, Obj b);
+_Bool __mixedecomp__(Obj a, Obj b);
+
 #define DEFOP_N(name, op)                                                      \
   Obj __##name##__(Obj a, Obj b) {                                             \
     __conv__(&a, &b);                                                          \
@@ -20,7 +34,7 @@ void GC_init();
     case FLOAT_TYPE:                                                           \
       return __float__(a.val.f op b.val.f);                                    \
     default:                                                                   \
-      return __NaN__();                                                        \
+      return __operr__(#name, a);                                              \
     }
 #define DEFOP_NF(name, op)                                                     \
   Obj __##name##__(Obj a, Obj b) {                                             \
@@ -28,9 +42,21 @@ void GC_init();
     TYPE kind = a.kind;                                                        \
     switch (kind) {                                                            \
     case INT_TYPE:                                                             \
-      return __int__(a.val.i op b.val.i);                                      \
+      return __int__(__i##name##__(a.val.i, b.val.i));                         \
     default:                                                                   \
-      return __NaN__();                                                        \
+      return __operr__(#name, a);                                              \
+    }
+#define DEFOP_DIV(name, op)                                                    \
+  Obj __##name##__(Obj a, Obj b) {                                             \
+    __conv__(&a, &b);                                                          \
+    TYPE kind = a.kind;                                                        \
+    switch (kind) {                                                            \
+    case INT_TYPE:                                                             \
+      return __int__(__i##name##__(a.val.i, b.val.i));                         \
+    case FLOAT_TYPE:                                                           \
+      return __float__(a.val.f op b.val.f);                                    \
+    default:                                                                   \
+      return __operr__(#name, a);                                              \
     }
 
 #define DEFOP_LOGICAL(name, op)                                                \
@@ -41,12 +67,14 @@ void GC_init();
     case BOOL_TYPE:                                                            \
       return __int__(a.val.b op b.val.b);                                      \
     default:                                                                   \
-      return __NaN__();                                                        \
+      return __operr__(#name, a);                                              \
     }
 
 #define DEFOP_BOOL(name, op)                                                   \
   _Bool __##name##__(Obj a, Obj b) {                                           \
-    __conv__(&a, &b);                                                          \
+    if (!__unify__(&a, &b)) {                                                  \
+      return __mixed##name##__(a, b);                                          \
+    }                                                                          \
     TYPE kind = a.kind;                                                        \
     switch (kind) {                                                            \
     case INT_TYPE:                                                             \
@@ -57,6 +85,10 @@ void GC_init();
       return a.val.b op b.val.b;                                               \
     case STR_TYPE:                                                             \
       return __str##name##__(a.val.s, b.val.s);                                \
+    case LIST_TYPE:                                                            \
+    case ATOM_TYPE:                                                            \
+    case FN_TYPE:                                                              \
+      return __obj##name##__(a, b);                                            \
     default:                                                                   \
       return 0;                                                                \
     }
@@ -71,8 +103,10 @@ void GC_init();
       return __float__(a.val.f op b.val.f);                                    \
     case STR_TYPE:                                                             \
       return __str__(__str##name##__(a.val.s, b.val.s));                       \
+    case LIST_TYPE:                                                            \
+      return __list__(__list##name##__(a.val.p, b.val.p), a.desc);             \
     default:                                                                   \
-      return __NaN__();                                                        \
+      return __operr__(#name, a);                                              \
     }
 
 #define DEF(type, name, op)                                                    \
@@ -84,7 +118,24 @@ Obj __NaN__() {
   return nan;
 }
 
-void __conv__(Obj *a, Obj *b) {
+// the name of the type held by a Dynamic
+char *__objname__(Obj o) {
+  if (o.desc != NULL) {
+    return o.desc->name;
+  }
+  return __tyname__(o.kind);
+}
+
+Obj __operr__(char *op, Obj a) {
+  printf("covalent runtime error: unsupported operand for %s: Dynamic holding "
+         "%s\n",
+         op, __objname__(a));
+  exit(9);
+}
+
+// makes a and b hold the same type, an int mixed with a float is converted into
+// a float, false if they hold different types
+_Bool __unify__(Obj *a, Obj *b) {
   TYPE a_ty = (a)->kind;
   TYPE b_ty = (b)->kind;
 
@@ -92,41 +143,115 @@ void __conv__(Obj *a, Obj *b) {
   Value b_val = b->val;
 
   if (a_ty == b_ty) {
-    return;
-  }
-  if (a_ty == FLOAT_TYPE && b_ty == INT_TYPE) {
+    return a->desc == NULL || strcmp(a->desc->code, b->desc->code) == 0;
+  } else if (a_ty == FLOAT_TYPE && b_ty == INT_TYPE) {
     *b = __float__((float)b_val.i);
+    return true;
   } else if (a_ty == INT_TYPE && b_ty == FLOAT_TYPE) {
     *a = __float__((float)a_val.i);
-  } else {
-    err("cannot conv balance a and b", 5);
+    return true;
   }
+  return false;
 }
 
-void writeln(Obj arg) {
+void __conv__(Obj *a, Obj *b) {
+  if (!__unify__(a, b)) {
+    printf("covalent runtime error: cannot operate on %s and %s\n",
+           __objname__(*a), __objname__(*b));
+    exit(5);
+  }
+}
+
+// values of different types are never equal and can't be compared
+_Bool __mixedeq__(Obj a, Obj b) { return false; }
+
+_Bool __mixedcomp__(Obj a, Obj b) {
+  __conv__(&a, &b);
+  return false;
+}
+
+_Bool __mixedecomp__(Obj a, Obj b) {
+  __conv__(&a, &b);
+  return false;
+}
+
+Obj __box__(const TypeDesc *desc, void *at) {
+  switch (desc->kind) {
+  case INT_TYPE:
+    return __int__(*(int *)at);
+  case FLOAT_TYPE:
+    return __float__(*(float *)at);
+  case BOOL_TYPE:
+    return __bool__(*(_Bool *)at);
+  case STR_TYPE:
+    return __str__(*(Str **)at);
+  case DYN_TYPE:
+    return *(Obj *)at;
+  default:
+    return (Obj){desc->kind, (Value){.p = *(void **)at}, desc};
+  }
+}
+
+void __write__(Obj arg) {
   TYPE ty = arg.kind;
   switch (ty) {
   case INT_TYPE: {
-    printf("%d\n", arg.val.i);
+    printf("%d", arg.val.i);
     break;
   }
 
   case FLOAT_TYPE: {
-    printf("%f\n", arg.val.f);
+    printf("%f", arg.val.f);
     break;
   }
   case STR_TYPE: {
-    printf("%.*s\n", arg.val.s->len, arg.val.s->val);
+    printf("%.*s", arg.val.s->len, arg.val.s->val);
     break;
   }
   case BOOL_TYPE: {
     if (arg.val.b == 0) {
-      printf("false\n");
+      printf("false");
     } else {
-      printf("true\n");
+      printf("true");
     }
+    break;
   }
+  case LIST_TYPE: {
+    List *list = arg.val.p;
+    printf("[");
+    for (size_t i = 0; i < list->size; i++) {
+      if (i != 0) {
+        printf(", ");
+      }
+      void *at = (char *)list->array + i * list->elem_size;
+      __write__(__box__(arg.desc->item, at));
+    }
+    printf("]");
+    break;
   }
+  case ATOM_TYPE: {
+    printf("%s { ", arg.desc->name);
+    for (int i = 0; i < arg.desc->field_count; i++) {
+      if (i != 0) {
+        printf(", ");
+      }
+      void *at = (char *)arg.val.p + arg.desc->offsets[i];
+      printf("%s: ", arg.desc->field_names[i]);
+      __write__(__box__(arg.desc->fields[i], at));
+    }
+    printf(" }");
+    break;
+  }
+  case FN_TYPE: {
+    printf("<%s>", arg.desc->name);
+    break;
+  }
+  }
+}
+
+void writeln(Obj arg) {
+  __write__(arg);
+  printf("\n");
 }
 
 Str *__strnew__(char *s) {
@@ -141,13 +266,67 @@ Str *__strnew__(char *s) {
   return obj;
 }
 
+List *__listadd__(List *a, List *b) {
+  List *list = __listnew__(a->elem_size, a->boxed, a->size + b->size, NULL);
+  memcpy(list->array, a->array, a->size * a->elem_size);
+  memcpy((char *)list->array + a->size * a->elem_size, b->array,
+         b->size * b->elem_size);
+  return list;
+}
+
+_Bool __eq__(Obj a, Obj b);
+// lists and atoms are equal if all their items are, functions if they are the
+// same function
+_Bool __objeq__(Obj a, Obj b) {
+  switch (a.kind) {
+  case LIST_TYPE: {
+    List *list_a = a.val.p;
+    List *list_b = b.val.p;
+    if (list_a->size != list_b->size) {
+      return false;
+    }
+    for (size_t i = 0; i < list_a->size; i++) {
+      void *at_a = (char *)list_a->array + i * list_a->elem_size;
+      void *at_b = (char *)list_b->array + i * list_b->elem_size;
+      if (!__eq__(__box__(a.desc->item, at_a), __box__(b.desc->item, at_b))) {
+        return false;
+      }
+    }
+    return true;
+  }
+  case ATOM_TYPE: {
+    for (int i = 0; i < a.desc->field_count; i++) {
+      void *at_a = (char *)a.val.p + a.desc->offsets[i];
+      void *at_b = (char *)b.val.p + b.desc->offsets[i];
+      if (!__eq__(__box__(a.desc->fields[i], at_a),
+                  __box__(b.desc->fields[i], at_b))) {
+        return false;
+      }
+    }
+    return true;
+  }
+  default:
+    return a.val.p == b.val.p;
+  }
+}
+
+_Bool __objcomp__(Obj a, Obj b) {
+  __operr__("comp", a);
+  return false;
+}
+
+_Bool __objecomp__(Obj a, Obj b) {
+  __operr__("ecomp", a);
+  return false;
+}
+
 DEF(STR, add, +);
 
 DEF(N, sub, -);
 
 DEF(N, mul, *);
 
-DEF(N, div, /);
+DEF(DIV, div, /);
 DEF(NF, mod, %);
 
 DEF(LOGICAL, and, &&);
@@ -202,10 +381,35 @@ Str *__stradd__(Str *a, Str *b) {
 }
 
 void err(char *err, int code) {
-  printf("covalent runtime error: %s", err);
+  printf("covalent runtime error: %s\n", err);
   exit(code);
 }
 
+// the smallest int divided by -1 wraps around instead of trapping
+int __idiv__(int a, int b) {
+  if (b == 0) {
+    err("division by zero", 11);
+  }
+  return b == -1 ? (int)(0u - (unsigned)a) : a / b;
+}
+
+int __imod__(int a, int b) {
+  if (b == 0) {
+    err("division by zero", 11);
+  }
+  return b == -1 ? 0 : a % b;
+}
+
+size_t __index__(List *list, int index) {
+  if (index < 0 || (size_t)index >= list->size) {
+    printf("covalent runtime error: index %d is out of bounds for a list of "
+           "size %zu\n",
+           index, list->size);
+    exit(10);
+  }
+  return index;
+}
+
 unsigned int type_size(TYPE ty) {
   switch (ty) {
   case INT_TYPE:
@@ -231,19 +435,17 @@ Str *__strclone__(Str *obj) {
 
 void __init__() { GC_init(); }
 
-List *__listnew__(size_t elem_size, size_t size, ...) {
+// items is an array of size items or NULL for an empty list
+List *__listnew__(size_t elem_size, _Bool boxed, size_t size, void *items) {
   void *arr = GC_malloc(elem_size * size);
-  va_list args;
-  va_start(args, size);
-
-  char *next_ele = (char *)arr;
-  for (int i = 0; i < size * elem_size / 4; i++) {
-    ((int *)arr)[i] = va_arg(args, int);
+  if (items != NULL) {
+    memcpy(arr, items, elem_size * size);
   }
 
   List *list = (List *)GC_malloc(sizeof(List));
   list->array = arr;
   list->elem_size = elem_size;
+  list->boxed = boxed;
   list->size = size;
   return list;
 }
@@ -254,15 +456,25 @@ List *Listdotpush(List *self, Obj item) {
   self->array =
       GC_realloc(self->array, self->size * self->elem_size + self->elem_size);
   void *insert = (char *)self->array + self->size * self->elem_size;
-  memcpy(insert, &item.val, self->elem_size);
+  if (self->boxed) {
+    // List(Dynamic) keeps the whole Dynamic
+    memcpy(insert, &item, self->elem_size);
+  } else {
+    memcpy(insert, &item.val, self->elem_size);
+  }
+  self->size++;
 
   return self;
 }
 
 // set pop(T): List(T) self -> List(T)
 List *Listdotpop(List *self) {
-  self->array =
-      GC_realloc(self->array, self->size * self->elem_size - self->elem_size);
+  if (self->size == 0) {
+    err("cannot pop from an empty list", 8);
+  }
+
+  self->size--;
+  self->array = GC_realloc(self->array, self->size * self->elem_size);
   return self;
 }
 
@@ -294,11 +506,20 @@ IonizedInt *IonizedIntdotNew(int val) {
 // testing externs
 Str *cprompt(Str *msg) {
   printf("%.*s", msg->len, msg->val);
-  char *cstr;
-  scanf("%s", cstr);
+  char *cstr = GC_malloc(256);
+  scanf("%255s", cstr);
   return __strnew__(cstr);
 }
 
+// the command line args as a List(str), the program name first
+List *__args__(int argc, char **argv) {
+  List *args = __listnew__(sizeof(Str *), false, argc, NULL);
+  for (int i = 0; i < argc; i++) {
+    ((Str **)args->array)[i] = __strnew__(argv[i]);
+  }
+  return args;
+}
+
 Str *itos(int i) {
   char *str = GC_malloc(10);
   sprintf(str, "%d", i);
@@ -307,7 +528,122 @@ Str *itos(int i) {
 
 char *strtocstr(Str *str) {
   char *cstr = GC_malloc(str->len + 1);
-  strcpy(cstr, str->val);
+  memcpy(cstr, str->val, str->len);
   cstr[str->len] = '\0';
   return cstr;
-}
\ No newline at end of file
+}
+Str *ftos(float f) {
+  char *str = GC_malloc(48);
+  sprintf(str, "%f", f);
+  return __strnew__(str);
+}
+
+Str *btos(_Bool b) { return __strnew__(b ? "true" : "false"); }
+
+char *__tyname__(TYPE ty) {
+  switch (ty) {
+  case INT_TYPE:
+    return "int";
+  case FLOAT_TYPE:
+    return "float";
+  case STR_TYPE:
+    return "str";
+  case BOOL_TYPE:
+    return "bool";
+  default:
+    return "unknown";
+  }
+}
+
+void __converr__(Str *s, char *into) {
+  printf("covalent runtime error: cannot convert str \"%.*s\" into %s\n",
+         s->len, s->val, into);
+  exit(6);
+}
+
+// strtol and strtof skip leading whitespace, the whole str has to be the value
+int __stoi__(Str *s) {
+  char *cstr = strtocstr(s);
+  char *end;
+  errno = 0;
+  long i = strtol(cstr, &end, 10);
+  if (s->len == 0 || isspace((unsigned char)cstr[0]) || *end != '\0' ||
+      errno == ERANGE || i < INT_MIN || i > INT_MAX) {
+    __converr__(s, "int");
+  }
+  return (int)i;
+}
+
+float __stof__(Str *s) {
+  char *cstr = strtocstr(s);
+  char *end;
+  float f = strtof(cstr, &end);
+  if (s->len == 0 || isspace((unsigned char)cstr[0]) || *end != '\0') {
+    __converr__(s, "float");
+  }
+  return f;
+}
+
+_Bool __stob__(Str *s) {
+  if (__streq__(s, __strnew__("true"))) {
+    return true;
+  }
+  if (__streq__(s, __strnew__("false"))) {
+    return false;
+  }
+  __converr__(s, "bool");
+  return false;
+}
+
+const TypeDesc __desc_i = {INT_TYPE, "int", "i"};
+const TypeDesc __desc_f = {FLOAT_TYPE, "float", "f"};
+const TypeDesc __desc_b = {BOOL_TYPE, "bool", "b"};
+const TypeDesc __desc_s = {STR_TYPE, "str", "s"};
+const TypeDesc __desc_d = {DYN_TYPE, "Dynamic", "d"};
+
+_Bool __is__(Obj o, const TypeDesc *desc) {
+  if (desc->kind == DYN_TYPE) {
+    return true;
+  }
+  if (o.kind != desc->kind) {
+    return false;
+  }
+  // descriptors of the same type may come from different modules
+  return o.desc == NULL || strcmp(o.desc->code, desc->code) == 0;
+}
+
+Str *__typename__(Obj o) { return __strnew__(__objname__(o)); }
+
+void __downcast__(Obj o, const TypeDesc *into) {
+  if (!__is__(o, into)) {
+    printf("covalent runtime error: cannot downcast Dynamic holding %s into "
+           "%s\n",
+           __objname__(o), into->name);
+    exit(7);
+  }
+}
+
+int __dyntoi__(Obj o) {
+  __downcast__(o, &__desc_i);
+  return o.val.i;
+}
+
+float __dyntof__(Obj o) {
+  __downcast__(o, &__desc_f);
+  return o.val.f;
+}
+
+_Bool __dyntob__(Obj o) {
+  __downcast__(o, &__desc_b);
+  return o.val.b;
+}
+
+Str *__dyntos__(Obj o) {
+  __downcast__(o, &__desc_s);
+  return o.val.s;
+}
+
+void *__dyntop__(Obj o, const TypeDesc *into) {
+  __downcast__(o, into);
+  return o.val.p;
+}
@@ -1,15 +1,41 @@
+#include "math.h"
 #include "stdbool.h"
+#include "stddef.h"
 #include "stdlib.h"
 
 void GC_free(void *);
+void *GC_malloc(unsigned int);
 #define free(a) GC_free(a) // remove later
 
 #define INT_TYPE 0
 #define FLOAT_TYPE 1
 #define STR_TYPE 2
 #define BOOL_TYPE 3
+#define LIST_TYPE 4
+#define ATOM_TYPE 5
+#define FN_TYPE 6
+#define DYN_TYPE 7 // only used by descriptors, a boxed Dynamic is never Dynamic
 typedef char TYPE;
 
+// runtime description of a covalent type, generated by the compiler for every
+// type that gets boxed into a Dynamic
+typedef struct TypeDesc {
+  TYPE kind;
+  char *name;
+  char *code; // the type mangle, types are compared by code
+  const struct TypeDesc *item; // item of a List, return type of a function
+  int field_count;
+  char **field_names;
+  const struct TypeDesc **fields;
+  size_t *offsets;
+} TypeDesc;
+
+extern const TypeDesc __desc_i;
+extern const TypeDesc __desc_f;
+extern const TypeDesc __desc_b;
+extern const TypeDesc __desc_s;
+extern const TypeDesc __desc_d;
+
 typedef struct Str {
   char *val;
   int len;
@@ -20,6 +46,7 @@ typedef union Value {
   float f;
   _Bool b;
   Str *s;
+  void *p; // lists, atoms and functions
 } Value;
 
 #define INT_SIZE sizeof(TYPE) + sizeof(int)
@@ -30,6 +57,7 @@ typedef union Value {
 typedef struct {
   TYPE kind;
   Value val;
+  const TypeDesc *desc; // NULL for basic types
 } Obj;
 
 typedef struct IonTable {
@@ -57,6 +85,12 @@ void writeln(Obj arg);
 #define __float__(flo) ((Obj){FLOAT_TYPE, (Value){.f = flo}})
 #define __str__(str) ((Obj){STR_TYPE, (Value){.s = str}})
 #define __bool__(bool) ((Obj){BOOL_TYPE, (Value){.b = bool}})
+#define __list__(list, desc) ((Obj){LIST_TYPE, (Value){.p = (void *)list}, desc})
+#define __atom__(atom, desc) ((Obj){ATOM_TYPE, (Value){.p = (void *)atom}, desc})
+#define __fn__(fn, desc) ((Obj){FN_TYPE, (Value){.p = (void *)fn}, desc})
+
+// boxes the value of type desc stored at at
+Obj __box__(const TypeDesc *desc, void *at);
 
 Str *__strnew__(char *val);
 
@@ -78,18 +112,44 @@ Str *__stradd__(Str *a, Str *b);
 Str *__strclone__(Str *obj);
 void __init__();
 
-#define __listget__(list, type, index) (((type *)(list)->array)[index])
+#define __listget__(list, type, index)                                        \
+  (((type *)(list)->array)[__index__(list, index)])
 
 typedef struct List {
   void *array;
   size_t size;
   size_t elem_size;
+  _Bool boxed; // a List(Dynamic), its items are whole Dynamics
 } List;
 
 List *Listdotpush(List *self, Obj item);
-List *__listnew__(size_t elem_size, size_t size, ...);
+List *__listnew__(size_t elem_size, _Bool boxed, size_t size, void *items);
 List *Listdotpop(List *self);
 
 void __free__(void *item);
 
-Str *itos(int i);
\ No newline at end of file
+List *__args__(int argc, char **argv);
+Str *itos(int i);
+Str *ftos(float f);
+Str *btos(_Bool b);
+char *strtocstr(Str *str);
+
+// checked int division and list indexing, exit with a runtime error on failure
+int __idiv__(int a, int b);
+int __imod__(int a, int b);
+size_t __index__(List *list, int index);
+
+// fallible conversions, exit with a runtime error on failure
+int __stoi__(Str *s);
+float __stof__(Str *s);
+_Bool __stob__(Str *s);
+
+// checked Dynamic downcasts
+int __dyntoi__(Obj o);
+float __dyntof__(Obj o);
+_Bool __dyntob__(Obj o);
+Str *__dyntos__(Obj o);
+void *__dyntop__(Obj o, const TypeDesc *into); // lists and atoms
+
+_Bool __is__(Obj o, const TypeDesc *desc);
+Str *__typename__(Obj o);
\ No newline at end of file
@@ -1,12 +1,24 @@
 use std::env::current_exe;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+use std::thread;
 
+use crate::analysis::lint::{Level, Linter};
 use crate::analysis::Analyzer;
 use crate::backend::c;
+use crate::err::{ErrKind, Severity};
 use crate::ir::gen::IRGen;
-use crate::ir::Codegen;
+use crate::ir::interp::{Interpreter, Program};
+use crate::ir::tools::{self, PassManager};
+use crate::ir::{bytecode, ssa, text, Codegen, IROp};
+use crate::parser::ast::{Ast, Blueprint, NodeId, Tree};
 use crate::parser::parse::Parse;
 use crate::parser::Parser;
 
+const INTERP_STACK: usize = 1 << 30;
+
 #[allow(unused)]
 pub struct CSettings {
     compiler: Option<String>,
@@ -24,6 +36,16 @@ pub enum Backend {
     C(CSettings),
     Custom { name: String, settings: Vec<String> },
 }
+// what a compile writes, an executable from the backend, the IR as text (.air), the IR
+// lowered to SSA (.ssa) or the IR as bytecode for covalent vm (.atomc)
+#[derive(Clone, Copy, PartialEq)]
+pub enum Emit {
+    Exe,
+    Ir,
+    Ssa,
+    Bytecode,
+}
+
 pub struct CompilerConfig {
     input: String,
     pub libdir: String,
@@ -31,6 +53,10 @@ pub struct CompilerConfig {
     pub debug: bool,
     pub output: String,
     pub workdir: String,
+    pub lints: Vec<(String, Level)>, // lint levels from --warn, --deny and --allow in order
+    pub emit: Emit,
+    pub opt: u8,     // optimization level from -O0, -O1 or -O2
+    pub stats: bool, // print what each pass did
 }
 impl CompilerConfig {
     pub fn new(
@@ -50,22 +76,117 @@ impl CompilerConfig {
             debug,
             output,
             workdir,
+            lints: Vec::new(),
+            emit: Emit::Exe,
+            opt: 0,
+            stats: false,
         }
     }
     pub fn compile(&self) {
+        let ir = self.gen_ir();
+        self.emit(ir);
+    }
+
+    // compiles a textual IR program, the backend gets it without going through the front end
+    pub fn compile_ir(&self) {
+        self.emit(self.parse_ir());
+    }
+
+    pub fn parse_ir(&self) -> Vec<IROp> {
+        let Ok(ir) = text::parse(&self.input) else {
+            std::process::exit(1);
+        };
+        ir
+    }
+
+    pub fn gen_ir(&self) -> Vec<IROp> {
+        self.try_gen_ir().unwrap_or_else(|_| std::process::exit(1))
+    }
+
+    // the IR of the program, an error is reported before its kind is returned
+    pub fn try_gen_ir(&self) -> Result<Vec<IROp>, ErrKind> {
         let mut parser = Parser::new(self.input.clone());
         let prog = parser.parse_prog();
+        if let Some(kind) = parser.failed() {
+            return Err(kind);
+        }
+
+        let blueprints = parser.functions.clone();
 
-        let prog = Analyzer::analyz_prog(prog, parser.functions, self.workdir.clone()).unwrap();
+        let (ast, prog) =
+            Analyzer::analyz_prog(parser.ast, prog, parser.functions, self.workdir.clone())?;
+        self.lint(&ast, &prog, &blueprints, &parser.allows)?;
         if self.debug {
-            dbg!(&prog);
+            dbg!(Tree(&ast, &prog));
         }
 
         let mut codegen = Codegen::new();
-        let ir = codegen.gen_prog(prog).unwrap();
+        codegen.gen_prog(&ast, prog)
+    }
+
+    // runs the program, with the interpreter or compiled by the backend, args are its command
+    // line args after the program name, returns its exit code. the program name is the path of
+    // the executable either way
+    pub fn run(&self, ir: Vec<IROp>, args: Vec<String>, interp: bool) -> i32 {
+        let exe = Path::new(".").join(&self.output);
+        if interp {
+            let name = exe.to_string_lossy().into_owned();
+            return self.interpret(ir, std::iter::once(name).chain(args).collect());
+        }
+        self.emit(ir);
+        Command::new(exe)
+            .args(args)
+            .status()
+            .expect("failed to execute the compiled program")
+            .code()
+            .unwrap_or(1)
+    }
+
+    // runs the program with the interpreter, a runtime error prints its message and gives
+    // the exit code the C runtime exits with
+    pub fn interpret(&self, mut ir: Vec<IROp>, args: Vec<String>) -> i32 {
+        self.optimize(&mut ir);
+        interpret(&ir, args)
+    }
+
+    fn optimize(&self, ir: &mut Vec<IROp>) {
+        let mut passes = PassManager::new(self.opt);
+        passes.run(ir);
+        if self.stats {
+            for stats in &passes.stats {
+                println!("{stats}");
+            }
+        }
+
         if self.debug {
             dbg!(&ir);
         }
+    }
+
+    fn emit(&self, mut ir: Vec<IROp>) {
+        self.optimize(&mut ir);
+        // debug builds check the ir before it is written or compiled
+        let verified = !cfg!(debug_assertions) || tools::verify(&ir).is_ok();
+        if self.emit == Emit::Ir {
+            let path = format!("{}.air", self.output);
+            fs::write(&path, text::print(&ir)).unwrap_or_else(|_| panic!("err writing to {path}"));
+        }
+        if !verified {
+            std::process::exit(1);
+        }
+        if self.emit == Emit::Bytecode {
+            let path = format!("{}.atomc", self.output);
+            fs::write(&path, bytecode::write(&ir))
+                .unwrap_or_else(|_| panic!("err writing to {path}"));
+        }
+        if self.emit == Emit::Ssa {
+            let path = format!("{}.ssa", self.output);
+            let ssa = ssa::print(&ssa::lower(&ir));
+            fs::write(&path, ssa).unwrap_or_else(|_| panic!("err writing to {path}"));
+        }
+        if self.emit != Emit::Exe {
+            return;
+        }
         match self.backend {
             Backend::C(_) => {
                 c::compile(self, ir);
@@ -73,4 +194,54 @@ impl CompilerConfig {
             _ => todo!(),
         }
     }
+
+    fn lint(
+        &self,
+        ast: &Ast,
+        prog: &[NodeId],
+        blueprints: &[Blueprint],
+        allows: &[String],
+    ) -> Result<(), ErrKind> {
+        let mut linter = Linter::new();
+        for (lint, level) in &self.lints {
+            if let Err(msg) = linter.set_level(lint, *level) {
+                println!("{msg}");
+                return Err(ErrKind::Lint);
+            }
+        }
+
+        let diagnostics = linter.run(ast, prog, blueprints, allows);
+        for diagnostic in &diagnostics {
+            diagnostic.out_error();
+        }
+        if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+            return Err(ErrKind::Lint);
+        }
+        Ok(())
+    }
+}
+
+// runs a program with the interpreter, debug builds check the ir before it runs
+pub fn interpret(ir: &[IROp], args: Vec<String>) -> i32 {
+    if cfg!(debug_assertions) && tools::verify(ir).is_err() {
+        std::process::exit(1);
+    }
+
+    // interpreted calls take more of the native stack than compiled ones, the program gets a
+    // thread with a bigger stack so it recurses about as deep as the executable does
+    let program = Program::new(ir);
+    let run = move || {
+        let (mut out, mut input) = (io::stdout().lock(), io::stdin().lock());
+        let result = Interpreter::new(&program, &mut out, &mut input).run(args);
+        result.unwrap_or_else(|trap| {
+            println!("{trap}");
+            trap.code
+        })
+    };
+    thread::Builder::new()
+        .stack_size(INTERP_STACK)
+        .spawn(run)
+        .unwrap()
+        .join()
+        .unwrap()
 }
@@ -1,41 +1,101 @@
 use std::collections::HashMap;
 
-use crate::parser::ast::{Blueprint, Literal};
+use crate::parser::ast::{Blueprint, Literal, NodeId};
+use crate::symbol::SymbolId;
 use crate::types::{self, AtomDetails, AtomKind, AtomType, BasicType, FunctionType};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Symbol {
-    pub name: String,
+    pub name: SymbolId,
     pub ty: AtomType,
 
     pub value: Option<Literal>,
 
     pub expected: Option<AtomType>,
+    pub mutable: bool, // part bindings, types and blueprints can't be reassigned
+}
+
+impl Symbol {
+    // values declared with set, state or part and params, not functions, types or consts
+    pub fn is_variable(&self) -> bool {
+        self.value.is_none()
+            && !self.ty.is_type()
+            && !matches!(
+                self.ty.kind,
+                AtomKind::Function(_) | AtomKind::Blueprint(_) | AtomKind::Iface(_)
+            )
+    }
+}
+
+// an implementation of an interface for a type, methods are registered as blueprints named by types::method_name
+#[derive(Debug, Clone, PartialEq)]
+pub struct Impl {
+    pub iface: String,
+    pub ty: AtomType,
+    pub methods: Vec<String>,
+}
+
+// a generic type alias, ty is analyzed again with generics bound on every use (Pair(int))
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alias {
+    pub name: String,
+    pub generics: Vec<String>,
+    pub ty: NodeId,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ScopeId(usize);
+
+// scopes key their symbols by interned name
+#[derive(Debug, Clone, Default)]
+pub struct Scope {
+    pub symbols: HashMap<SymbolId, Symbol>,
+    pub blueprints: HashMap<SymbolId, Blueprint>,
+    pub impls: Vec<Impl>,
+    pub aliases: Vec<Alias>,
+    pub uses: Vec<ScopeId>, // namespaces of the modules used here, searched after the scope itself
+}
+
+impl Scope {
+    fn clear(&mut self) {
+        self.symbols.clear();
+        self.blueprints.clear();
+        self.impls.clear();
+        self.aliases.clear();
+        self.uses.clear();
+    }
 }
 
+// scopes live in an arena and the open ones form a stack above the root scope, opening and closing
+// a scope doesn't copy anything, closed scopes are reused. scopes that are never closed (the scopes
+// of a module) stay in the arena and can be opened again with open
 #[derive(Clone, Debug)]
 pub struct Enviroment {
-    pub symbols: HashMap<String, Symbol>,
-    pub parent: Option<Box<Enviroment>>,
-    pub blueprints: Vec<Blueprint>,
+    scopes: Vec<Scope>,
+    stack: Vec<ScopeId>, // the root first
+    free: Vec<ScopeId>,
+}
+
+impl Default for Enviroment {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Enviroment {
     // Initialize the top-level environment. This environment serves as the parent for all other environments and contains the built-in types.
     pub fn init() -> Self {
-        let mut symbols = HashMap::new();
+        let mut env = Self::new();
 
         macro_rules! insert {
             ($name: expr, $type: expr) => {
-                symbols.insert(
-                    $name.to_owned(),
-                    Symbol {
-                        name: $name.to_owned(),
-                        ty: $type,
-                        value: None,
-                        expected: None,
-                    },
-                );
+                env.add(Symbol {
+                    name: SymbolId::intern($name),
+                    ty: $type,
+                    value: None,
+                    expected: None,
+                    mutable: false,
+                });
             };
         }
 
@@ -77,70 +137,131 @@ impl Enviroment {
         complex!(types::Str);
         complex!(types::Const);
 
+        env
+    }
+
+    // an empty root scope
+    pub fn new() -> Self {
         Self {
-            symbols,
-            parent: None,
-            blueprints: Vec::new(),
+            scopes: vec![Scope::default()],
+            stack: vec![ScopeId(0)],
+            free: Vec::new(),
         }
     }
 
-    pub fn new(parent: Option<Box<Self>>) -> Self {
-        Self {
-            symbols: HashMap::new(),
-            parent,
-            blueprints: Vec::new(),
+    // a scope that isn't open yet
+    pub fn new_scope(&mut self) -> ScopeId {
+        match self.free.pop() {
+            Some(id) => id,
+            None => {
+                self.scopes.push(Scope::default());
+                ScopeId(self.scopes.len() - 1)
+            }
         }
     }
 
     pub fn child(&mut self) {
-        *self = Enviroment::new(Some(Box::new(self.clone())));
+        let id = self.new_scope();
+        self.stack.push(id);
     }
 
     pub fn parent(&mut self) {
-        *self = *self.parent.as_ref().unwrap().clone();
+        let id = self.stack.pop().unwrap();
+        self.scopes[id.0].clear();
+        self.free.push(id);
     }
 
-    pub fn get_ty(&self, name: &String) -> Option<AtomType> {
-        let sym = self.get(name);
+    // opens scopes above the root scope instead of the open ones, returns the scopes that were open
+    pub fn open(&mut self, scopes: &[ScopeId]) -> Vec<ScopeId> {
+        let mut stack = vec![self.stack[0]];
+        stack.extend_from_slice(scopes);
+        std::mem::replace(&mut self.stack, stack)
+    }
 
-        if sym.is_some() {
-            Some(sym.unwrap().ty.clone())
-        } else {
-            if self.parent.is_some() {
-                self.parent.as_ref().unwrap().get_ty(name)
-            } else {
-                None
-            }
-        }
+    // the open scopes above the root scope
+    pub fn opened(&self) -> Vec<ScopeId> {
+        self.stack[1..].to_vec()
     }
 
-    pub fn get(&self, name: &String) -> Option<&Symbol> {
-        if self.symbols.contains_key(name) {
-            return Some(&self.symbols[name]);
-        }
+    // reopens the scopes returned by open
+    pub fn restore(&mut self, stack: Vec<ScopeId>) {
+        self.stack = stack;
+    }
 
-        if self.parent.is_some() {
-            self.parent.as_ref().unwrap().get(name)
-        } else {
-            None
-        }
+    pub fn scope(&self, id: ScopeId) -> &Scope {
+        &self.scopes[id.0]
+    }
+
+    fn current(&self) -> &Scope {
+        &self.scopes[self.stack.last().unwrap().0]
+    }
+
+    fn current_mut(&mut self) -> &mut Scope {
+        let id = *self.stack.last().unwrap();
+        &mut self.scopes[id.0]
+    }
+
+    // the open scopes from the innermost one
+    fn open_scopes(&self) -> impl Iterator<Item = &Scope> {
+        self.stack.iter().rev().map(|id| &self.scopes[id.0])
+    }
+
+    // looks in scope, then in the namespaces it uses
+    fn find<'a, T>(&'a self, scope: &'a Scope, f: impl Fn(&'a Scope) -> Option<T>) -> Option<T> {
+        f(scope).or_else(|| scope.uses.iter().find_map(|id| f(&self.scopes[id.0])))
     }
 
-    pub fn has(&self, name: &String) -> bool {
-        if self.symbols.contains_key(name) {
-            true
-        } else if self.parent.is_some() {
-            self.parent.as_ref().unwrap().has(name)
-        } else {
-            false
+    // makes the names in a namespace visible in the innermost scope
+    pub fn use_namespace(&mut self, namespace: ScopeId) {
+        if !self.current().uses.contains(&namespace) {
+            self.current_mut().uses.push(namespace);
         }
     }
 
+    // a scope that is filled outside of the open ones, like the namespace of a module
+    pub fn scope_mut(&mut self, id: ScopeId) -> &mut Scope {
+        &mut self.scopes[id.0]
+    }
+
+    pub fn get_ty(&self, id: SymbolId) -> Option<AtomType> {
+        self.get(id).map(|sym| sym.ty.clone())
+    }
+
+    pub fn get(&self, id: SymbolId) -> Option<&Symbol> {
+        self.open_scopes()
+            .find_map(|scope| self.find(scope, |scope| scope.symbols.get(&id)))
+    }
+
+    // a symbol declared or used in the innermost scope
+    pub fn local(&self, id: SymbolId) -> Option<&Symbol> {
+        self.find(self.current(), |scope| scope.symbols.get(&id))
+    }
+
+    // the symbols declared in the innermost scope
+    pub fn locals(&self) -> impl Iterator<Item = &Symbol> {
+        self.current().symbols.values()
+    }
+
+    // how many scopes up name is declared, 0 if it is in this one
+    pub fn scope_of(&self, id: SymbolId) -> Option<usize> {
+        self.open_scopes()
+            .position(|scope| self.find(scope, |scope| scope.symbols.get(&id)).is_some())
+    }
+
+    // the number of scopes above the root one
+    pub fn depth(&self) -> usize {
+        self.stack.len() - 1
+    }
+
+    pub fn has(&self, id: SymbolId) -> bool {
+        self.get(id).is_some()
+    }
+
     //TODO: REMOVE
     // member expr parent is passed to a function as first arg if it takes it as an arg for ex.
     // set push: List(T) self, T item -> List(T)
-    pub fn ty_parent_fn(&self, ty: &AtomType, name: &String) -> Option<AtomType> {
-        let parent = self.symbols.get(name);
+    pub fn ty_parent_fn(&self, ty: &AtomType, id: SymbolId) -> Option<AtomType> {
+        let parent = self.local(id);
 
         if parent.is_some() {
             if let &AtomKind::Function(ref f) = &parent.unwrap().ty.kind {
@@ -152,53 +273,73 @@ impl Enviroment {
         None
     }
 
-    pub fn modify_ty(&mut self, name: &String, ty: AtomType) {
-        if self.symbols.contains_key(name) {
-            self.symbols.get_mut(name).map(|val| val.ty = ty);
-        } else if self.parent.is_some() {
-            self.parent.as_mut().unwrap().modify_ty(name, ty);
+    pub fn modify(&mut self, sym: Symbol) {
+        let id = sym.name;
+        for scope in self.stack.iter().rev() {
+            if let Some(val) = self.scopes[scope.0].symbols.get_mut(&id) {
+                *val = sym;
+                return;
+            }
         }
     }
 
-    pub fn modify(&mut self, name: &String, sym: Symbol) {
-        if self.symbols.contains_key(name) {
-            self.symbols.get_mut(name).map(|val| *val = sym);
-        } else if self.parent.is_some() {
-            self.parent.as_mut().unwrap().modify(name, sym);
-        }
+    pub fn add(&mut self, sym: Symbol) {
+        self.current_mut().symbols.insert(sym.name, sym);
     }
 
-    pub fn add(&mut self, sym: Symbol) {
-        let name = sym.name.clone();
-        if self.symbols.contains_key(&name) {
-            self.modify(&name, sym)
-        } else {
-            self.symbols.insert(name, sym);
-        }
+    pub fn add_blueprint(&mut self, blueprint: Blueprint) {
+        let id = blueprint.name.sym();
+        self.current_mut().blueprints.insert(id, blueprint);
     }
 
-    pub fn get_blueprint(&self, name: &String) -> Option<Blueprint> {
-        for blueprint in &self.blueprints {
-            if blueprint.name.val() == name {
-                return Some(blueprint.clone());
-            }
-        }
+    pub fn add_impl(&mut self, imp: Impl) {
+        self.current_mut().impls.push(imp);
+    }
 
-        if self.parent.is_some() {
-            return self.parent.as_ref().unwrap().get_blueprint(name);
-        }
-        return None;
+    pub fn add_alias(&mut self, alias: Alias) {
+        self.current_mut().aliases.push(alias);
     }
 
-    pub fn top(&mut self) -> &mut Enviroment {
-        if self.parent.is_none() {
-            self
-        } else {
-            self.parent.as_mut().unwrap().top()
-        }
-    } // returns the top level enviroment
+    pub fn get_blueprint(&self, id: SymbolId) -> Option<Blueprint> {
+        self.open_scopes()
+            .find_map(|scope| self.find(scope, |scope| scope.blueprints.get(&id)))
+            .cloned()
+    }
+
+    // finds the implementation of iface for ty
+    pub fn get_impl(&self, iface: &str, ty: &AtomType) -> Option<&Impl> {
+        let name = types::impl_name(ty);
+        self.open_scopes().find_map(|scope| {
+            self.find(scope, |scope| {
+                scope
+                    .impls
+                    .iter()
+                    .find(|i| i.iface == iface && types::impl_name(&i.ty) == name)
+            })
+        })
+    }
 
-    pub fn push_function(&mut self, name: String, func: FunctionType) {
+    // finds the interface that provides method for ty
+    pub fn get_method_impl(&self, ty: &AtomType, method: &str) -> Option<&Impl> {
+        let name = types::impl_name(ty);
+        self.open_scopes().find_map(|scope| {
+            self.find(scope, |scope| {
+                scope.impls.iter().find(|i| {
+                    types::impl_name(&i.ty) == name && i.methods.iter().any(|m| m == method)
+                })
+            })
+        })
+    }
+
+    pub fn get_alias(&self, name: &str) -> Option<&Alias> {
+        self.open_scopes().find_map(|scope| {
+            self.find(scope, |scope| {
+                scope.aliases.iter().find(|alias| alias.name == name)
+            })
+        })
+    }
+
+    pub fn push_function(&mut self, name: SymbolId, func: FunctionType) {
         self.add(Symbol {
             name,
             ty: AtomType {
@@ -208,29 +349,29 @@ impl Enviroment {
 
             value: None,
             expected: None,
+            mutable: false,
         });
     }
 
-    pub fn expect(&mut self, name: &String, ty: AtomType) {
-        self.symbols
-            .get_mut(name)
-            .map(|sym| sym.expected = Some(ty));
+    // functions in the root scope are seen by every module
+    pub fn push_top_function(&mut self, name: SymbolId, func: FunctionType) {
+        let top = self.stack.split_off(1);
+        self.push_function(name, func);
+        self.stack.extend(top);
     }
 
-    pub fn is_expected(&mut self, name: &String, ty: &AtomType) -> bool {
-        let sym = self.get(name);
-        if sym.is_none() {
-            if self.parent.is_some() {
-                return self.parent.as_mut().unwrap().is_expected(name, ty);
-            }
-
-            panic!("symbol not found");
+    pub fn expect(&mut self, id: SymbolId, ty: AtomType) {
+        if let Some(sym) = self.current_mut().symbols.get_mut(&id) {
+            sym.expected = Some(ty);
         }
+    }
 
-        if sym.unwrap().expected.is_none() {
-            return true;
-        }
+    pub fn is_expected(&mut self, id: SymbolId, ty: &AtomType) -> bool {
+        let sym = self.get(id).expect("symbol not found");
 
-        &sym.unwrap().expected == &Some(ty.clone())
+        match &sym.expected {
+            Some(expected) => expected == ty,
+            None => true,
+        }
     }
 }
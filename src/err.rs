@@ -10,6 +10,20 @@ pub enum ErrKind {
     VarAlreadyDeclared,
     OperationNotGranted,
     UnexceptedArgs,
+    ImmutableAssign,
+    MissingReturn,
+    Lint,
+    ConstEval,
+    Module,
+    IRSyntax,
+    IRVerify,
+    Bytecode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning, // reported without stopping compilation
 }
 
 #[derive(Debug, Clone)]
@@ -18,12 +32,17 @@ pub struct ATErr {
     pub msg: String,
     pub line: u16,
     pub column: u16,
+    pub severity: Severity,
 }
 
 impl ATErr {
     pub fn get_error(&self) -> String {
+        let prefix = match self.severity {
+            Severity::Error => "",
+            Severity::Warning => "warning ",
+        };
         format!(
-            "code:AT00{}\n{}\nat line:{}, column:{}",
+            "{prefix}code:AT{:03}\n{}\nat line:{}, column:{}",
             self.kind.clone() as u8,
             self.msg,
             self.line,
@@ -33,10 +52,23 @@ impl ATErr {
 
     // customize later
     pub fn out_error(&self) {
+        #[cfg(test)]
+        REPORTED.with(|reported| reported.borrow_mut().push(self.get_error()));
         println!("{}", self.get_error());
     }
 }
 
+// errors reported on this thread, tests check the diagnostics of programs that fail to compile
+#[cfg(test)]
+thread_local! {
+    static REPORTED: std::cell::RefCell<Vec<String>> = const { std::cell::RefCell::new(Vec::new()) };
+}
+
+#[cfg(test)]
+pub fn take_reported() -> Vec<String> {
+    REPORTED.with(|reported| reported.take())
+}
+
 #[macro_export]
 macro_rules! err {
     ($self: ident, $kind: path, $msg: literal) => {
@@ -45,6 +77,7 @@ macro_rules! err {
             msg: $msg.to_string(),
             line: $self.line,
             column: $self.column,
+            severity: $crate::err::Severity::Error,
         }
         .out_error();
 
@@ -57,6 +90,7 @@ macro_rules! err {
             msg: $msg,
             line: $self.line,
             column: $self.column,
+            severity: $crate::err::Severity::Error,
         }
         .out_error();
 
@@ -0,0 +1,775 @@
+// the bytecode format (.atomc files), the IR in binary so programs can be shipped and run by
+// covalent vm without the front end or a C toolchain
+//
+// header      magic "ATMC", version u16
+// pool        the constants, literals and names: tag u8 then i32, f32, bool u8 or len and utf8
+// types       the types, a tag u8 then pool indexes and indexes of types before it, or the size of
+//             a List(T, N)
+// functions   the defs: name, ret type, pub u8, params and the offset and length of their body
+// main        the offset and length of the top level code
+// code        the length of the code then the bodies, an op count and that many ops
+//
+// an op is an opcode u8 and its operands. counts, lengths and indexes are LEB128, the other
+// numbers are little endian. a reader only reads its own version
+use std::collections::HashMap;
+
+use indexmap::IndexMap;
+
+use super::IROp;
+use crate::err;
+use crate::err::{ATErr, ErrKind};
+use crate::parser::ast::{Ident, Literal};
+use crate::symbol::SymbolId;
+use crate::types::{self, Atom, AtomKind, AtomType, BasicType, FunctionType};
+
+pub const MAGIC: &[u8; 4] = b"ATMC";
+pub const VERSION: u16 = 2;
+
+// an entry of the pool or of the types, equal entries are written once
+#[derive(Default)]
+struct Table {
+    entries: Vec<Vec<u8>>,
+    ids: HashMap<Vec<u8>, u32>,
+}
+
+impl Table {
+    fn add(&mut self, entry: Vec<u8>) -> u32 {
+        if let Some(id) = self.ids.get(&entry) {
+            return *id;
+        }
+        let id = self.entries.len() as u32;
+        self.entries.push(entry.clone());
+        self.ids.insert(entry, id);
+        id
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        uint(out, self.entries.len() as u32);
+        for entry in &self.entries {
+            out.extend(entry);
+        }
+    }
+}
+
+fn uint(out: &mut Vec<u8>, mut n: u32) {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+#[derive(Default)]
+struct Writer {
+    pool: Table,
+    types: Table,
+    functions: Vec<Vec<u8>>,
+    code: Vec<u8>,
+}
+
+pub fn write(ir: &[IROp]) -> Vec<u8> {
+    let mut writer = Writer::default();
+    let main = writer.body(ir);
+    let (offset, len) = writer.code_add(main);
+
+    let mut out = MAGIC.to_vec();
+    out.extend(VERSION.to_le_bytes());
+    writer.pool.write(&mut out);
+    writer.types.write(&mut out);
+    uint(&mut out, writer.functions.len() as u32);
+    for function in &writer.functions {
+        out.extend(function);
+    }
+    uint(&mut out, offset);
+    uint(&mut out, len);
+    uint(&mut out, writer.code.len() as u32);
+    out.extend(writer.code);
+    out
+}
+
+impl Writer {
+    // appends a body to the code, returns where it is
+    fn code_add(&mut self, body: Vec<u8>) -> (u32, u32) {
+        let offset = self.code.len() as u32;
+        self.code.extend(&body);
+        (offset, body.len() as u32)
+    }
+
+    fn lit(&mut self, lit: &Literal) -> u32 {
+        let mut entry = Vec::new();
+        match lit {
+            Literal::Int(i) => {
+                entry.push(0);
+                entry.extend(i.to_le_bytes());
+            }
+            Literal::Float(f) => {
+                entry.push(1);
+                entry.extend(f.to_le_bytes());
+            }
+            Literal::Str(s) => {
+                entry.push(2);
+                uint(&mut entry, s.len() as u32);
+                entry.extend(s.as_bytes());
+            }
+            Literal::Bool(b) => {
+                entry.push(3);
+                entry.push(*b as u8);
+            }
+        }
+        self.pool.add(entry)
+    }
+
+    fn name(&mut self, name: &str) -> u32 {
+        self.lit(&Literal::Str(name.to_string()))
+    }
+
+    fn ty(&mut self, ty: &AtomType) -> u32 {
+        let mut entry = Vec::new();
+        match &ty.kind {
+            AtomKind::Basic(BasicType::Int) => entry.push(0),
+            AtomKind::Basic(BasicType::Float) => entry.push(1),
+            AtomKind::Basic(BasicType::Bool) => entry.push(2),
+            AtomKind::Basic(BasicType::Void) => entry.push(3),
+            AtomKind::Dynamic => entry.push(4),
+            AtomKind::Any => entry.push(5),
+            AtomKind::Unknown => entry.push(6),
+            AtomKind::Atom(atom) if atom == &*types::Str => entry.push(7),
+            AtomKind::Atom(Atom {
+                generics,
+                len: Some(len),
+                ..
+            }) => {
+                entry.push(11);
+                uint(&mut entry, self.ty(&generics[0]));
+                uint(&mut entry, *len);
+            }
+            AtomKind::Atom(atom) if types::is_builtin(atom) => {
+                entry.push(8);
+                uint(&mut entry, self.name(&atom.name));
+                uint(&mut entry, atom.generics.len() as u32);
+                for generic in atom.generics.values() {
+                    uint(&mut entry, self.ty(generic));
+                }
+            }
+            AtomKind::Atom(atom) => {
+                entry.push(9);
+                uint(&mut entry, self.name(&atom.name));
+                uint(&mut entry, atom.fields.len() as u32);
+                for (name, ty) in &atom.fields {
+                    uint(&mut entry, self.name(name));
+                    uint(&mut entry, self.ty(ty));
+                }
+            }
+            AtomKind::Function(func) => {
+                entry.push(10);
+                uint(&mut entry, func.params.len() as u32);
+                for param in &func.params {
+                    uint(&mut entry, self.ty(param));
+                }
+                uint(&mut entry, self.ty(&func.return_type));
+            }
+            kind => unreachable!("{kind} has no bytecode"),
+        }
+        self.types.add(entry)
+    }
+
+    fn ident(&mut self, ident: &Ident, out: &mut Vec<u8>) {
+        match ident {
+            Ident::UnTagged(name) => {
+                out.push(0);
+                uint(out, self.name(name.as_str()));
+            }
+            Ident::Typed(ty, name) => {
+                out.push(1);
+                uint(out, self.ty(ty));
+                uint(out, self.name(name.as_str()));
+            }
+            Ident::Tagged(..) => unreachable!("tagged idents are analyzed before IR"),
+        }
+    }
+
+    fn idents(&mut self, idents: &[Ident], out: &mut Vec<u8>) {
+        uint(out, idents.len() as u32);
+        for ident in idents {
+            self.ident(ident, out);
+        }
+    }
+
+    fn body(&mut self, body: &[IROp]) -> Vec<u8> {
+        let mut out = Vec::new();
+        uint(&mut out, body.len() as u32);
+        for op in body {
+            self.op(op, &mut out);
+        }
+        out
+    }
+
+    // a def goes into the function table, its op is the index of its entry
+    fn def(
+        &mut self,
+        ret: &AtomType,
+        name: &str,
+        params: &[Ident],
+        body: &[IROp],
+        public: bool,
+    ) -> u32 {
+        let body = self.body(body);
+        let mut entry = Vec::new();
+        uint(&mut entry, self.name(name));
+        uint(&mut entry, self.ty(ret));
+        entry.push(public as u8);
+        self.idents(params, &mut entry);
+        let (offset, len) = self.code_add(body);
+        uint(&mut entry, offset);
+        uint(&mut entry, len);
+
+        self.functions.push(entry);
+        self.functions.len() as u32 - 1
+    }
+
+    fn op(&mut self, op: &IROp, out: &mut Vec<u8>) {
+        out.push(opcode(op));
+        match op {
+            IROp::Import(ty, module, name, params) => {
+                uint(out, self.ty(ty));
+                uint(out, self.name(module));
+                uint(out, self.name(name));
+                uint(out, params.len() as u32);
+                for param in params {
+                    uint(out, self.ty(param));
+                }
+            }
+            IROp::Extern(ty, name, params) => {
+                uint(out, self.ty(ty));
+                uint(out, self.name(name));
+                self.idents(params, out);
+            }
+            IROp::Atom(name, fields, public) => {
+                uint(out, self.name(name));
+                self.idents(fields, out);
+                out.push(*public as u8);
+            }
+            IROp::Def(ret, name, params, body, public) => {
+                let function = self.def(ret, name, params, body, *public);
+                uint(out, function);
+            }
+
+            IROp::Call(ty, count) | IROp::New(ty, count) => {
+                uint(out, self.ty(ty));
+                uint(out, *count as u32);
+            }
+            IROp::Ret(ty)
+            | IROp::Add(ty)
+            | IROp::Sub(ty)
+            | IROp::Mul(ty)
+            | IROp::Div(ty)
+            | IROp::Mod(ty)
+            | IROp::Is(ty)
+            | IROp::Set(ty)
+            | IROp::LoadIdx(ty) => uint(out, self.ty(ty)),
+            IROp::Comp
+            | IROp::EComp
+            | IROp::Eq
+            | IROp::And
+            | IROp::Or
+            | IROp::TypeOf
+            | IROp::Args
+            | IROp::Pop => (),
+
+            IROp::Const(lit) => uint(out, self.lit(lit)),
+            IROp::List(ty, items) => {
+                uint(out, self.ty(ty));
+                uint(out, items.len() as u32);
+                for item in items {
+                    out.extend(self.body(item));
+                }
+            }
+            IROp::Conv(into, from) => {
+                uint(out, self.ty(into));
+                uint(out, self.ty(from));
+            }
+            IROp::Alloc(ty, name)
+            | IROp::Dealloc(ty, name)
+            | IROp::Store(ty, name)
+            | IROp::Load(ty, name)
+            | IROp::LoadProp(ty, name) => {
+                uint(out, self.ty(ty));
+                uint(out, self.name(name));
+            }
+
+            IROp::If(ty, body, alt) => {
+                uint(out, self.ty(ty));
+                out.extend(self.body(body));
+                out.extend(self.body(alt));
+            }
+            IROp::While(cond, body) => {
+                out.extend(self.body(cond));
+                out.extend(self.body(body));
+            }
+        }
+    }
+}
+
+fn opcode(op: &IROp) -> u8 {
+    match op {
+        IROp::Import(..) => 0,
+        IROp::Extern(..) => 1,
+        IROp::Atom(..) => 2,
+        IROp::Def(..) => 3,
+        IROp::Call(..) => 4,
+        IROp::Ret(_) => 5,
+        IROp::Add(_) => 6,
+        IROp::Sub(_) => 7,
+        IROp::Mul(_) => 8,
+        IROp::Div(_) => 9,
+        IROp::Mod(_) => 10,
+        IROp::Comp => 11,
+        IROp::EComp => 12,
+        IROp::Eq => 13,
+        IROp::And => 14,
+        IROp::Or => 15,
+        IROp::Const(_) => 16,
+        IROp::List(..) => 17,
+        IROp::New(..) => 18,
+        IROp::Conv(..) => 19,
+        IROp::Is(_) => 20,
+        IROp::TypeOf => 21,
+        IROp::Args => 22,
+        IROp::Alloc(..) => 23,
+        IROp::Dealloc(..) => 24,
+        IROp::Store(..) => 25,
+        IROp::Set(_) => 26,
+        IROp::Load(..) => 27,
+        IROp::LoadProp(..) => 28,
+        IROp::LoadIdx(_) => 29,
+        IROp::If(..) => 30,
+        IROp::While(..) => 31,
+        IROp::Pop => 32,
+    }
+}
+
+// an entry of the function table
+struct Function {
+    name: String,
+    ret: AtomType,
+    public: bool,
+    params: Vec<Ident>,
+    offset: usize,
+    len: usize,
+}
+
+// bytecode has no lines, errors give the byte they are at in their message
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    pool: Vec<Literal>,
+    types: Vec<AtomType>,
+    functions: Vec<Function>,
+    reading: Vec<usize>, // the functions whose body is being read
+    code: usize,         // where the code starts
+    line: u16,
+    column: u16,
+}
+
+// reads a bytecode file, errors are reported with the byte they are at
+pub fn read(bytes: &[u8]) -> Result<Vec<IROp>, ErrKind> {
+    let mut reader = Reader {
+        bytes,
+        pos: 0,
+        pool: Vec::new(),
+        types: Vec::new(),
+        functions: Vec::new(),
+        reading: Vec::new(),
+        code: 0,
+        line: 0,
+        column: 0,
+    };
+    reader.header()?;
+
+    for _ in 0..reader.uint()? {
+        let lit = reader.constant()?;
+        reader.pool.push(lit);
+    }
+    for _ in 0..reader.uint()? {
+        let ty = reader.type_entry()?;
+        reader.types.push(ty);
+    }
+    for _ in 0..reader.uint()? {
+        let function = reader.function()?;
+        reader.functions.push(function);
+    }
+    let (offset, len) = (reader.uint()? as usize, reader.uint()? as usize);
+    let code_len = reader.uint()? as usize;
+    reader.code = reader.pos;
+    if reader.bytes.len() - reader.code != code_len {
+        let found = reader.bytes.len() - reader.code;
+        err!(
+            reader,
+            ErrKind::Bytecode,
+            format!("the code is {found} bytes, the header says {code_len}")
+        );
+    }
+    reader.code_body(offset, len)
+}
+
+impl Reader<'_> {
+    fn header(&mut self) -> Result<(), ErrKind> {
+        if self.bytes.len() < 6 || &self.bytes[..4] != MAGIC {
+            err!(self, ErrKind::Bytecode, "not a covalent bytecode file");
+        }
+        let version = u16::from_le_bytes([self.bytes[4], self.bytes[5]]);
+        if version != VERSION {
+            err!(
+                self,
+                ErrKind::Bytecode,
+                format!("bytecode version {version} isn't supported, this covalent reads version {VERSION}, build the program again")
+            );
+        }
+        self.pos = 6;
+        Ok(())
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&[u8], ErrKind> {
+        if self.bytes.len() - self.pos < len {
+            err!(
+                self,
+                ErrKind::Bytecode,
+                format!("unexpected end of the file at byte {}", self.pos)
+            );
+        }
+        self.pos += len;
+        Ok(&self.bytes[self.pos - len..self.pos])
+    }
+
+    fn byte(&mut self) -> Result<u8, ErrKind> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn uint(&mut self) -> Result<u32, ErrKind> {
+        let (start, mut n, mut shift) = (self.pos, 0u32, 0);
+        loop {
+            let byte = self.byte()?;
+            if shift > 28 || (shift == 28 && byte > 0x0f) {
+                err!(
+                    self,
+                    ErrKind::Bytecode,
+                    format!("number at byte {start} is too big")
+                );
+            }
+            n |= ((byte & 0x7f) as u32) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+            shift += 7;
+        }
+    }
+
+    fn four(&mut self) -> Result<[u8; 4], ErrKind> {
+        Ok(self.bytes(4)?.try_into().unwrap())
+    }
+
+    fn constant(&mut self) -> Result<Literal, ErrKind> {
+        let start = self.pos;
+        let lit = match self.byte()? {
+            0 => Literal::Int(i32::from_le_bytes(self.four()?)),
+            1 => Literal::Float(f32::from_le_bytes(self.four()?)),
+            2 => {
+                let len = self.uint()? as usize;
+                match String::from_utf8(self.bytes(len)?.to_vec()) {
+                    Ok(s) => Literal::Str(s),
+                    Err(_) => {
+                        err!(
+                            self,
+                            ErrKind::Bytecode,
+                            format!("str at byte {start} isn't utf8")
+                        );
+                    }
+                }
+            }
+            3 => Literal::Bool(self.byte()? != 0),
+            tag => {
+                err!(
+                    self,
+                    ErrKind::Bytecode,
+                    format!("unknown constant {tag} at byte {start}")
+                );
+            }
+        };
+        Ok(lit)
+    }
+
+    fn lit(&mut self) -> Result<Literal, ErrKind> {
+        let start = self.pos;
+        let idx = self.uint()? as usize;
+        match self.pool.get(idx) {
+            Some(lit) => Ok(lit.clone()),
+            None => {
+                err!(
+                    self,
+                    ErrKind::Bytecode,
+                    format!("constant {idx} at byte {start} isn't in the pool")
+                );
+            }
+        }
+    }
+
+    fn name(&mut self) -> Result<String, ErrKind> {
+        let start = self.pos;
+        match self.lit()? {
+            Literal::Str(name) => Ok(name),
+            _ => {
+                err!(
+                    self,
+                    ErrKind::Bytecode,
+                    format!("constant at byte {start} isn't a name")
+                );
+            }
+        }
+    }
+
+    // types only refer to the types before them
+    fn ty(&mut self) -> Result<AtomType, ErrKind> {
+        let start = self.pos;
+        let idx = self.uint()? as usize;
+        match self.types.get(idx) {
+            Some(ty) => Ok(ty.clone()),
+            None => {
+                err!(
+                    self,
+                    ErrKind::Bytecode,
+                    format!("type {idx} at byte {start} isn't in the types before it")
+                );
+            }
+        }
+    }
+
+    fn type_entry(&mut self) -> Result<AtomType, ErrKind> {
+        let start = self.pos;
+        let kind = match self.byte()? {
+            0 => AtomKind::Basic(BasicType::Int),
+            1 => AtomKind::Basic(BasicType::Float),
+            2 => AtomKind::Basic(BasicType::Bool),
+            3 => AtomKind::Basic(BasicType::Void),
+            4 => AtomKind::Dynamic,
+            5 => AtomKind::Any,
+            6 => AtomKind::Unknown,
+            7 => AtomKind::Atom(types::Str.clone()),
+            8 => {
+                let name = self.name()?;
+                let generics = (0..self.uint()?)
+                    .map(|_| self.ty())
+                    .collect::<Result<Vec<_>, _>>()?;
+                let builtin = [&*types::List, &*types::Back, &*types::Const]
+                    .into_iter()
+                    .find(|builtin| builtin.name == name);
+                match builtin {
+                    Some(builtin) => AtomKind::Atom(builtin.spec(&generics)),
+                    None => {
+                        err!(
+                            self,
+                            ErrKind::Bytecode,
+                            format!("unknown built-in type {name} at byte {start}")
+                        );
+                    }
+                }
+            }
+            9 => {
+                let name = self.name()?;
+                let mut fields = IndexMap::new();
+                for _ in 0..self.uint()? {
+                    fields.insert(self.name()?, self.ty()?);
+                }
+                AtomKind::Atom(Atom::new(name, fields, IndexMap::new()))
+            }
+            10 => {
+                let params = (0..self.uint()?)
+                    .map(|_| self.ty())
+                    .collect::<Result<_, _>>()?;
+                AtomKind::Function(FunctionType {
+                    params,
+                    return_type: Box::new(self.ty()?),
+                })
+            }
+            11 => {
+                let item = self.ty()?;
+                AtomKind::Atom(types::List.spec(&[item]).sized(Some(self.uint()?)))
+            }
+            tag => {
+                err!(
+                    self,
+                    ErrKind::Bytecode,
+                    format!("unknown type {tag} at byte {start}")
+                );
+            }
+        };
+        Ok(AtomType {
+            kind,
+            details: None,
+        })
+    }
+
+    fn ident(&mut self) -> Result<Ident, ErrKind> {
+        let start = self.pos;
+        match self.byte()? {
+            0 => Ok(Ident::UnTagged(SymbolId::intern(&self.name()?))),
+            1 => {
+                let ty = self.ty()?;
+                Ok(Ident::Typed(Box::new(ty), SymbolId::intern(&self.name()?)))
+            }
+            tag => {
+                err!(
+                    self,
+                    ErrKind::Bytecode,
+                    format!("unknown ident {tag} at byte {start}")
+                );
+            }
+        }
+    }
+
+    fn idents(&mut self) -> Result<Vec<Ident>, ErrKind> {
+        (0..self.uint()?).map(|_| self.ident()).collect()
+    }
+
+    fn function(&mut self) -> Result<Function, ErrKind> {
+        Ok(Function {
+            name: self.name()?,
+            ret: self.ty()?,
+            public: self.byte()? != 0,
+            params: self.idents()?,
+            offset: self.uint()? as usize,
+            len: self.uint()? as usize,
+        })
+    }
+
+    // the body at offset in the code, it has to be len bytes
+    fn code_body(&mut self, offset: usize, len: usize) -> Result<Vec<IROp>, ErrKind> {
+        let (pos, start) = (self.pos, self.code + offset);
+        if start + len > self.bytes.len() {
+            err!(
+                self,
+                ErrKind::Bytecode,
+                format!("body at code offset {offset} goes past the end of the file")
+            );
+        }
+        self.pos = start;
+        let body = self.body()?;
+        if self.pos != start + len {
+            err!(
+                self,
+                ErrKind::Bytecode,
+                format!("body at code offset {offset} isn't {len} bytes")
+            );
+        }
+        self.pos = pos;
+        Ok(body)
+    }
+
+    fn body(&mut self) -> Result<Vec<IROp>, ErrKind> {
+        (0..self.uint()?).map(|_| self.op()).collect()
+    }
+
+    fn op(&mut self) -> Result<IROp, ErrKind> {
+        let start = self.pos;
+        let op = match self.byte()? {
+            0 => {
+                let (ty, module, name) = (self.ty()?, self.name()?, self.name()?);
+                let params = (0..self.uint()?)
+                    .map(|_| self.ty())
+                    .collect::<Result<_, _>>()?;
+                IROp::Import(ty, module, name, params)
+            }
+            1 => IROp::Extern(self.ty()?, self.name()?, self.idents()?),
+            2 => IROp::Atom(self.name()?, self.idents()?, self.byte()? != 0),
+            3 => {
+                let idx = self.uint()? as usize;
+                let Some(function) = self.functions.get(idx) else {
+                    err!(
+                        self,
+                        ErrKind::Bytecode,
+                        format!("function {idx} at byte {start} isn't in the function table")
+                    );
+                };
+                let (name, ret, public) =
+                    (function.name.clone(), function.ret.clone(), function.public);
+                let (params, offset, len) =
+                    (function.params.clone(), function.offset, function.len);
+                // a body that defines its own function would be read forever
+                if self.reading.contains(&idx) {
+                    err!(
+                        self,
+                        ErrKind::Bytecode,
+                        format!("function {idx} at byte {start} is defined in its own body")
+                    );
+                }
+                self.reading.push(idx);
+                let body = self.code_body(offset, len)?;
+                self.reading.pop();
+                IROp::Def(ret, name, params, body, public)
+            }
+
+            4 => IROp::Call(self.ty()?, self.count()?),
+            5 => IROp::Ret(self.ty()?),
+            6 => IROp::Add(self.ty()?),
+            7 => IROp::Sub(self.ty()?),
+            8 => IROp::Mul(self.ty()?),
+            9 => IROp::Div(self.ty()?),
+            10 => IROp::Mod(self.ty()?),
+            11 => IROp::Comp,
+            12 => IROp::EComp,
+            13 => IROp::Eq,
+            14 => IROp::And,
+            15 => IROp::Or,
+
+            16 => IROp::Const(self.lit()?),
+            17 => {
+                let ty = self.ty()?;
+                let items = (0..self.uint()?)
+                    .map(|_| self.body())
+                    .collect::<Result<_, _>>()?;
+                IROp::List(ty, items)
+            }
+            18 => IROp::New(self.ty()?, self.count()?),
+            19 => IROp::Conv(self.ty()?, self.ty()?),
+            20 => IROp::Is(self.ty()?),
+            21 => IROp::TypeOf,
+            22 => IROp::Args,
+            23 => IROp::Alloc(self.ty()?, self.name()?),
+            24 => IROp::Dealloc(self.ty()?, self.name()?),
+            25 => IROp::Store(self.ty()?, self.name()?),
+            26 => IROp::Set(self.ty()?),
+            27 => IROp::Load(self.ty()?, self.name()?),
+            28 => IROp::LoadProp(self.ty()?, self.name()?),
+            29 => IROp::LoadIdx(self.ty()?),
+
+            30 => IROp::If(self.ty()?, self.body()?, self.body()?),
+            31 => IROp::While(self.body()?, self.body()?),
+            32 => IROp::Pop,
+            opcode => {
+                err!(
+                    self,
+                    ErrKind::Bytecode,
+                    format!("unknown opcode {opcode} at byte {start}")
+                );
+            }
+        };
+        Ok(op)
+    }
+
+    fn count(&mut self) -> Result<u16, ErrKind> {
+        let start = self.pos;
+        match u16::try_from(self.uint()?) {
+            Ok(count) => Ok(count),
+            Err(_) => {
+                err!(
+                    self,
+                    ErrKind::Bytecode,
+                    format!("count at byte {start} is too big")
+                );
+            }
+        }
+    }
+}
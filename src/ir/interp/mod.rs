@@ -0,0 +1,417 @@
+// an interpreter that runs the IR directly, without a backend or a C toolchain
+//
+// values behave like the ones of the C runtime (std.c), lists, atoms and strs are shared like
+// the pointers the generated C passes around, and runtime errors have the same messages and
+// exit codes. externs call host functions registered by name
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{BufRead, Write};
+use std::rc::Rc;
+
+use super::IROp;
+use crate::parser::ast::Literal;
+use crate::types::{AtomKind, AtomType, BasicType};
+
+mod runtime;
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Int(i32),
+    Float(f32),
+    Bool(bool),
+    Str(Rc<str>),
+    List(Rc<RefCell<Vec<Value>>>),
+    Atom(Rc<Object>),
+    Func(Rc<Function>),
+    Dyn(Rc<Boxed>),
+    Void, // what void calls return
+}
+
+// an atom, its fields are in the order of the fields of its type
+#[derive(Debug)]
+pub struct Object {
+    pub ty: AtomType,
+    pub fields: RefCell<Vec<Value>>,
+}
+
+// a def, runtime function or extern and its type
+#[derive(Debug)]
+pub struct Function {
+    pub name: String,
+    pub ty: AtomType,
+}
+
+// the value a Dynamic holds and its type
+#[derive(Debug)]
+pub struct Boxed {
+    pub ty: AtomType,
+    pub value: Value,
+}
+
+// writes a value like writeln in the C runtime does
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(i) => write!(f, "{i}"),
+            Value::Float(float) => write!(f, "{:.6}", *float as f64),
+            Value::Str(s) => write!(f, "{s}"),
+            Value::Bool(b) => write!(f, "{b}"),
+            Value::List(list) => {
+                write!(f, "[")?;
+                for (i, item) in list.borrow().iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                write!(f, "]")
+            }
+            Value::Atom(object) => {
+                write!(f, "{} {{ ", object.ty.kind)?;
+                for (i, (name, value)) in object
+                    .names()
+                    .zip(object.fields.borrow().iter())
+                    .enumerate()
+                {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{name}: {value}")?;
+                }
+                write!(f, " }}")
+            }
+            Value::Func(func) => write!(f, "<{}>", func.ty.kind),
+            Value::Dyn(boxed) => write!(f, "{}", boxed.value),
+            Value::Void => Ok(()),
+        }
+    }
+}
+
+impl Object {
+    fn names(&self) -> impl Iterator<Item = &String> {
+        match &self.ty.kind {
+            AtomKind::Atom(atom) => Some(atom.fields.keys()),
+            _ => None,
+        }
+        .into_iter()
+        .flatten()
+    }
+}
+
+// a runtime error, the program stops and exits with code
+#[derive(Debug)]
+pub struct Trap {
+    pub msg: String,
+    pub code: i32,
+}
+
+impl Trap {
+    pub fn new(msg: impl Into<String>, code: i32) -> Self {
+        Self {
+            msg: msg.into(),
+            code,
+        }
+    }
+}
+
+impl fmt::Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "covalent runtime error: {}", self.msg)
+    }
+}
+
+// a function an extern calls, gets the args of the call and returns its value
+pub type Host = fn(&mut Interpreter<'_>, Vec<Value>) -> Result<Value, Trap>;
+
+struct Def {
+    params: Vec<String>,
+    body: Vec<IROp>,
+}
+
+// the defs and the top level code of a program, names are resolved when it is made so it can
+// run on another thread than the one that compiled it
+pub struct Program {
+    defs: HashMap<String, Def>,
+    top: Vec<IROp>,
+}
+
+impl Program {
+    pub fn new(ir: &[IROp]) -> Self {
+        let mut program = Self {
+            defs: HashMap::new(),
+            top: Vec::new(),
+        };
+        for op in ir {
+            match op {
+                IROp::Def(_, name, params, body, _) => {
+                    let params = params.iter().map(|param| param.clone().tuple().1).collect();
+                    let def = Def {
+                        params,
+                        body: body.clone(),
+                    };
+                    program.defs.insert(name.clone(), def);
+                }
+                IROp::Import(..) | IROp::Extern(..) | IROp::Atom(..) => (),
+                op => program.top.push(op.clone()),
+            }
+        }
+        program
+    }
+}
+
+// where a value on the stack was loaded from, the target of a set
+enum Place {
+    Var(String),
+    Prop(Rc<Object>, usize),
+    Index(Rc<RefCell<Vec<Value>>>, usize),
+}
+
+// a value on the stack
+struct Entry {
+    value: Value,
+    place: Option<Place>,
+}
+
+enum Flow {
+    Next,
+    Ret(Value),
+}
+
+type Vars = HashMap<String, Value>;
+
+pub struct Interpreter<'a> {
+    program: &'a Program,
+    out: &'a mut dyn Write,
+    input: &'a mut dyn BufRead,
+    hosts: HashMap<String, Host>,
+    args: Vec<String>,
+}
+
+impl<'a> Interpreter<'a> {
+    // writeln writes to out and the default hosts read from input
+    pub fn new(program: &'a Program, out: &'a mut dyn Write, input: &'a mut dyn BufRead) -> Self {
+        let mut interp = Self {
+            program,
+            out,
+            input,
+            hosts: HashMap::new(),
+            args: Vec::new(),
+        };
+        for (name, host) in runtime::HOSTS {
+            interp.register(name, host);
+        }
+        interp
+    }
+
+    // externs named name call host, replaces the host registered before
+    pub fn register(&mut self, name: &str, host: Host) {
+        self.hosts.insert(name.to_string(), host);
+    }
+
+    // runs the top level code with args as the command line args (the program name first),
+    // returns the exit code of its ret or 0
+    pub fn run(&mut self, args: Vec<String>) -> Result<i32, Trap> {
+        self.args = args;
+        match self.body(&self.program.top, &mut Vars::new())? {
+            Flow::Ret(Value::Int(code)) => Ok(code),
+            _ => Ok(0),
+        }
+    }
+
+    fn body(&mut self, ops: &[IROp], vars: &mut Vars) -> Result<Flow, Trap> {
+        let mut stack = Vec::new();
+        for op in ops {
+            if let Flow::Ret(value) = self.op(op, &mut stack, vars)? {
+                return Ok(Flow::Ret(value));
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    // the value the ops leave on the stack
+    fn eval(&mut self, ops: &[IROp], vars: &mut Vars) -> Result<Value, Trap> {
+        let mut stack = Vec::new();
+        for op in ops {
+            self.op(op, &mut stack, vars)?;
+        }
+        Ok(pop(&mut stack, 1)?.remove(0).value)
+    }
+
+    // the ops that run bodies or call are here, the others are in expr so the frames of the
+    // functions a call recurses through stay small
+    fn op(&mut self, op: &IROp, stack: &mut Vec<Entry>, vars: &mut Vars) -> Result<Flow, Trap> {
+        match op {
+            IROp::Call(ty, count) => {
+                let callee = pop(stack, 1)?.remove(0);
+                let args = pop(stack, *count as usize)?;
+                let args = args.into_iter().map(|arg| arg.value).collect();
+                let value = self.call(callee.value, args)?;
+                if ty.kind != AtomKind::Basic(BasicType::Void) {
+                    push(stack, value, None);
+                }
+            }
+            IROp::Ret(_) => {
+                let value = stack.pop().map_or(Value::Void, |entry| entry.value);
+                return Ok(Flow::Ret(value));
+            }
+            IROp::List(_, items) => {
+                let items = items
+                    .iter()
+                    .map(|item| self.eval(item, vars))
+                    .collect::<Result<_, _>>()?;
+                push(stack, Value::List(Rc::new(RefCell::new(items))), None);
+            }
+            IROp::If(_, body, alt) => {
+                let cond = pop(stack, 1)?.remove(0).value;
+                let body = if runtime::truthy(&cond) { body } else { alt };
+                return self.body(body, vars);
+            }
+            IROp::While(cond, body) => {
+                while runtime::truthy(&self.eval(cond, vars)?) {
+                    if let Flow::Ret(value) = self.body(body, vars)? {
+                        return Ok(Flow::Ret(value));
+                    }
+                }
+            }
+            op => self.expr(op, stack, vars)?,
+        }
+        Ok(Flow::Next)
+    }
+
+    fn expr(&mut self, op: &IROp, stack: &mut Vec<Entry>, vars: &mut Vars) -> Result<(), Trap> {
+        match op {
+            IROp::Import(..) | IROp::Extern(..) | IROp::Atom(..) | IROp::Def(..) => (),
+            IROp::Alloc(..) | IROp::Dealloc(..) => (),
+
+            IROp::Const(lit) => {
+                let value = match lit {
+                    Literal::Int(int) => Value::Int(*int),
+                    Literal::Float(float) => Value::Float(*float),
+                    Literal::Str(s) => Value::Str(s.as_str().into()),
+                    Literal::Bool(b) => Value::Bool(*b),
+                };
+                push(stack, value, None);
+            }
+            IROp::New(ty, count) => {
+                let fields = pop(stack, *count as usize)?;
+                let object = Object {
+                    ty: ty.clone(),
+                    fields: RefCell::new(fields.into_iter().map(|field| field.value).collect()),
+                };
+                push(stack, Value::Atom(Rc::new(object)), None);
+            }
+            IROp::Args => {
+                let args = self.args.iter().map(|arg| Value::Str(arg.as_str().into()));
+                let list = Value::List(Rc::new(RefCell::new(args.collect())));
+                push(stack, list, None);
+            }
+
+            IROp::Store(_, name) => {
+                let value = pop(stack, 1)?.remove(0).value;
+                vars.insert(name.clone(), value);
+            }
+            IROp::Set(_) => {
+                let mut popped = pop(stack, 2)?;
+                let value = popped.pop().unwrap().value;
+                match popped.pop().unwrap().place {
+                    Some(Place::Var(name)) => {
+                        vars.insert(name, value);
+                    }
+                    Some(Place::Prop(object, idx)) => object.fields.borrow_mut()[idx] = value,
+                    Some(Place::Index(list, idx)) => list.borrow_mut()[idx] = value,
+                    None => return Err(Trap::new("set of a value that wasn't loaded", 1)),
+                }
+            }
+            IROp::Load(ty, name) => match vars.get(name) {
+                Some(value) => push(stack, value.clone(), Some(Place::Var(name.clone()))),
+                None => {
+                    let func = Function {
+                        name: name.clone(),
+                        ty: ty.clone(),
+                    };
+                    push(stack, Value::Func(Rc::new(func)), None);
+                }
+            },
+            IROp::LoadProp(_, name) => {
+                let entry = pop(stack, 1)?.remove(0);
+                let (value, place) = runtime::prop(entry.value, name)?;
+                push(stack, value, place);
+            }
+            IROp::LoadIdx(_) => {
+                let popped = pop(stack, 2)?;
+                let [list, idx] = <[Entry; 2]>::try_from(popped).ok().unwrap();
+                let (value, place) = runtime::index(list.value, idx.value)?;
+                push(stack, value, Some(place));
+            }
+
+            IROp::Conv(into, from) => {
+                let entry = pop(stack, 1)?.remove(0);
+                push(stack, runtime::conv(entry.value, into, from)?, None);
+            }
+            IROp::Is(ty) => {
+                let entry = pop(stack, 1)?.remove(0);
+                let is = match &entry.value {
+                    Value::Dyn(boxed) => runtime::is(boxed, ty),
+                    _ => false,
+                };
+                push(stack, Value::Bool(is), None);
+            }
+            IROp::TypeOf => {
+                let entry = pop(stack, 1)?.remove(0);
+                let name = runtime::type_name(&entry.value);
+                push(stack, Value::Str(name.into()), None);
+            }
+            IROp::Pop => {
+                stack.pop();
+            }
+
+            // the binary ops, the left operand is on top
+            op => {
+                let popped = pop(stack, 2)?;
+                let [right, left] = <[Entry; 2]>::try_from(popped).ok().unwrap();
+                push(stack, runtime::binary(op, left.value, right.value)?, None);
+            }
+        }
+        Ok(())
+    }
+
+    fn call(&mut self, callee: Value, args: Vec<Value>) -> Result<Value, Trap> {
+        let Value::Func(func) = callee else {
+            return Err(Trap::new("call of a value that isn't a function", 1));
+        };
+
+        let name = &func.name;
+        let program = self.program;
+        if let Some(def) = program.defs.get(name) {
+            let mut vars = def.params.iter().cloned().zip(args).collect();
+            return match self.body(&def.body, &mut vars)? {
+                Flow::Ret(value) => Ok(value),
+                Flow::Next => Ok(Value::Void),
+            };
+        }
+        if let Some(result) = runtime::builtin(self, name, &func.ty, &args) {
+            return result;
+        }
+        match self.hosts.get(name) {
+            Some(host) => host(self, args),
+            None => Err(Trap::new(
+                format!("{name} has no definition and no registered host function"),
+                12,
+            )),
+        }
+    }
+}
+
+fn push(stack: &mut Vec<Entry>, value: Value, place: Option<Place>) {
+    stack.push(Entry { value, place });
+}
+
+// pops count entries, the deepest first
+fn pop(stack: &mut Vec<Entry>, count: usize) -> Result<Vec<Entry>, Trap> {
+    if stack.len() < count {
+        return Err(Trap::new("stack underflow", 1));
+    }
+    Ok(stack.split_off(stack.len() - count))
+}
@@ -0,0 +1,437 @@
+// the built-ins of the C runtime (std.c) for the interpreter, ops on Dynamic values,
+// conversions, lists and the default host functions
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use super::{Boxed, Host, Interpreter, Place, Trap, Value};
+use crate::ir::IROp;
+use crate::types::{self, AtomKind, AtomType, BasicType};
+
+// the C functions the examples declare with extern
+pub const HOSTS: [(&str, Host); 3] = [
+    ("cprompt", cprompt),
+    ("atoi", atoi),
+    ("strtocstr", strtocstr),
+];
+
+// prints msg and reads a word
+fn cprompt(interp: &mut Interpreter<'_>, args: Vec<Value>) -> Result<Value, Trap> {
+    let _ = write!(interp.out, "{}", args[0]);
+    let _ = interp.out.flush();
+
+    let mut word = Vec::new();
+    while let Some(&byte) = interp.input.fill_buf().ok().and_then(|buf| buf.first()) {
+        if byte.is_ascii_whitespace() {
+            if !word.is_empty() {
+                break;
+            }
+        } else if word.len() < 255 {
+            word.push(byte);
+        }
+        interp.input.consume(1);
+    }
+    Ok(Value::Str(String::from_utf8_lossy(&word).into()))
+}
+
+// the int at the start of a str after whitespace, 0 if there is none
+fn atoi(_: &mut Interpreter<'_>, args: Vec<Value>) -> Result<Value, Trap> {
+    let s = args[0].to_string();
+    let s = s.trim_start();
+    let (sign, digits) = match s.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, s.strip_prefix('+').unwrap_or(s)),
+    };
+    let int = digits
+        .bytes()
+        .take_while(u8::is_ascii_digit)
+        .fold(0i32, |int, digit| {
+            int.wrapping_mul(10).wrapping_add((digit - b'0') as i32)
+        });
+    Ok(Value::Int(int.wrapping_mul(sign)))
+}
+
+// Back(str) is the same value as str for the interpreter
+fn strtocstr(_: &mut Interpreter<'_>, mut args: Vec<Value>) -> Result<Value, Trap> {
+    Ok(args.remove(0))
+}
+
+// the runtime functions the IR calls without an extern, None if name isn't one
+pub fn builtin(
+    interp: &mut Interpreter<'_>,
+    name: &str,
+    ty: &AtomType,
+    args: &[Value],
+) -> Option<Result<Value, Trap>> {
+    let result = match name {
+        "writeln" => {
+            let _ = writeln!(interp.out, "{}", args[0]);
+            Ok(Value::Void)
+        }
+        "Listdotpush" => {
+            let Value::List(list) = &args[0] else {
+                return None;
+            };
+            // only List(Dynamic) keeps the Dynamic, other lists keep the value it holds
+            let keeps = match &ty.kind {
+                AtomKind::Function(func) => match &func.params[0].kind {
+                    AtomKind::Atom(atom) => atom.generics[0].kind == AtomKind::Dynamic,
+                    _ => false,
+                },
+                _ => false,
+            };
+            let item = match &args[1] {
+                Value::Dyn(boxed) if !keeps => boxed.value.clone(),
+                item => item.clone(),
+            };
+            list.borrow_mut().push(item);
+            Ok(args[0].clone())
+        }
+        "Listdotpop" => {
+            let Value::List(list) = &args[0] else {
+                return None;
+            };
+            match list.borrow_mut().pop() {
+                Some(_) => Ok(args[0].clone()),
+                None => Err(Trap::new("cannot pop from an empty list", 8)),
+            }
+        }
+        _ => return None,
+    };
+    Some(result)
+}
+
+// the name of a type at runtime, what typeof gives
+fn name(ty: &AtomType) -> String {
+    ty.kind.to_string()
+}
+
+pub fn type_name(value: &Value) -> String {
+    match value {
+        Value::Dyn(boxed) => name(&boxed.ty),
+        _ => "unknown".to_string(),
+    }
+}
+
+// if a Dynamic holds a value of type ty
+pub fn is(boxed: &Boxed, ty: &AtomType) -> bool {
+    ty.kind == AtomKind::Dynamic || types::ty_code(&boxed.ty) == types::ty_code(ty)
+}
+
+pub fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Bool(b) => *b,
+        Value::Int(i) => *i != 0,
+        Value::Dyn(boxed) => truthy(&boxed.value),
+        _ => true,
+    }
+}
+
+pub fn prop(value: Value, name: &str) -> Result<(Value, Option<Place>), Trap> {
+    match value {
+        Value::Atom(object) => {
+            let idx = object
+                .names()
+                .position(|field| field == name)
+                .ok_or_else(|| Trap::new(format!("{} has no field {name}", object.ty), 1))?;
+            let value = object.fields.borrow()[idx].clone();
+            Ok((value, Some(Place::Prop(object, idx))))
+        }
+        Value::List(list) if name == "size" => Ok((Value::Int(list.borrow().len() as i32), None)),
+        Value::Str(s) if name == "size" => Ok((Value::Int(s.len() as i32), None)),
+        value => Err(Trap::new(
+            format!("{} has no field {name}", type_of(&value)),
+            1,
+        )),
+    }
+}
+
+pub fn index(list: Value, idx: Value) -> Result<(Value, Place), Trap> {
+    let (Value::List(list), Value::Int(idx)) = (list, idx) else {
+        return Err(Trap::new("only lists can be indexed by an int", 1));
+    };
+    let size = list.borrow().len();
+    if idx < 0 || idx as usize >= size {
+        return Err(Trap::new(
+            format!("index {idx} is out of bounds for a list of size {size}"),
+            10,
+        ));
+    }
+    let value = list.borrow()[idx as usize].clone();
+    Ok((value, Place::Index(list, idx as usize)))
+}
+
+// the type of a value that isn't a Dynamic, lists, atoms and functions are only known by kind
+fn type_of(value: &Value) -> &'static str {
+    match value {
+        Value::Int(_) => "int",
+        Value::Float(_) => "float",
+        Value::Bool(_) => "bool",
+        Value::Str(_) => "str",
+        Value::List(_) => "List",
+        Value::Atom(_) => "atom",
+        Value::Func(..) => "function",
+        Value::Dyn(_) => "Dynamic",
+        Value::Void => "void",
+    }
+}
+
+fn op_name(op: &IROp) -> &'static str {
+    match op {
+        IROp::Add(_) => "add",
+        IROp::Sub(_) => "sub",
+        IROp::Mul(_) => "mul",
+        IROp::Div(_) => "div",
+        IROp::Mod(_) => "mod",
+        IROp::Comp => "comp",
+        IROp::EComp => "ecomp",
+        IROp::Eq => "eq",
+        IROp::And => "and",
+        IROp::Or => "or",
+        _ => unreachable!("{op:?} isn't a binary op"),
+    }
+}
+
+pub fn binary(op: &IROp, left: Value, right: Value) -> Result<Value, Trap> {
+    if let (Value::Dyn(left), Value::Dyn(right)) = (&left, &right) {
+        return dynamic(op, left, right);
+    }
+
+    match (op, &left, &right) {
+        // lists, atoms and functions are pointers in C
+        (IROp::Eq, Value::List(l), Value::List(r)) => Ok(Value::Bool(Rc::ptr_eq(l, r))),
+        (IROp::Eq, Value::Atom(l), Value::Atom(r)) => Ok(Value::Bool(Rc::ptr_eq(l, r))),
+        (IROp::Eq, Value::Func(l), Value::Func(r)) => Ok(Value::Bool(l.name == r.name)),
+        _ => apply(op, &left, &right).unwrap_or_else(|| {
+            Err(Trap::new(
+                format!(
+                    "unsupported operands for {}: {} and {}",
+                    op_name(op),
+                    type_of(&left),
+                    type_of(&right)
+                ),
+                9,
+            ))
+        }),
+    }
+}
+
+// an op on two values of the same basic type or strs, None if the op doesn't take them
+fn apply(op: &IROp, left: &Value, right: &Value) -> Option<Result<Value, Trap>> {
+    use Value::{Bool, Float, Int, Str};
+
+    let div_zero = || Some(Err(Trap::new("division by zero", 11)));
+    let value = match (op, left, right) {
+        (IROp::Add(_), Int(l), Int(r)) => Int(l.wrapping_add(*r)),
+        (IROp::Sub(_), Int(l), Int(r)) => Int(l.wrapping_sub(*r)),
+        (IROp::Mul(_), Int(l), Int(r)) => Int(l.wrapping_mul(*r)),
+        (IROp::Div(_) | IROp::Mod(_), Int(_), Int(0)) => return div_zero(),
+        (IROp::Div(_), Int(l), Int(r)) => Int(l.wrapping_div(*r)),
+        (IROp::Mod(_), Int(l), Int(r)) => Int(l.wrapping_rem(*r)),
+
+        (IROp::Add(_), Float(l), Float(r)) => Float(l + r),
+        (IROp::Sub(_), Float(l), Float(r)) => Float(l - r),
+        (IROp::Mul(_), Float(l), Float(r)) => Float(l * r),
+        (IROp::Div(_), Float(l), Float(r)) => Float(l / r),
+
+        (IROp::Comp, Int(l), Int(r)) => Bool(l > r),
+        (IROp::EComp, Int(l), Int(r)) => Bool(l >= r),
+        (IROp::Eq, Int(l), Int(r)) => Bool(l == r),
+        (IROp::Comp, Float(l), Float(r)) => Bool(l > r),
+        (IROp::EComp, Float(l), Float(r)) => Bool(l >= r),
+        (IROp::Eq, Float(l), Float(r)) => Bool(l == r),
+        (IROp::Comp, Bool(l), Bool(r)) => Bool(l > r),
+        (IROp::EComp, Bool(l), Bool(r)) => Bool(l >= r),
+        (IROp::Eq, Bool(l), Bool(r)) => Bool(l == r),
+        (IROp::And, Bool(l), Bool(r)) => Bool(*l && *r),
+        (IROp::Or, Bool(l), Bool(r)) => Bool(*l || *r),
+
+        // strs compare by length, ecomp is also true for the same str
+        (IROp::Add(_), Str(l), Str(r)) => Str(format!("{l}{r}").into()),
+        (IROp::Comp, Str(l), Str(r)) => Bool(l.len() > r.len()),
+        (IROp::EComp, Str(l), Str(r)) => Bool(l.len() > r.len() || Rc::ptr_eq(l, r)),
+        (IROp::Eq, Str(l), Str(r)) => Bool(l == r),
+        _ => return None,
+    };
+    Some(Ok(value))
+}
+
+// an op on two Dynamic values, ints are converted into floats to operate on floats and
+// values of other types can't be mixed, except by == which is false for them
+fn dynamic(op: &IROp, left: &Boxed, right: &Boxed) -> Result<Value, Trap> {
+    let float = || AtomType {
+        kind: AtomKind::Basic(BasicType::Float),
+        details: None,
+    };
+    let (ty, l, r) = match (&left.value, &right.value) {
+        (Value::Int(l), Value::Float(_)) => (float(), Value::Float(*l as f32), right.value.clone()),
+        (Value::Float(_), Value::Int(r)) => (float(), left.value.clone(), Value::Float(*r as f32)),
+        _ if types::ty_code(&left.ty) == types::ty_code(&right.ty) => {
+            (left.ty.clone(), left.value.clone(), right.value.clone())
+        }
+        // values of different types are never equal
+        _ if *op == IROp::Eq => return Ok(Value::Bool(false)),
+        _ => {
+            return Err(Trap::new(
+                format!(
+                    "cannot operate on {} and {}",
+                    name(&left.ty),
+                    name(&right.ty)
+                ),
+                5,
+            ))
+        }
+    };
+
+    let unsupported = || {
+        Err(Trap::new(
+            format!(
+                "unsupported operand for {}: Dynamic holding {}",
+                op_name(op),
+                name(&ty)
+            ),
+            9,
+        ))
+    };
+    let value = match (op, &l, &r) {
+        (IROp::Eq, _, _) => return Ok(Value::Bool(equal(&l, &r)?)),
+        (IROp::Comp | IROp::EComp, Value::List(_) | Value::Atom(_) | Value::Func(..), _) => {
+            return unsupported()
+        }
+        (IROp::Add(_), Value::List(l), Value::List(r)) => {
+            let items = l
+                .borrow()
+                .iter()
+                .chain(r.borrow().iter())
+                .cloned()
+                .collect();
+            Value::List(Rc::new(RefCell::new(items)))
+        }
+        // floats only have the arithmetic ops of ints besides mod
+        (IROp::Mod(_), Value::Float(_), _) => return unsupported(),
+        _ => match apply(op, &l, &r) {
+            Some(value) => value?,
+            None => return unsupported(),
+        },
+    };
+    Ok(match (op, value) {
+        (IROp::Comp | IROp::EComp, value) => value,
+        // the C runtime boxes the result of and and or as an int
+        (IROp::And | IROp::Or, Value::Bool(b)) => boxed(int(), Value::Int(b as i32)),
+        (_, value) => boxed(ty, value),
+    })
+}
+
+fn int() -> AtomType {
+    AtomType {
+        kind: AtomKind::Basic(BasicType::Int),
+        details: None,
+    }
+}
+
+fn boxed(ty: AtomType, value: Value) -> Value {
+    Value::Dyn(Rc::new(Boxed { ty, value }))
+}
+
+// lists and atoms are equal if all their items are, functions if they are the same function
+fn equal(left: &Value, right: &Value) -> Result<bool, Trap> {
+    match (left, right) {
+        (Value::List(l), Value::List(r)) => {
+            let (l, r) = (l.borrow(), r.borrow());
+            if l.len() != r.len() {
+                return Ok(false);
+            }
+            for (l, r) in l.iter().zip(r.iter()) {
+                if !equal(l, r)? {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+        (Value::Atom(l), Value::Atom(r)) => {
+            for (l, r) in l.fields.borrow().iter().zip(r.fields.borrow().iter()) {
+                if !equal(l, r)? {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+        (Value::Func(l), Value::Func(r)) => Ok(l.name == r.name),
+        (Value::Dyn(l), Value::Dyn(r)) => Ok(truthy(&dynamic(&IROp::Eq, l, r)?)),
+        _ => match apply(&IROp::Eq, left, right) {
+            Some(value) => Ok(truthy(&value?)),
+            None => Ok(false),
+        },
+    }
+}
+
+// converts value of type from into a value of type into
+pub fn conv(value: Value, into: &AtomType, from: &AtomType) -> Result<Value, Trap> {
+    if into == from {
+        return Ok(value);
+    }
+
+    let value = match (&into.kind, &from.kind, value) {
+        (AtomKind::Dynamic, _, value) => boxed(from.clone(), value),
+        (_, AtomKind::Dynamic, Value::Dyn(boxed)) => {
+            if !is(&boxed, into) {
+                return Err(Trap::new(
+                    format!(
+                        "cannot downcast Dynamic holding {} into {}",
+                        name(&boxed.ty),
+                        name(into)
+                    ),
+                    7,
+                ));
+            }
+            boxed.value.clone()
+        }
+
+        (AtomKind::Basic(BasicType::Bool), AtomKind::Basic(_), value) => Value::Bool(match value {
+            Value::Int(i) => i != 0,
+            Value::Float(f) => f != 0.0,
+            value => truthy(&value),
+        }),
+        (AtomKind::Basic(BasicType::Int), AtomKind::Basic(_), value) => Value::Int(match value {
+            Value::Float(f) => f as i32,
+            Value::Bool(b) => b as i32,
+            value => return Ok(value),
+        }),
+        (AtomKind::Basic(BasicType::Float), AtomKind::Basic(_), value) => {
+            Value::Float(match value {
+                Value::Int(i) => i as f32,
+                Value::Bool(b) => b as i32 as f32,
+                value => return Ok(value),
+            })
+        }
+
+        (AtomKind::Atom(atom), _, value) if atom == &*types::Str => match value {
+            Value::Int(_) | Value::Float(_) | Value::Bool(_) => {
+                Value::Str(value.to_string().into())
+            }
+            value => value,
+        },
+        (AtomKind::Basic(basic), AtomKind::Atom(atom), Value::Str(s)) if atom == &*types::Str => {
+            parse(&s, *basic)?
+        }
+
+        // Back, Const and lists of a fixed size are the same values for the interpreter
+        (_, _, value) => value,
+    };
+    Ok(value)
+}
+
+// a str into an int, float or bool, the whole str has to be the value
+fn parse(s: &str, into: BasicType) -> Result<Value, Trap> {
+    let value = match into {
+        BasicType::Int => s.parse::<i32>().ok().map(Value::Int),
+        BasicType::Float => s.parse::<f32>().ok().map(Value::Float),
+        BasicType::Bool => match s {
+            "true" => Some(Value::Bool(true)),
+            "false" => Some(Value::Bool(false)),
+            _ => None,
+        },
+        BasicType::Void => None,
+    };
+    value.ok_or_else(|| Trap::new(format!("cannot convert str \"{s}\" into {into}"), 6))
+}
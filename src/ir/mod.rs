@@ -1,16 +1,23 @@
 use crate::parser::ast::{Ident, Literal};
 
+use std::collections::HashMap;
+
 use crate::enviroment::Enviroment;
 use crate::types::{self, AtomKind, AtomType, BasicType};
 
+pub mod bytecode;
 pub mod gen;
+pub mod interp;
+pub mod ssa;
+pub mod text;
 pub mod tools;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum IROp {
     Import(AtomType, String, String, Vec<AtomType>), // ty mod fun arg count
     Extern(AtomType, String, Vec<Ident>),
-    Def(AtomType, String, Vec<Ident>, Vec<IROp>),
+    Atom(String, Vec<Ident>, bool), // declares a user atom and its fields, public if declared with pub
+    Def(AtomType, String, Vec<Ident>, Vec<IROp>, bool),
 
     Call(AtomType, u16),
     Ret(AtomType),
@@ -29,7 +36,11 @@ pub enum IROp {
 
     Const(Literal),
     List(AtomType, Vec<Vec<IROp>>), // each item is a bunch of operations
+    New(AtomType, u16),             // constructs an atom from field count values
     Conv(AtomType, AtomType),
+    Is(AtomType), // if the Dynamic value on the stack holds a value of type
+    TypeOf,       // the name of the type held by the Dynamic value on the stack
+    Args,         // the command line args as a List(str), only in top level code
     Alloc(AtomType, String),
     Dealloc(AtomType, String), // when allocing a var with a new type we dealloc the old val
     Store(AtomType, String),
@@ -39,10 +50,9 @@ pub enum IROp {
     LoadIdx(AtomType),          // loads an index
 
     If(AtomType, Vec<IROp>, Vec<IROp>),
-    While(Vec<IROp>),
+    While(Vec<IROp>, Vec<IROp>), // the condition, run before every iteration, and the body
     Pop,
 }
-use crate::err::ATErr;
 
 use self::IROp::*;
 // TODO: better op impl
@@ -59,7 +69,8 @@ pub fn get_op_type(op: &IROp) -> AtomType {
     match op {
         Import(t, _, _, _) => t,
         Extern(t, _, _) => t,
-        Def(t, _, _, _) => t,
+        Atom(_, _, _) => &void,
+        Def(t, _, _, _, _) => t,
 
         Call(t, _) => t,
         Ret(t) => t,
@@ -85,7 +96,25 @@ pub fn get_op_type(op: &IROp) -> AtomType {
         }
 
         Const(lit) => return lit.get_ty(),
+        New(t, _) => t,
         Conv(t, _) => t,
+        Is(_) => &bool,
+        TypeOf => {
+            return AtomType {
+                kind: AtomKind::Atom(types::Str.clone()),
+                details: None,
+            }
+        }
+        Args => {
+            let str = AtomType {
+                kind: AtomKind::Atom(types::Str.clone()),
+                details: None,
+            };
+            return AtomType {
+                kind: AtomKind::Atom(types::List.spec(&[str])),
+                details: None,
+            };
+        }
         Store(t, _) => t,
         Set(t) => t,
         Load(t, _) => t,
@@ -95,7 +124,7 @@ pub fn get_op_type(op: &IROp) -> AtomType {
         Alloc(t, _) => t,
         Dealloc(t, _) => t,
         If(t, _, _) => t,
-        While(_) => &void,
+        While(..) => &void,
         Pop => &void,
     }
     .clone()
@@ -103,14 +132,53 @@ pub fn get_op_type(op: &IROp) -> AtomType {
 
 pub struct Codegen {
     env: Enviroment,
-    _warnings: Vec<ATErr>, // program can continue error
+    names: Vec<HashMap<String, String>>, // variable => its C name, for each open scope
+    declared: Vec<String>, // C names of the variables declared in the current function
 }
 
 impl Codegen {
     pub fn new() -> Self {
         Self {
-            env: Enviroment::new(None),
-            _warnings: Vec::new(),
+            env: Enviroment::new(),
+            names: vec![HashMap::new()],
+            declared: Vec::new(),
         }
     }
+
+    fn enter(&mut self) {
+        self.env.child();
+        self.names.push(HashMap::new());
+    }
+
+    fn leave(&mut self) {
+        self.env.parent();
+        self.names.pop();
+    }
+
+    // every declaration in a function gets its own C variable, a name declared again
+    // (shadowing, or in a sibling block) becomes name__N
+    fn declare(&mut self, name: &str) -> String {
+        let mut c_name = name.to_string();
+        let mut n = 0;
+        while self.declared.contains(&c_name) {
+            n += 1;
+            c_name = format!("{name}__{n}");
+        }
+
+        self.declared.push(c_name.clone());
+        self.names
+            .last_mut()
+            .unwrap()
+            .insert(name.to_string(), c_name.clone());
+        c_name
+    }
+
+    // the C name of a variable, functions and globals keep their name
+    fn c_name(&self, name: &str) -> String {
+        self.names
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).cloned())
+            .unwrap_or(name.to_string())
+    }
 }
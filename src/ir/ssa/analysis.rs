@@ -0,0 +1,212 @@
+// analyses over a lowered function, liveness, escape and constant propagation
+
+use std::collections::{BTreeSet, HashSet};
+
+use super::{BlockId, Func, InstKind, Temp, Term};
+use crate::ir::tools::{fold_binary, fold_conv};
+use crate::parser::ast::Literal;
+
+pub struct Liveness {
+    pub live_in: Vec<BTreeSet<Temp>>,
+    pub live_out: Vec<BTreeSet<Temp>>,
+}
+
+// the temps live at the start and at the end of each block, a phi operand is live at the end
+// of the predecessor it comes from and not at the start of the block of the phi
+pub fn liveness(func: &Func) -> Liveness {
+    let len = func.blocks.len();
+    let mut uses = vec![BTreeSet::new(); len]; // used in the block before it defines them
+    let mut defs = vec![BTreeSet::new(); len];
+    let mut phi_uses = vec![BTreeSet::new(); len]; // passed to the phis of the successors
+    for (b, block) in func.blocks.iter().enumerate() {
+        for inst in &block.insts {
+            if let InstKind::Phi(incoming) = &inst.kind {
+                for (pred, temp) in incoming {
+                    phi_uses[pred.0 as usize].insert(*temp);
+                }
+            } else {
+                for temp in inst.kind.operands() {
+                    if !defs[b].contains(&temp) {
+                        uses[b].insert(temp);
+                    }
+                }
+            }
+            if let Some(dest) = inst.dest {
+                defs[b].insert(dest);
+            }
+        }
+        for temp in block.term.operands() {
+            if !defs[b].contains(&temp) {
+                uses[b].insert(temp);
+            }
+        }
+    }
+
+    let mut live = Liveness {
+        live_in: vec![BTreeSet::new(); len],
+        live_out: vec![BTreeSet::new(); len],
+    };
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for b in (0..len).rev() {
+            let mut out = phi_uses[b].clone();
+            for succ in func.blocks[b].term.succs() {
+                out.extend(&live.live_in[succ.0 as usize]);
+            }
+            let mut live_in = uses[b].clone();
+            live_in.extend(out.difference(&defs[b]));
+
+            if out != live.live_out[b] || live_in != live.live_in[b] {
+                live.live_out[b] = out;
+                live.live_in[b] = live_in;
+                changed = true;
+            }
+        }
+    }
+    live
+}
+
+// the temps whose value can be reached after the function returns or from other functions,
+// values that are returned, passed to calls or stored in lists, atoms and props escape and
+// so do the temps a phi or a conv that escapes was made from, lists and atoms made by a
+// function that don't escape can live on its stack
+pub fn escapes(func: &Func) -> BTreeSet<Temp> {
+    let mut escaping = Vec::new();
+    let mut flows = Vec::new(); // (from, into), into holds the value of from
+    for block in &func.blocks {
+        for inst in &block.insts {
+            match &inst.kind {
+                InstKind::Call(_, args) => escaping.extend(args),
+                InstKind::List(items) | InstKind::New(items) => escaping.extend(items),
+                InstKind::SetProp(_, _, val) | InstKind::SetIndex(_, _, val) => escaping.push(*val),
+                InstKind::Phi(_) | InstKind::Conv(..) => {
+                    let into = inst.dest.unwrap();
+                    flows.extend(inst.kind.operands().into_iter().map(|from| (from, into)));
+                }
+                _ => (),
+            }
+        }
+        if let Term::Ret(Some(val)) = block.term {
+            escaping.push(val);
+        }
+    }
+
+    let mut escaped = BTreeSet::new();
+    while let Some(temp) = escaping.pop() {
+        if escaped.insert(temp) {
+            for (from, into) in &flows {
+                if *into == temp {
+                    escaping.push(*from);
+                }
+            }
+        }
+    }
+    escaped
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Lattice {
+    Unknown, // not reached yet, or never
+    Const(Literal),
+    Varying,
+}
+
+pub struct Consts {
+    pub values: Vec<Lattice>, // of each temp
+    pub reachable: Vec<bool>, // of each block
+}
+
+fn join(left: &Lattice, right: &Lattice) -> Lattice {
+    match (left, right) {
+        (Lattice::Unknown, other) | (other, Lattice::Unknown) => other.clone(),
+        (Lattice::Const(l), Lattice::Const(r)) if l == r => left.clone(),
+        _ => Lattice::Varying,
+    }
+}
+
+// sparse conditional constant propagation, temps are consts if they are on every path that
+// runs, branches on consts only reach the side they take
+pub fn const_prop(func: &Func) -> Consts {
+    let mut consts = Consts {
+        values: vec![Lattice::Unknown; func.temps.len()],
+        reachable: vec![false; func.blocks.len()],
+    };
+    consts.reachable[0] = true;
+    let mut edges: HashSet<(BlockId, BlockId)> = HashSet::new();
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (b, block) in func.blocks.iter().enumerate() {
+            if !consts.reachable[b] {
+                continue;
+            }
+            let id = BlockId(b as u32);
+
+            for inst in &block.insts {
+                let Some(dest) = inst.dest else {
+                    continue;
+                };
+                let value = eval(func, &inst.kind, dest, &consts.values, &edges, id);
+                let old = &consts.values[dest.0 as usize];
+                let value = join(old, &value);
+                if value != *old {
+                    consts.values[dest.0 as usize] = value;
+                    changed = true;
+                }
+            }
+
+            let succs = match &block.term {
+                Term::Branch(cond, then, alt) => match &consts.values[cond.0 as usize] {
+                    Lattice::Const(Literal::Bool(true)) => vec![*then],
+                    Lattice::Const(Literal::Bool(false)) => vec![*alt],
+                    Lattice::Unknown => Vec::new(),
+                    _ => vec![*then, *alt],
+                },
+                term => term.succs(),
+            };
+            for succ in succs {
+                if edges.insert((id, succ)) {
+                    consts.reachable[succ.0 as usize] = true;
+                    changed = true;
+                }
+            }
+        }
+    }
+    consts
+}
+
+fn eval(
+    func: &Func,
+    kind: &InstKind,
+    dest: Temp,
+    values: &[Lattice],
+    edges: &HashSet<(BlockId, BlockId)>,
+    block: BlockId,
+) -> Lattice {
+    let value = |temp: &Temp| &values[temp.0 as usize];
+    let ty = &func.temps[dest.0 as usize];
+    match kind {
+        InstKind::Const(lit) => Lattice::Const(lit.clone()),
+        InstKind::Phi(incoming) => incoming
+            .iter()
+            .filter(|(pred, _)| edges.contains(&(*pred, block)))
+            .fold(Lattice::Unknown, |joined, (_, temp)| {
+                join(&joined, value(temp))
+            }),
+        InstKind::Binary(op, left, right) => match (value(left), value(right)) {
+            (Lattice::Const(l), Lattice::Const(r)) => {
+                fold_binary(&op.op(ty.clone()), l, r).map_or(Lattice::Varying, Lattice::Const)
+            }
+            (Lattice::Varying, _) | (_, Lattice::Varying) => Lattice::Varying,
+            _ => Lattice::Unknown,
+        },
+        InstKind::Conv(from, temp) => match value(temp) {
+            Lattice::Const(lit) if from == ty => Lattice::Const(lit.clone()),
+            Lattice::Const(lit) => fold_conv(lit, ty).map_or(Lattice::Varying, Lattice::Const),
+            other => other.clone(),
+        },
+        _ => Lattice::Varying,
+    }
+}
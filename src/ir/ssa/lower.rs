@@ -0,0 +1,491 @@
+use std::collections::BTreeMap;
+
+use super::{BinOp, Block, BlockId, Func, Inst, InstKind, Module, Temp, Term};
+use crate::ir::{get_op_type, IROp};
+use crate::types::{AtomKind, AtomType, BasicType};
+
+// lowers the IROp tree, defs become functions and the top level code the function main
+pub fn lower(ir: &[IROp]) -> Module {
+    let mut module = Module {
+        decls: Vec::new(),
+        funcs: Vec::new(),
+    };
+    let mut top = Vec::new();
+    for op in ir {
+        match op {
+            IROp::Import(..) | IROp::Extern(..) | IROp::Atom(..) => module.decls.push(op.clone()),
+            IROp::Def(ret, name, params, body, public) => {
+                let params = params.iter().map(|param| param.clone().tuple()).collect();
+                module
+                    .funcs
+                    .push(Lowerer::func(name, params, ret.clone(), body, *public));
+            }
+            op => top.push(op.clone()),
+        }
+    }
+
+    let int = AtomType {
+        kind: AtomKind::Basic(BasicType::Int),
+        details: None,
+    };
+    module
+        .funcs
+        .push(Lowerer::func("main", Vec::new(), int, &top, true));
+    module
+}
+
+// where a value on the simulated stack was loaded from, the target of a set
+enum Place {
+    Var(String),
+    Prop(Temp, String),
+    Index(Temp, Temp),
+}
+
+// a value on the simulated stack
+struct Entry {
+    temp: Temp,
+    place: Option<Place>,
+}
+
+struct Lowerer {
+    func: Func,
+    current: BlockId,
+    vars: BTreeMap<String, Temp>, // the temp each variable holds at this point
+    done: bool,                   // the current block rets, the ops after it never run
+}
+
+impl Lowerer {
+    fn func(
+        name: &str,
+        params: Vec<(AtomType, String)>,
+        ret: AtomType,
+        body: &[IROp],
+        public: bool,
+    ) -> Func {
+        let mut lowerer = Lowerer {
+            func: Func {
+                name: name.to_string(),
+                params: params.clone(),
+                ret,
+                public,
+                blocks: Vec::new(),
+                temps: Vec::new(),
+            },
+            current: BlockId(0),
+            vars: BTreeMap::new(),
+            done: false,
+        };
+        lowerer.current = lowerer.new_block();
+        for (i, (ty, name)) in params.into_iter().enumerate() {
+            let temp = lowerer.inst(ty, InstKind::Param(i));
+            lowerer.vars.insert(name, temp);
+        }
+
+        lowerer.body(body);
+        lowerer.trivial_phis();
+        renumber(&mut lowerer.func);
+        lowerer.func
+    }
+
+    fn new_block(&mut self) -> BlockId {
+        self.func.blocks.push(Block {
+            insts: Vec::new(),
+            term: Term::Ret(None),
+        });
+        BlockId(self.func.blocks.len() as u32 - 1)
+    }
+
+    fn block_mut(&mut self, id: BlockId) -> &mut Block {
+        &mut self.func.blocks[id.0 as usize]
+    }
+
+    fn end(&mut self, block: BlockId, term: Term) {
+        self.block_mut(block).term = term;
+    }
+
+    // the block a branch ends in and its variables, None if it rets
+    fn end_branch(&mut self) -> Option<(BlockId, BTreeMap<String, Temp>)> {
+        if self.done {
+            self.done = false;
+            return None;
+        }
+        Some((self.current, self.vars.clone()))
+    }
+
+    fn inst(&mut self, ty: AtomType, kind: InstKind) -> Temp {
+        let temp = Temp(self.func.temps.len() as u32);
+        self.func.temps.push(ty);
+        let current = self.current;
+        self.block_mut(current).insts.push(Inst {
+            dest: Some(temp),
+            kind,
+        });
+        temp
+    }
+
+    fn effect(&mut self, kind: InstKind) {
+        let current = self.current;
+        self.block_mut(current)
+            .insts
+            .push(Inst { dest: None, kind });
+    }
+
+    fn ty(&self, temp: Temp) -> AtomType {
+        self.func.temps[temp.0 as usize].clone()
+    }
+
+    // a block scope, variables declared in it are gone after it
+    fn body(&mut self, ops: &[IROp]) {
+        let outer: Vec<String> = self.vars.keys().cloned().collect();
+        let mut stack = Vec::new();
+        self.ops(ops, &mut stack);
+        self.vars.retain(|name, _| outer.contains(name));
+    }
+
+    fn ops(&mut self, ops: &[IROp], stack: &mut Vec<Entry>) {
+        for op in ops {
+            if self.done {
+                break;
+            }
+            self.op(op, stack);
+        }
+    }
+
+    fn pop(stack: &mut Vec<Entry>, count: usize) -> Vec<Entry> {
+        stack.split_off(stack.len() - count)
+    }
+
+    fn op(&mut self, op: &IROp, stack: &mut Vec<Entry>) {
+        let push = |stack: &mut Vec<Entry>, temp, place| stack.push(Entry { temp, place });
+
+        match op {
+            IROp::Import(..) | IROp::Extern(..) | IROp::Atom(..) | IROp::Def(..) => (),
+            IROp::Alloc(..) => (),
+
+            IROp::Const(lit) => {
+                let temp = self.inst(lit.get_ty(), InstKind::Const(lit.clone()));
+                push(stack, temp, None);
+            }
+            IROp::Load(ty, name) => match self.vars.get(name) {
+                Some(temp) => {
+                    let place = Some(Place::Var(name.clone()));
+                    push(stack, *temp, place);
+                }
+                None => {
+                    let temp = self.inst(ty.clone(), InstKind::Global(name.clone()));
+                    push(stack, temp, None);
+                }
+            },
+            IROp::LoadProp(ty, name) => {
+                let parent = Self::pop(stack, 1);
+                let kind = InstKind::Prop(parent[0].temp, name.clone());
+                let temp = self.inst(ty.clone(), kind);
+                let place = Some(Place::Prop(parent[0].temp, name.clone()));
+                push(stack, temp, place);
+            }
+            IROp::LoadIdx(ty) => {
+                let operands = Self::pop(stack, 2);
+                let (list, index) = (operands[0].temp, operands[1].temp);
+                let temp = self.inst(ty.clone(), InstKind::Index(list, index));
+                push(stack, temp, Some(Place::Index(list, index)));
+            }
+            IROp::Args => {
+                let temp = self.inst(get_op_type(op), InstKind::Args);
+                push(stack, temp, None);
+            }
+
+            IROp::Add(_)
+            | IROp::Sub(_)
+            | IROp::Mul(_)
+            | IROp::Div(_)
+            | IROp::Mod(_)
+            | IROp::Comp
+            | IROp::EComp
+            | IROp::Eq
+            | IROp::And
+            | IROp::Or => {
+                let binop = match op {
+                    IROp::Add(_) => BinOp::Add,
+                    IROp::Sub(_) => BinOp::Sub,
+                    IROp::Mul(_) => BinOp::Mul,
+                    IROp::Div(_) => BinOp::Div,
+                    IROp::Mod(_) => BinOp::Mod,
+                    IROp::Comp => BinOp::Gt,
+                    IROp::EComp => BinOp::Ge,
+                    IROp::Eq => BinOp::Eq,
+                    IROp::And => BinOp::And,
+                    _ => BinOp::Or,
+                };
+                // the left operand is on top of the stack
+                let operands = Self::pop(stack, 2);
+                let kind = InstKind::Binary(binop, operands[1].temp, operands[0].temp);
+                let temp = self.inst(get_op_type(op), kind);
+                push(stack, temp, None);
+            }
+
+            IROp::Call(ty, count) => {
+                let callee = Self::pop(stack, 1);
+                let args = Self::pop(stack, *count as usize);
+                let kind =
+                    InstKind::Call(callee[0].temp, args.iter().map(|arg| arg.temp).collect());
+                if ty.kind == AtomKind::Basic(BasicType::Void) {
+                    self.effect(kind);
+                } else {
+                    let temp = self.inst(ty.clone(), kind);
+                    push(stack, temp, None);
+                }
+            }
+            IROp::Ret(_) => {
+                let val = Self::pop(stack, 1)[0].temp;
+                self.end(self.current, Term::Ret(Some(val)));
+                self.done = true;
+            }
+
+            IROp::List(_, items) => {
+                let mut temps = Vec::new();
+                for item in items {
+                    let mut item_stack = Vec::new();
+                    self.ops(item, &mut item_stack);
+                    temps.extend(item_stack.iter().map(|entry| entry.temp));
+                }
+                let temp = self.inst(get_op_type(op), InstKind::List(temps));
+                push(stack, temp, None);
+            }
+            IROp::New(ty, count) => {
+                let fields = Self::pop(stack, *count as usize);
+                let kind = InstKind::New(fields.iter().map(|field| field.temp).collect());
+                let temp = self.inst(ty.clone(), kind);
+                push(stack, temp, None);
+            }
+            IROp::Conv(into, from) => {
+                let val = Self::pop(stack, 1);
+                let temp = self.inst(into.clone(), InstKind::Conv(from.clone(), val[0].temp));
+                push(stack, temp, None);
+            }
+            IROp::Is(ty) => {
+                let val = Self::pop(stack, 1);
+                let temp = self.inst(get_op_type(op), InstKind::Is(ty.clone(), val[0].temp));
+                push(stack, temp, None);
+            }
+            IROp::TypeOf => {
+                let val = Self::pop(stack, 1);
+                let temp = self.inst(get_op_type(op), InstKind::TypeOf(val[0].temp));
+                push(stack, temp, None);
+            }
+
+            IROp::Store(_, name) => {
+                let val = Self::pop(stack, 1)[0].temp;
+                self.vars.insert(name.clone(), val);
+            }
+            IROp::Dealloc(_, name) => {
+                if let Some(temp) = self.vars.get(name) {
+                    self.effect(InstKind::Drop(*temp));
+                }
+            }
+            IROp::Set(_) => {
+                let mut operands = Self::pop(stack, 2);
+                let val = operands.pop().unwrap().temp;
+                let target = operands.pop().unwrap();
+                match target.place {
+                    Some(Place::Var(name)) => {
+                        self.vars.insert(name, val);
+                    }
+                    // the target was loaded only to be set
+                    Some(Place::Prop(parent, name)) => {
+                        self.remove(target.temp);
+                        self.effect(InstKind::SetProp(parent, name, val));
+                    }
+                    Some(Place::Index(list, index)) => {
+                        self.remove(target.temp);
+                        self.effect(InstKind::SetIndex(list, index, val));
+                    }
+                    None => (),
+                }
+            }
+            IROp::Pop => {
+                stack.pop();
+            }
+
+            IROp::If(_, body, alt) => {
+                let cond = Self::pop(stack, 1)[0].temp;
+                self.lower_if(cond, body, alt);
+            }
+            IROp::While(cond, body) => self.lower_while(cond, body),
+        }
+    }
+
+    fn lower_if(&mut self, cond: Temp, body: &[IROp], alt: &[IROp]) {
+        let from = self.current;
+        let before = self.vars.clone();
+
+        let then = self.new_block();
+        self.current = then;
+        self.body(body);
+        let then_end = self.end_branch();
+
+        self.vars = before.clone();
+        let (alt_start, alt_end) = if alt.is_empty() {
+            (None, Some((from, before.clone())))
+        } else {
+            let alt_start = self.new_block();
+            self.current = alt_start;
+            self.body(alt);
+            (Some(alt_start), self.end_branch())
+        };
+
+        let ends: Vec<_> = [then_end, alt_end].into_iter().flatten().collect();
+        if ends.is_empty() {
+            self.end(from, Term::Branch(cond, then, alt_start.unwrap()));
+            self.done = true;
+            return;
+        }
+
+        let join = self.new_block();
+        self.end(from, Term::Branch(cond, then, alt_start.unwrap_or(join)));
+        for (block, _) in &ends {
+            if *block != from {
+                self.end(*block, Term::Jump(join));
+            }
+        }
+
+        self.current = join;
+        self.vars = before.clone();
+        for name in before.keys() {
+            let incoming: Vec<(BlockId, Temp)> = ends
+                .iter()
+                .map(|(block, vars)| (*block, vars[name]))
+                .collect();
+            if incoming.iter().all(|(_, temp)| *temp == incoming[0].1) {
+                self.vars.insert(name.clone(), incoming[0].1);
+                continue;
+            }
+            let phi = self.inst(self.ty(incoming[0].1), InstKind::Phi(incoming));
+            self.vars.insert(name.clone(), phi);
+        }
+    }
+
+    // cond are the ops of the condition, C evaluates them before each iteration so they are
+    // lowered again in the header and the insts they made before the while are dropped
+    fn lower_while(&mut self, cond: &[IROp], body: &[IROp]) {
+        let pre = self.current;
+        let header = self.new_block();
+        self.end(pre, Term::Jump(header));
+        self.current = header;
+
+        // a phi for every variable, the ones the body doesn't set are removed after lowering
+        let mut phis = Vec::new();
+        for (name, temp) in self.vars.clone() {
+            let phi = self.inst(self.ty(temp), InstKind::Phi(vec![(pre, temp)]));
+            self.vars.insert(name.clone(), phi);
+            phis.push((name, phi));
+        }
+        let mut cond_stack = Vec::new();
+        self.ops(cond, &mut cond_stack);
+        let cond = cond_stack.pop().unwrap().temp;
+        let header_vars = self.vars.clone();
+
+        let body_start = self.new_block();
+        let exit = self.new_block();
+        self.end(self.current, Term::Branch(cond, body_start, exit));
+
+        self.current = body_start;
+        self.body(body);
+        if let Some((latch, vars)) = self.end_branch() {
+            self.end(latch, Term::Jump(header));
+            for (name, phi) in phis {
+                self.incoming(header, phi, latch, vars[&name]);
+            }
+        }
+
+        self.current = exit;
+        self.vars = header_vars;
+    }
+
+    fn incoming(&mut self, block: BlockId, phi: Temp, from: BlockId, temp: Temp) {
+        for inst in &mut self.block_mut(block).insts {
+            if let (Some(dest), InstKind::Phi(incoming)) = (inst.dest, &mut inst.kind) {
+                if dest == phi {
+                    incoming.push((from, temp));
+                }
+            }
+        }
+    }
+
+    fn remove(&mut self, temp: Temp) {
+        let current = self.current;
+        self.block_mut(current)
+            .insts
+            .retain(|inst| inst.dest != Some(temp));
+    }
+
+    // removes the phis that only join one temp (and themselves), uses get that temp
+    fn trivial_phis(&mut self) {
+        loop {
+            let mut trivial = None;
+            for (b, block) in self.func.blocks.iter().enumerate() {
+                for (i, inst) in block.insts.iter().enumerate() {
+                    let (Some(dest), InstKind::Phi(incoming)) = (inst.dest, &inst.kind) else {
+                        continue;
+                    };
+                    let mut temps: Vec<Temp> = incoming
+                        .iter()
+                        .map(|(_, temp)| *temp)
+                        .filter(|temp| *temp != dest)
+                        .collect();
+                    temps.dedup();
+                    if temps.len() == 1 {
+                        trivial = Some((b, i, dest, temps[0]));
+                    }
+                }
+            }
+
+            let Some((b, i, phi, temp)) = trivial else {
+                return;
+            };
+            self.func.blocks[b].insts.remove(i);
+            replace(&mut self.func, phi, temp);
+        }
+    }
+}
+
+fn replace(func: &mut Func, from: Temp, to: Temp) {
+    let swap = |temp: &mut Temp| {
+        if *temp == from {
+            *temp = to;
+        }
+    };
+    for block in &mut func.blocks {
+        for inst in &mut block.insts {
+            inst.kind.operands_mut().into_iter().for_each(swap);
+        }
+        match &mut block.term {
+            Term::Branch(cond, _, _) | Term::Ret(Some(cond)) => swap(cond),
+            Term::Jump(_) | Term::Ret(None) => (),
+        }
+    }
+}
+
+// numbers the temps in the order they are defined, lowering leaves gaps where it dropped insts
+fn renumber(func: &mut Func) {
+    let mut map = vec![None; func.temps.len()];
+    let mut temps = Vec::new();
+    for inst in func.blocks.iter_mut().flat_map(|block| &mut block.insts) {
+        if let Some(dest) = &mut inst.dest {
+            map[dest.0 as usize] = Some(Temp(temps.len() as u32));
+            temps.push(func.temps[dest.0 as usize].clone());
+            *dest = Temp(temps.len() as u32 - 1);
+        }
+    }
+
+    let get = |temp: &mut Temp| *temp = map[temp.0 as usize].unwrap();
+    for block in &mut func.blocks {
+        for inst in &mut block.insts {
+            inst.kind.operands_mut().into_iter().for_each(get);
+        }
+        match &mut block.term {
+            Term::Branch(cond, _, _) | Term::Ret(Some(cond)) => get(cond),
+            Term::Jump(_) | Term::Ret(None) => (),
+        }
+    }
+    func.temps = temps;
+}
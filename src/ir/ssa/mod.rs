@@ -0,0 +1,390 @@
+// the second IR level, functions of basic blocks over SSA temporaries lowered from IROp
+//
+// every temp is defined by one instruction and has one type, variables are renamed into temps
+// and joined with phis where control flow meets, so data flow doesn't depend on a stack
+// printed with --emit=ssa:
+//
+// fn at_4facti(int n) -> int {
+// b0:
+//     %0: int = param 0
+//     %1: int = const 1
+//     %2: bool = gt %0 %1
+//     br %2 b1 b2
+// ...
+
+use std::fmt;
+
+use crate::parser::ast::Literal;
+use crate::types::AtomType;
+
+use super::{text, IROp};
+
+pub mod analysis;
+mod lower;
+
+pub use lower::lower;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Temp(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BlockId(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Gt,
+    Ge,
+    Eq,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InstKind {
+    Param(usize),
+    Const(Literal),
+    Global(String), // a def, import or extern
+    Args,
+    Binary(BinOp, Temp, Temp), // left, right
+    Call(Temp, Vec<Temp>),     // callee, args
+    List(Vec<Temp>),
+    New(Vec<Temp>), // atom fields in order
+    Conv(AtomType, Temp),
+    Is(AtomType, Temp),
+    TypeOf(Temp),
+    Prop(Temp, String),
+    Index(Temp, Temp), // list, index
+    SetProp(Temp, String, Temp),
+    SetIndex(Temp, Temp, Temp), // list, index, value
+    Drop(Temp),                 // frees the value of a variable at the end of its block
+    Phi(Vec<(BlockId, Temp)>),  // the temp that comes from each predecessor
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inst {
+    pub dest: Option<Temp>, // None for ops that only have effects and void calls
+    pub kind: InstKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term {
+    Jump(BlockId),
+    Branch(Temp, BlockId, BlockId), // cond, then, else
+    Ret(Option<Temp>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub insts: Vec<Inst>,
+    pub term: Term,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Func {
+    pub name: String,
+    pub params: Vec<(AtomType, String)>,
+    pub ret: AtomType,
+    pub public: bool,
+    pub blocks: Vec<Block>,   // the first block is the entry
+    pub temps: Vec<AtomType>, // the type of each temp
+}
+
+// a lowered program, top level code is the function main
+#[derive(Debug, Clone, PartialEq)]
+pub struct Module {
+    pub decls: Vec<IROp>, // imports, externs and atoms as they are in IROp
+    pub funcs: Vec<Func>,
+}
+
+impl BinOp {
+    // the IROp of the op, that the folding of ir::tools takes
+    pub fn op(self, ty: AtomType) -> IROp {
+        match self {
+            BinOp::Add => IROp::Add(ty),
+            BinOp::Sub => IROp::Sub(ty),
+            BinOp::Mul => IROp::Mul(ty),
+            BinOp::Div => IROp::Div(ty),
+            BinOp::Mod => IROp::Mod(ty),
+            BinOp::Gt => IROp::Comp,
+            BinOp::Ge => IROp::EComp,
+            BinOp::Eq => IROp::Eq,
+            BinOp::And => IROp::And,
+            BinOp::Or => IROp::Or,
+        }
+    }
+}
+
+impl InstKind {
+    pub fn operands(&self) -> Vec<Temp> {
+        let mut operands = Vec::new();
+        match self {
+            InstKind::Param(_) | InstKind::Const(_) | InstKind::Global(_) | InstKind::Args => (),
+            InstKind::Conv(_, temp)
+            | InstKind::Is(_, temp)
+            | InstKind::TypeOf(temp)
+            | InstKind::Prop(temp, _)
+            | InstKind::Drop(temp) => operands.push(*temp),
+            InstKind::Binary(_, left, right)
+            | InstKind::Index(left, right)
+            | InstKind::SetProp(left, _, right) => operands.extend([*left, *right]),
+            InstKind::SetIndex(list, index, val) => operands.extend([*list, *index, *val]),
+            InstKind::Call(callee, args) => {
+                operands.push(*callee);
+                operands.extend(args);
+            }
+            InstKind::List(items) | InstKind::New(items) => operands.extend(items),
+            InstKind::Phi(incoming) => operands.extend(incoming.iter().map(|(_, temp)| *temp)),
+        }
+        operands
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Temp> {
+        match self {
+            InstKind::Param(_) | InstKind::Const(_) | InstKind::Global(_) | InstKind::Args => {
+                Vec::new()
+            }
+            InstKind::Conv(_, temp)
+            | InstKind::Is(_, temp)
+            | InstKind::TypeOf(temp)
+            | InstKind::Prop(temp, _)
+            | InstKind::Drop(temp) => vec![temp],
+            InstKind::Binary(_, left, right)
+            | InstKind::Index(left, right)
+            | InstKind::SetProp(left, _, right) => vec![left, right],
+            InstKind::SetIndex(list, index, val) => vec![list, index, val],
+            InstKind::Call(callee, args) => std::iter::once(callee).chain(args).collect(),
+            InstKind::List(items) | InstKind::New(items) => items.iter_mut().collect(),
+            InstKind::Phi(incoming) => incoming.iter_mut().map(|(_, temp)| temp).collect(),
+        }
+    }
+}
+
+impl Term {
+    pub fn succs(&self) -> Vec<BlockId> {
+        match self {
+            Term::Jump(block) => vec![*block],
+            Term::Branch(_, then, alt) => vec![*then, *alt],
+            Term::Ret(_) => Vec::new(),
+        }
+    }
+
+    pub fn operands(&self) -> Vec<Temp> {
+        match self {
+            Term::Branch(cond, _, _) | Term::Ret(Some(cond)) => vec![*cond],
+            Term::Jump(_) | Term::Ret(None) => Vec::new(),
+        }
+    }
+}
+
+impl Func {
+    // the instruction that defines each temp
+    pub fn defs(&self) -> Vec<Option<&Inst>> {
+        let mut defs = vec![None; self.temps.len()];
+        for inst in self.blocks.iter().flat_map(|block| &block.insts) {
+            if let Some(dest) = inst.dest {
+                defs[dest.0 as usize] = Some(inst);
+            }
+        }
+        defs
+    }
+}
+
+impl fmt::Display for Temp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "%{}", self.0)
+    }
+}
+
+impl fmt::Display for BlockId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "b{}", self.0)
+    }
+}
+
+impl fmt::Display for BinOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            BinOp::Add => "add",
+            BinOp::Sub => "sub",
+            BinOp::Mul => "mul",
+            BinOp::Div => "div",
+            BinOp::Mod => "mod",
+            BinOp::Gt => "gt",
+            BinOp::Ge => "ge",
+            BinOp::Eq => "eq",
+            BinOp::And => "and",
+            BinOp::Or => "or",
+        };
+        write!(f, "{name}")
+    }
+}
+
+fn list(temps: &[Temp]) -> String {
+    temps
+        .iter()
+        .map(|temp| temp.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+impl fmt::Display for InstKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstKind::Param(i) => write!(f, "param {i}"),
+            InstKind::Const(Literal::Str(s)) => write!(f, "const \"{s}\""),
+            InstKind::Const(Literal::Int(i)) => write!(f, "const {i}"),
+            InstKind::Const(Literal::Float(x)) => write!(f, "const {x:?}"),
+            InstKind::Const(Literal::Bool(b)) => write!(f, "const {b}"),
+            InstKind::Global(name) => write!(f, "global {name}"),
+            InstKind::Args => write!(f, "args"),
+            InstKind::Binary(op, left, right) => write!(f, "{op} {left} {right}"),
+            InstKind::Call(callee, args) if args.is_empty() => write!(f, "call {callee}"),
+            InstKind::Call(callee, args) => write!(f, "call {callee} {}", list(args)),
+            InstKind::List(items) => write!(f, "list [{}]", list(items)),
+            InstKind::New(fields) => write!(f, "new {}", list(fields)),
+            InstKind::Conv(from, temp) => write!(f, "conv {from} {temp}"),
+            InstKind::Is(ty, temp) => write!(f, "is {ty} {temp}"),
+            InstKind::TypeOf(temp) => write!(f, "typeof {temp}"),
+            InstKind::Prop(temp, name) => write!(f, "prop {temp} {name}"),
+            InstKind::Index(list, index) => write!(f, "index {list} {index}"),
+            InstKind::SetProp(temp, name, val) => write!(f, "setprop {temp} {name} {val}"),
+            InstKind::SetIndex(list, index, val) => write!(f, "setindex {list} {index} {val}"),
+            InstKind::Drop(temp) => write!(f, "drop {temp}"),
+            InstKind::Phi(incoming) => {
+                let incoming: Vec<String> = incoming
+                    .iter()
+                    .map(|(block, temp)| format!("[{block} {temp}]"))
+                    .collect();
+                write!(f, "phi {}", incoming.join(" "))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Term::Jump(block) => write!(f, "jmp {block}"),
+            Term::Branch(cond, then, alt) => write!(f, "br {cond} {then} {alt}"),
+            Term::Ret(Some(val)) => write!(f, "ret {val}"),
+            Term::Ret(None) => write!(f, "ret"),
+        }
+    }
+}
+
+impl fmt::Display for Func {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let params: Vec<String> = self
+            .params
+            .iter()
+            .map(|(ty, name)| format!("{ty} {name}"))
+            .collect();
+        let public = if self.public { "pub " } else { "" };
+        writeln!(
+            f,
+            "{public}fn {}({}) -> {} {{",
+            self.name,
+            params.join(", "),
+            self.ret
+        )?;
+        for (i, block) in self.blocks.iter().enumerate() {
+            writeln!(f, "{}:", BlockId(i as u32))?;
+            for inst in &block.insts {
+                match inst.dest {
+                    Some(dest) => {
+                        let ty = &self.temps[dest.0 as usize];
+                        writeln!(f, "    {dest}: {ty} = {}", inst.kind)?
+                    }
+                    None => writeln!(f, "    {}", inst.kind)?,
+                }
+            }
+            writeln!(f, "    {}", block.term)?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", text::print(&self.decls))?;
+        for func in &self.funcs {
+            write!(f, "{func}")?;
+        }
+        Ok(())
+    }
+}
+
+// the module with the results of the analyses as comments after each function
+pub fn print(module: &Module) -> String {
+    let mut out = text::print(&module.decls);
+    for func in &module.funcs {
+        out.push_str(&func.to_string());
+
+        let live = analysis::liveness(func);
+        for (b, temps) in live.live_in.iter().enumerate() {
+            if !temps.is_empty() {
+                let temps: Vec<Temp> = temps.iter().copied().collect();
+                out.push_str(&format!(
+                    "# live in {}: {}\n",
+                    BlockId(b as u32),
+                    list(&temps)
+                ));
+            }
+        }
+
+        let consts = analysis::const_prop(func);
+        let defs = func.defs();
+        let folded: Vec<String> = consts
+            .values
+            .iter()
+            .enumerate()
+            .filter_map(|(i, value)| match (value, defs[i]) {
+                (
+                    _,
+                    Some(Inst {
+                        kind: InstKind::Const(_),
+                        ..
+                    }),
+                ) => None,
+                (analysis::Lattice::Const(lit), _) => Some(format!(
+                    "{} = {}",
+                    Temp(i as u32),
+                    InstKind::Const(lit.clone())
+                )),
+                _ => None,
+            })
+            .collect();
+        if !folded.is_empty() {
+            out.push_str(&format!("# consts: {}\n", folded.join(", ")));
+        }
+        let unreachable: Vec<String> = (0..func.blocks.len())
+            .filter(|b| !consts.reachable[*b])
+            .map(|b| BlockId(b as u32).to_string())
+            .collect();
+        if !unreachable.is_empty() {
+            out.push_str(&format!("# unreachable: {}\n", unreachable.join(" ")));
+        }
+
+        let escapes = analysis::escapes(func);
+        let local: Vec<Temp> = defs
+            .iter()
+            .enumerate()
+            .filter_map(|(i, def)| match def {
+                Some(Inst {
+                    kind: InstKind::List(_) | InstKind::New(_),
+                    ..
+                }) if !escapes.contains(&Temp(i as u32)) => Some(Temp(i as u32)),
+                _ => None,
+            })
+            .collect();
+        if !local.is_empty() {
+            out.push_str(&format!("# local allocations: {}\n", list(&local)));
+        }
+    }
+    out
+}
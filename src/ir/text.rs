@@ -0,0 +1,738 @@
+// the textual IR (.air files), one op per line and nested bodies in braces
+//
+// import void std writeln(Dynamic)
+// atom Point(int x, int y)
+// pub def int at_4facti(int n) {
+//     load int n
+//     const 1
+//     comp
+//     if void {
+//         ...
+//     } else {
+//         ...
+//     }
+// }
+//
+// types are written like in the language: int, str, List(int), Fn(int, str) -> bool, any, ?
+// for Unknown. user atoms are written by name and get their fields from their atom op,
+// which may come after the uses. # starts a comment
+use std::collections::HashMap;
+
+use indexmap::IndexMap;
+
+use super::IROp;
+use crate::err;
+use crate::err::{ATErr, ErrKind};
+use crate::parser::ast::{Ident, Literal};
+use crate::symbol::SymbolId;
+use crate::types::{self, Atom, AtomKind, AtomType, BasicType, FunctionType};
+
+const INDENT: &str = "    ";
+
+pub fn print(ir: &[IROp]) -> String {
+    let mut out = String::new();
+    for op in ir {
+        print_op(op, 0, &mut out);
+    }
+    out
+}
+
+pub fn print_ty(ty: &AtomType) -> String {
+    match &ty.kind {
+        AtomKind::Basic(basic) => basic.to_string(),
+        AtomKind::Dynamic => "Dynamic".to_string(),
+        AtomKind::Any => "any".to_string(),
+        AtomKind::Unknown => "?".to_string(),
+        AtomKind::Atom(atom) if atom == &*types::Str => "str".to_string(),
+        AtomKind::Atom(atom) if types::is_builtin(atom) => {
+            let generics: Vec<String> = atom
+                .generics
+                .values()
+                .map(print_ty)
+                .chain(atom.len.map(|len| len.to_string()))
+                .collect();
+            format!("{}({})", atom.name, generics.join(", "))
+        }
+        AtomKind::Atom(atom) => atom.name.clone(),
+        AtomKind::Function(func) => {
+            let params: Vec<String> = func.params.iter().map(print_ty).collect();
+            format!(
+                "Fn({}) -> {}",
+                params.join(", "),
+                print_ty(&func.return_type)
+            )
+        }
+        // only broken IR has these, they are printed for the verifier to report them
+        _ => ty.to_string(),
+    }
+}
+
+fn print_ident(ident: &Ident) -> String {
+    match ident {
+        Ident::Typed(ty, name) => format!("{} {name}", print_ty(ty)),
+        Ident::UnTagged(name) => name.to_string(),
+        Ident::Tagged(..) => unreachable!("tagged idents are analyzed before IR"),
+    }
+}
+
+fn print_idents(idents: &[Ident]) -> String {
+    let idents: Vec<String> = idents.iter().map(print_ident).collect();
+    idents.join(", ")
+}
+
+fn print_lit(lit: &Literal) -> String {
+    match lit {
+        Literal::Int(i) => i.to_string(),
+        Literal::Float(f) => format!("{f:?}"),
+        Literal::Bool(b) => b.to_string(),
+        Literal::Str(s) => {
+            let mut out = String::from("\"");
+            for c in s.chars() {
+                match c {
+                    '"' => out.push_str("\\\""),
+                    '\\' => out.push_str("\\\\"),
+                    '\n' => out.push_str("\\n"),
+                    '\t' => out.push_str("\\t"),
+                    c => out.push(c),
+                }
+            }
+            out.push('"');
+            out
+        }
+    }
+}
+
+fn print_body(body: &[IROp], depth: usize, out: &mut String) {
+    for op in body {
+        print_op(op, depth + 1, out);
+    }
+    out.push_str(&INDENT.repeat(depth));
+    out.push('}');
+}
+
+fn print_op(op: &IROp, depth: usize, out: &mut String) {
+    out.push_str(&INDENT.repeat(depth));
+    out.push_str(&print_head(op));
+    match op {
+        IROp::Def(_, _, _, body, _) => {
+            out.push_str(" {\n");
+            print_body(body, depth, out);
+        }
+        IROp::While(cond, body) => {
+            out.push_str(" {\n");
+            print_body(cond, depth, out);
+            out.push_str(" do {\n");
+            print_body(body, depth, out);
+        }
+        IROp::If(_, body, alt) => {
+            out.push_str(" {\n");
+            print_body(body, depth, out);
+            if !alt.is_empty() {
+                out.push_str(" else {\n");
+                print_body(alt, depth, out);
+            }
+        }
+        IROp::List(_, items) => {
+            out.push_str(" {\n");
+            for item in items {
+                out.push_str(&INDENT.repeat(depth + 1));
+                out.push_str("item {\n");
+                print_body(item, depth + 1, out);
+                out.push('\n');
+            }
+            out.push_str(&INDENT.repeat(depth));
+            out.push('}');
+        }
+        _ => (),
+    }
+    out.push('\n');
+}
+
+// the first line of an op, without the bodies of defs, ifs, whiles and lists
+pub fn print_head(op: &IROp) -> String {
+    let pub_ = |public: &bool| if *public { "pub " } else { "" };
+    match op {
+        IROp::Import(ty, module, name, params) => {
+            let params: Vec<String> = params.iter().map(print_ty).collect();
+            format!(
+                "import {} {module} {name}({})",
+                print_ty(ty),
+                params.join(", ")
+            )
+        }
+        IROp::Extern(ty, name, params) => {
+            format!("extern {} {name}({})", print_ty(ty), print_idents(params))
+        }
+        IROp::Atom(name, fields, public) => {
+            format!("{}atom {name}({})", pub_(public), print_idents(fields))
+        }
+        IROp::Def(ret, name, params, _, public) => format!(
+            "{}def {} {name}({})",
+            pub_(public),
+            print_ty(ret),
+            print_idents(params)
+        ),
+
+        IROp::Call(ty, count) => format!("call {} {count}", print_ty(ty)),
+        IROp::Ret(ty) => format!("ret {}", print_ty(ty)),
+
+        IROp::Add(ty) => format!("add {}", print_ty(ty)),
+        IROp::Sub(ty) => format!("sub {}", print_ty(ty)),
+        IROp::Mul(ty) => format!("mul {}", print_ty(ty)),
+        IROp::Div(ty) => format!("div {}", print_ty(ty)),
+        IROp::Mod(ty) => format!("mod {}", print_ty(ty)),
+
+        IROp::Comp => "comp".to_string(),
+        IROp::EComp => "ecomp".to_string(),
+        IROp::Eq => "eq".to_string(),
+        IROp::And => "and".to_string(),
+        IROp::Or => "or".to_string(),
+
+        IROp::Const(lit) => format!("const {}", print_lit(lit)),
+        IROp::List(ty, _) => format!("list {}", print_ty(ty)),
+        IROp::New(ty, count) => format!("new {} {count}", print_ty(ty)),
+        IROp::Conv(into, from) => format!("conv {} {}", print_ty(into), print_ty(from)),
+        IROp::Is(ty) => format!("is {}", print_ty(ty)),
+        IROp::TypeOf => "typeof".to_string(),
+        IROp::Args => "args".to_string(),
+        IROp::Alloc(ty, name) => format!("alloc {} {name}", print_ty(ty)),
+        IROp::Dealloc(ty, name) => format!("dealloc {} {name}", print_ty(ty)),
+        IROp::Store(ty, name) => format!("store {} {name}", print_ty(ty)),
+        IROp::Set(ty) => format!("set {}", print_ty(ty)),
+        IROp::Load(ty, name) => format!("load {} {name}", print_ty(ty)),
+        IROp::LoadProp(ty, name) => format!("loadprop {} {name}", print_ty(ty)),
+        IROp::LoadIdx(ty) => format!("loadidx {}", print_ty(ty)),
+
+        IROp::If(ty, _, _) => format!("if {}", print_ty(ty)),
+        IROp::While(..) => "while".to_string(),
+        IROp::Pop => "pop".to_string(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    Word(String),
+    Str(String),
+    Punct(char), // ( ) { } ,
+    Arrow,
+    Newline,
+}
+
+// a token as it is written, for errors
+fn found(tok: Option<Tok>) -> String {
+    match tok {
+        Some(Tok::Word(word)) => word,
+        Some(Tok::Str(s)) => format!("{s:?}"),
+        Some(Tok::Punct(c)) => c.to_string(),
+        Some(Tok::Arrow) => "->".to_string(),
+        Some(Tok::Newline) => "the end of the line".to_string(),
+        None => "the end of the file".to_string(),
+    }
+}
+
+struct IRParser {
+    toks: Vec<(Tok, u16, u16)>,
+    pos: usize,
+    atoms: HashMap<String, usize>, // user atom => position of its fields
+    resolved: HashMap<String, Atom>,
+    resolving: Vec<String>,
+    line: u16,
+    column: u16,
+}
+
+// parses a textual IR program, errors are reported at their line and column
+pub fn parse(src: &str) -> Result<Vec<IROp>, ErrKind> {
+    let mut parser = IRParser {
+        toks: Vec::new(),
+        pos: 0,
+        atoms: HashMap::new(),
+        resolved: HashMap::new(),
+        resolving: Vec::new(),
+        line: 1,
+        column: 0,
+    };
+    parser.lex(src)?;
+    parser.find_atoms();
+
+    let mut ir = Vec::new();
+    loop {
+        parser.skip_newlines();
+        if parser.pos >= parser.toks.len() {
+            return Ok(ir);
+        }
+        ir.push(parser.op()?);
+    }
+}
+
+impl IRParser {
+    fn lex(&mut self, src: &str) -> Result<(), ErrKind> {
+        let chars: Vec<char> = src.chars().collect();
+        let (mut line, mut column) = (1, 0);
+        let mut i = 0;
+
+        while i < chars.len() {
+            let c = chars[i];
+            let (start, start_column) = (i, column);
+            let tok = match c {
+                '\n' => {
+                    line += 1;
+                    column = 0;
+                    i += 1;
+                    self.toks.push((Tok::Newline, line - 1, start_column));
+                    continue;
+                }
+                '#' => {
+                    while i < chars.len() && chars[i] != '\n' {
+                        i += 1;
+                    }
+                    continue;
+                }
+                c if c.is_whitespace() => {
+                    i += 1;
+                    column += 1;
+                    continue;
+                }
+                '(' | ')' | '{' | '}' | ',' => {
+                    i += 1;
+                    Tok::Punct(c)
+                }
+                '-' if chars.get(i + 1) == Some(&'>') => {
+                    i += 2;
+                    Tok::Arrow
+                }
+                '"' => {
+                    let mut s = String::new();
+                    i += 1;
+                    loop {
+                        match chars.get(i) {
+                            Some('"') => break,
+                            Some('\\') => {
+                                s.push(match chars.get(i + 1) {
+                                    Some('n') => '\n',
+                                    Some('t') => '\t',
+                                    Some(c) => *c,
+                                    None => '\\',
+                                });
+                                i += 2;
+                            }
+                            Some('\n') | None => {
+                                (self.line, self.column) = (line, start_column);
+                                err!(self, ErrKind::IRSyntax, "unterminated string");
+                            }
+                            Some(c) => {
+                                s.push(*c);
+                                i += 1;
+                            }
+                        }
+                    }
+                    i += 1;
+                    Tok::Str(s)
+                }
+                c if is_word(c) => {
+                    // a -> right after a word isn't part of it
+                    while i < chars.len()
+                        && is_word(chars[i])
+                        && !(chars[i] == '-' && chars.get(i + 1) == Some(&'>'))
+                    {
+                        i += 1;
+                    }
+                    Tok::Word(chars[start..i].iter().collect())
+                }
+                c => {
+                    (self.line, self.column) = (line, start_column);
+                    err!(
+                        self,
+                        ErrKind::IRSyntax,
+                        format!("unexpected character {c:?}")
+                    );
+                }
+            };
+            column += (i - start) as u16;
+            self.toks.push((tok, line, start_column));
+        }
+        Ok(())
+    }
+
+    // the fields of every atom op, so uses can come before the declaration
+    fn find_atoms(&mut self) {
+        for i in 0..self.toks.len() {
+            let line_start = i == 0 || self.toks[i - 1].0 == Tok::Newline;
+            let after_pub = i > 0 && self.toks[i - 1].0 == Tok::Word("pub".to_string());
+            if self.toks[i].0 != Tok::Word("atom".to_string()) || !(line_start || after_pub) {
+                continue;
+            }
+            if let Some((Tok::Word(name), _, _)) = self.toks.get(i + 1) {
+                self.atoms.insert(name.clone(), i + 2);
+            }
+        }
+    }
+
+    fn peek(&self) -> Option<&Tok> {
+        self.toks.get(self.pos).map(|(tok, _, _)| tok)
+    }
+
+    fn next(&mut self) -> Option<Tok> {
+        let (tok, line, column) = self.toks.get(self.pos)?.clone();
+        (self.line, self.column) = (line, column);
+        self.pos += 1;
+        Some(tok)
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.peek() == Some(&Tok::Newline) {
+            self.pos += 1;
+        }
+    }
+
+    fn word(&mut self) -> Result<String, ErrKind> {
+        match self.next() {
+            Some(Tok::Word(word)) => Ok(word),
+            tok => {
+                err!(
+                    self,
+                    ErrKind::IRSyntax,
+                    format!("expected a name, got {}", found(tok))
+                );
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: Tok) -> Result<(), ErrKind> {
+        match self.next() {
+            Some(tok) if tok == expected => Ok(()),
+            tok => {
+                err!(
+                    self,
+                    ErrKind::IRSyntax,
+                    format!("expected {expected:?}, got {}", found(tok))
+                );
+            }
+        }
+    }
+
+    fn count(&mut self) -> Result<u16, ErrKind> {
+        let word = self.word()?;
+        match word.parse() {
+            Ok(count) => Ok(count),
+            Err(_) => {
+                err!(
+                    self,
+                    ErrKind::IRSyntax,
+                    format!("expected a count, got {word}")
+                );
+            }
+        }
+    }
+
+    // comma separated items in parentheses
+    fn list<T>(
+        &mut self,
+        mut item: impl FnMut(&mut Self) -> Result<T, ErrKind>,
+    ) -> Result<Vec<T>, ErrKind> {
+        self.expect(Tok::Punct('('))?;
+        let mut items = Vec::new();
+        if self.peek() == Some(&Tok::Punct(')')) {
+            self.pos += 1;
+            return Ok(items);
+        }
+        loop {
+            items.push(item(self)?);
+            match self.next() {
+                Some(Tok::Punct(',')) => (),
+                Some(Tok::Punct(')')) => return Ok(items),
+                tok => {
+                    err!(
+                        self,
+                        ErrKind::IRSyntax,
+                        format!("expected , or ), got {}", found(tok))
+                    );
+                }
+            }
+        }
+    }
+
+    fn ty(&mut self) -> Result<AtomType, ErrKind> {
+        let name = self.word()?;
+        let kind = match name.as_str() {
+            "int" => AtomKind::Basic(BasicType::Int),
+            "float" => AtomKind::Basic(BasicType::Float),
+            "bool" => AtomKind::Basic(BasicType::Bool),
+            "void" => AtomKind::Basic(BasicType::Void),
+            "Dynamic" => AtomKind::Dynamic,
+            "any" => AtomKind::Any,
+            "?" => AtomKind::Unknown,
+            "str" => AtomKind::Atom(types::Str.clone()),
+            "Fn" => {
+                let params = self.list(Self::ty)?;
+                self.expect(Tok::Arrow)?;
+                AtomKind::Function(FunctionType {
+                    params,
+                    return_type: Box::new(self.ty()?),
+                })
+            }
+            _ => {
+                let builtin = [&*types::List, &*types::Back, &*types::Const]
+                    .into_iter()
+                    .find(|builtin| builtin.name == name);
+                match builtin {
+                    Some(builtin) => {
+                        // the size of a List(T, N) is an int after its generics
+                        let mut len = None;
+                        let generics = self.list(|this| match this.peek() {
+                            Some(Tok::Word(word)) if word.parse::<u32>().is_ok() => {
+                                len = word.parse().ok();
+                                this.pos += 1;
+                                Ok(None)
+                            }
+                            _ => this.ty().map(Some),
+                        })?;
+                        let generics: Vec<AtomType> = generics.into_iter().flatten().collect();
+                        AtomKind::Atom(builtin.spec(&generics).sized(len))
+                    }
+                    None => AtomKind::Atom(self.atom(&name)?),
+                }
+            }
+        };
+        Ok(AtomType {
+            kind,
+            details: None,
+        })
+    }
+
+    // the type of a user atom from its atom op
+    fn atom(&mut self, name: &str) -> Result<Atom, ErrKind> {
+        if let Some(atom) = self.resolved.get(name) {
+            return Ok(atom.clone());
+        }
+        let Some(&fields_pos) = self.atoms.get(name) else {
+            err!(self, ErrKind::IRSyntax, format!("unknown type {name}"));
+        };
+        if self.resolving.iter().any(|atom| atom == name) {
+            err!(
+                self,
+                ErrKind::IRSyntax,
+                format!("atom {name} contains itself")
+            );
+        }
+
+        self.resolving.push(name.to_string());
+        let pos = std::mem::replace(&mut self.pos, fields_pos);
+        let fields = self.list(Self::ident);
+        self.pos = pos;
+        self.resolving.pop();
+
+        let fields = fields?
+            .into_iter()
+            .map(|field| {
+                let (ty, name) = field.tuple();
+                (name, ty)
+            })
+            .collect();
+        let atom = Atom::new(name.to_string(), fields, IndexMap::new());
+        self.resolved.insert(name.to_string(), atom.clone());
+        Ok(atom)
+    }
+
+    // `ty name` or an untyped `name`
+    fn ident(&mut self) -> Result<Ident, ErrKind> {
+        let untyped = matches!(
+            self.toks.get(self.pos + 1),
+            Some((Tok::Punct(',' | ')'), _, _))
+        );
+        if untyped {
+            return Ok(Ident::UnTagged(SymbolId::intern(&self.word()?)));
+        }
+        let ty = self.ty()?;
+        Ok(Ident::Typed(Box::new(ty), SymbolId::intern(&self.word()?)))
+    }
+
+    fn lit(&mut self) -> Result<Literal, ErrKind> {
+        let word = match self.next() {
+            Some(Tok::Str(s)) => return Ok(Literal::Str(s)),
+            Some(Tok::Word(word)) => word,
+            tok => {
+                err!(
+                    self,
+                    ErrKind::IRSyntax,
+                    format!("expected a literal, got {}", found(tok))
+                );
+            }
+        };
+        let lit = match word.as_str() {
+            "true" => Some(Literal::Bool(true)),
+            "false" => Some(Literal::Bool(false)),
+            // floats are always printed with a dot, an exponent, inf or NaN
+            _ if word
+                .trim_start_matches('-')
+                .bytes()
+                .all(|b| b.is_ascii_digit()) =>
+            {
+                match word.parse() {
+                    Ok(int) => Some(Literal::Int(int)),
+                    Err(_) => {
+                        err!(
+                            self,
+                            ErrKind::IRSyntax,
+                            format!("int literal {word} does not fit in an int")
+                        );
+                    }
+                }
+            }
+            _ => word.parse().ok().map(Literal::Float),
+        };
+        match lit {
+            Some(lit) => Ok(lit),
+            None => {
+                err!(self, ErrKind::IRSyntax, format!("invalid literal {word}"));
+            }
+        }
+    }
+
+    // { ops }, the closing brace is on its own line
+    fn body(&mut self) -> Result<Vec<IROp>, ErrKind> {
+        self.expect(Tok::Punct('{'))?;
+        let mut body = Vec::new();
+        loop {
+            self.skip_newlines();
+            match self.peek() {
+                Some(Tok::Punct('}')) => {
+                    self.pos += 1;
+                    return Ok(body);
+                }
+                Some(_) => body.push(self.op()?),
+                None => {
+                    err!(self, ErrKind::IRSyntax, "missing }".to_string());
+                }
+            }
+        }
+    }
+
+    fn end_line(&mut self) -> Result<(), ErrKind> {
+        match self.next() {
+            Some(Tok::Newline) | None => Ok(()),
+            Some(tok) => {
+                err!(
+                    self,
+                    ErrKind::IRSyntax,
+                    format!("expected the end of the line, got {}", found(Some(tok)))
+                );
+            }
+        }
+    }
+
+    fn op(&mut self) -> Result<IROp, ErrKind> {
+        let mut mnemonic = self.word()?;
+        let public = mnemonic == "pub";
+        if public {
+            mnemonic = self.word()?;
+            if mnemonic != "def" && mnemonic != "atom" {
+                err!(self, ErrKind::IRSyntax, format!("{mnemonic} can't be pub"));
+            }
+        }
+
+        let op = match mnemonic.as_str() {
+            "import" => {
+                let ty = self.ty()?;
+                let module = self.word()?;
+                let name = self.word()?;
+                IROp::Import(ty, module, name, self.list(Self::ty)?)
+            }
+            "extern" => {
+                let ty = self.ty()?;
+                let name = self.word()?;
+                IROp::Extern(ty, name, self.list(Self::ident)?)
+            }
+            "atom" => {
+                let name = self.word()?;
+                IROp::Atom(name, self.list(Self::ident)?, public)
+            }
+            "def" => {
+                let ret = self.ty()?;
+                let name = self.word()?;
+                let params = self.list(Self::ident)?;
+                IROp::Def(ret, name, params, self.body()?, public)
+            }
+
+            "call" => IROp::Call(self.ty()?, self.count()?),
+            "ret" => IROp::Ret(self.ty()?),
+
+            "add" => IROp::Add(self.ty()?),
+            "sub" => IROp::Sub(self.ty()?),
+            "mul" => IROp::Mul(self.ty()?),
+            "div" => IROp::Div(self.ty()?),
+            "mod" => IROp::Mod(self.ty()?),
+
+            "comp" => IROp::Comp,
+            "ecomp" => IROp::EComp,
+            "eq" => IROp::Eq,
+            "and" => IROp::And,
+            "or" => IROp::Or,
+
+            "const" => IROp::Const(self.lit()?),
+            "list" => {
+                let ty = self.ty()?;
+                self.expect(Tok::Punct('{'))?;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_newlines();
+                    if self.peek() == Some(&Tok::Punct('}')) {
+                        self.pos += 1;
+                        break;
+                    }
+                    let item = self.word()?;
+                    if item != "item" {
+                        err!(
+                            self,
+                            ErrKind::IRSyntax,
+                            format!("expected item, got {item}")
+                        );
+                    }
+                    items.push(self.body()?);
+                }
+                IROp::List(ty, items)
+            }
+            "new" => IROp::New(self.ty()?, self.count()?),
+            "conv" => IROp::Conv(self.ty()?, self.ty()?),
+            "is" => IROp::Is(self.ty()?),
+            "typeof" => IROp::TypeOf,
+            "args" => IROp::Args,
+            "alloc" => IROp::Alloc(self.ty()?, self.word()?),
+            "dealloc" => IROp::Dealloc(self.ty()?, self.word()?),
+            "store" => IROp::Store(self.ty()?, self.word()?),
+            "set" => IROp::Set(self.ty()?),
+            "load" => IROp::Load(self.ty()?, self.word()?),
+            "loadprop" => IROp::LoadProp(self.ty()?, self.word()?),
+            "loadidx" => IROp::LoadIdx(self.ty()?),
+
+            "if" => {
+                let ty = self.ty()?;
+                let body = self.body()?;
+                let alt = if self.peek() == Some(&Tok::Word("else".to_string())) {
+                    self.pos += 1;
+                    self.body()?
+                } else {
+                    Vec::new()
+                };
+                IROp::If(ty, body, alt)
+            }
+            "while" => {
+                let cond = self.body()?;
+                let word = self.word()?;
+                if word != "do" {
+                    err!(self, ErrKind::IRSyntax, format!("expected do, got {word}"));
+                }
+                IROp::While(cond, self.body()?)
+            }
+            "pop" => IROp::Pop,
+            _ => {
+                err!(self, ErrKind::IRSyntax, format!("unknown op {mnemonic}"));
+            }
+        };
+        self.end_line()?;
+        Ok(op)
+    }
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '.' | '-' | '+' | '?')
+}
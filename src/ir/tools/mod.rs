@@ -0,0 +1,130 @@
+// tools over the IR between IRGen and the backends, the verifier and the optimization passes
+//
+// passes are picked by the optimization level, -O0 runs none, -O1 the cheap local passes and
+// -O2 also inlines small instances and drops the functions nothing calls
+
+use std::fmt;
+
+use super::IROp;
+
+mod passes;
+mod verify;
+
+pub use passes::{fold_binary, fold_conv, ConstFold, DeadCode, DeadFunctions, Inline, NoopConvs};
+pub use verify::verify;
+
+pub trait Pass {
+    fn name(&self) -> &'static str;
+    // rewrites the IR, returns how many rewrites it made
+    fn run(&self, ir: &mut Vec<IROp>) -> usize;
+}
+
+// what a pass did to the program
+pub struct Stats {
+    pub pass: &'static str,
+    pub changes: usize,
+    pub ops_before: usize,
+    pub ops_after: usize,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} changes, {} -> {} ops",
+            self.pass, self.changes, self.ops_before, self.ops_after
+        )
+    }
+}
+
+pub struct PassManager {
+    passes: Vec<Box<dyn Pass>>,
+    pub stats: Vec<Stats>,
+}
+
+impl PassManager {
+    // the passes of an optimization level, levels above 2 are the same as 2
+    pub fn new(level: u8) -> Self {
+        let mut manager = Self {
+            passes: Vec::new(),
+            stats: Vec::new(),
+        };
+        if level >= 1 {
+            manager.add(Box::new(NoopConvs));
+            manager.add(Box::new(ConstFold));
+        }
+        // folding again after inlining folds the const args in the inlined bodies
+        if level >= 2 {
+            manager.add(Box::new(Inline));
+            manager.add(Box::new(ConstFold));
+        }
+        if level >= 1 {
+            manager.add(Box::new(DeadCode));
+        }
+        if level >= 2 {
+            manager.add(Box::new(DeadFunctions));
+        }
+        manager
+    }
+
+    pub fn add(&mut self, pass: Box<dyn Pass>) {
+        self.passes.push(pass);
+    }
+
+    // runs every pass in order and records its stats
+    pub fn run(&mut self, ir: &mut Vec<IROp>) {
+        for pass in &self.passes {
+            let ops_before = count(ir);
+            let changes = pass.run(ir);
+            self.stats.push(Stats {
+                pass: pass.name(),
+                changes,
+                ops_before,
+                ops_after: count(ir),
+            });
+        }
+    }
+}
+
+// the ops in ir and in the bodies nested in them
+pub fn count(ir: &[IROp]) -> usize {
+    ir.iter()
+        .map(|op| {
+            1 + bodies(op)
+                .into_iter()
+                .map(|body| count(body))
+                .sum::<usize>()
+        })
+        .sum()
+}
+
+// the bodies nested in an op
+pub fn bodies(op: &IROp) -> Vec<&Vec<IROp>> {
+    match op {
+        IROp::Def(_, _, _, body, _) => vec![body],
+        IROp::If(_, body, alt) => vec![body, alt],
+        IROp::While(cond, body) => vec![cond, body],
+        IROp::List(_, items) => items.iter().collect(),
+        _ => Vec::new(),
+    }
+}
+
+pub fn bodies_mut(op: &mut IROp) -> Vec<&mut Vec<IROp>> {
+    match op {
+        IROp::Def(_, _, _, body, _) => vec![body],
+        IROp::If(_, body, alt) => vec![body, alt],
+        IROp::While(cond, body) => vec![cond, body],
+        IROp::List(_, items) => items.iter_mut().collect(),
+        _ => Vec::new(),
+    }
+}
+
+// calls f on every body in ir, inner bodies before the ones they are in and ir last
+pub fn each_body(ir: &mut Vec<IROp>, f: &mut impl FnMut(&mut Vec<IROp>)) {
+    for op in ir.iter_mut() {
+        for body in bodies_mut(op) {
+            each_body(body, f);
+        }
+    }
+    f(ir);
+}
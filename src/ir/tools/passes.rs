@@ -0,0 +1,371 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{bodies, count, each_body, Pass};
+use crate::ir::{get_op_type, IROp};
+use crate::parser::ast::Literal;
+use crate::types::{AtomKind, AtomType, BasicType};
+
+// instances with a body of more ops than this are not inlined
+const INLINE_LIMIT: usize = 8;
+
+// removes conversions of a value into its own type
+pub struct NoopConvs;
+
+impl Pass for NoopConvs {
+    fn name(&self) -> &'static str {
+        "noop_convs"
+    }
+
+    fn run(&self, ir: &mut Vec<IROp>) -> usize {
+        let mut changes = 0;
+        each_body(ir, &mut |body| {
+            let len = body.len();
+            body.retain(|op| !matches!(op, IROp::Conv(into, from) if into == from));
+            changes += len - body.len();
+        });
+        changes
+    }
+}
+
+// replaces ops on consts by the const they make, operations C would do differently
+// (overflowing division, floats that aren't finite) are left to C
+pub struct ConstFold;
+
+impl Pass for ConstFold {
+    fn name(&self) -> &'static str {
+        "const_fold"
+    }
+
+    fn run(&self, ir: &mut Vec<IROp>) -> usize {
+        let mut changes = 0;
+        each_body(ir, &mut |body| {
+            let mut folded = Vec::with_capacity(body.len());
+            for op in body.drain(..) {
+                match fold(&folded, &op) {
+                    Some((operands, lit)) => {
+                        folded.truncate(folded.len() - operands);
+                        folded.push(IROp::Const(lit));
+                        changes += 1;
+                    }
+                    None => folded.push(op),
+                }
+            }
+            *body = folded;
+        });
+        changes
+    }
+}
+
+// the const an op makes of the consts right before it and how many of them it takes
+fn fold(before: &[IROp], op: &IROp) -> Option<(usize, Literal)> {
+    let consts = |n: usize| -> Option<Vec<&Literal>> {
+        let start = before.len().checked_sub(n)?;
+        before[start..]
+            .iter()
+            .map(|op| match op {
+                IROp::Const(lit) => Some(lit),
+                _ => None,
+            })
+            .collect()
+    };
+
+    if let IROp::Conv(into, from) = op {
+        let lit = consts(1)?[0];
+        if lit.get_ty() != *from {
+            return None;
+        }
+        return Some((1, fold_conv(lit, into)?));
+    }
+
+    let binary = matches!(
+        op,
+        IROp::Add(_)
+            | IROp::Sub(_)
+            | IROp::Mul(_)
+            | IROp::Div(_)
+            | IROp::Mod(_)
+            | IROp::Comp
+            | IROp::EComp
+            | IROp::Eq
+            | IROp::And
+            | IROp::Or
+    );
+    if !binary {
+        return None;
+    }
+    // the left operand is on top of the stack
+    let operands = consts(2)?;
+    Some((2, fold_binary(op, operands[1], operands[0])?))
+}
+
+// the const a binary op makes of its operands, None if it isn't folded
+pub fn fold_binary(op: &IROp, left: &Literal, right: &Literal) -> Option<Literal> {
+    use Literal::*;
+
+    let arithmetic = matches!(
+        op,
+        IROp::Add(_) | IROp::Sub(_) | IROp::Mul(_) | IROp::Div(_) | IROp::Mod(_)
+    );
+    if left.get_ty() != right.get_ty() || arithmetic && get_op_type(op) != left.get_ty() {
+        return None;
+    }
+
+    let lit = match (op, left, right) {
+        (IROp::Add(_), Int(l), Int(r)) => Int(l.wrapping_add(*r)),
+        (IROp::Sub(_), Int(l), Int(r)) => Int(l.wrapping_sub(*r)),
+        (IROp::Mul(_), Int(l), Int(r)) => Int(l.wrapping_mul(*r)),
+        (IROp::Div(_), Int(l), Int(r)) => Int(l.checked_div(*r)?),
+        (IROp::Mod(_), Int(l), Int(r)) => Int(l.checked_rem(*r)?),
+        (IROp::Add(_), Float(l), Float(r)) => Float(l + r),
+        (IROp::Sub(_), Float(l), Float(r)) => Float(l - r),
+        (IROp::Mul(_), Float(l), Float(r)) => Float(l * r),
+        (IROp::Div(_), Float(l), Float(r)) => Float(l / r),
+
+        (IROp::Comp, Int(l), Int(r)) => Bool(l > r),
+        (IROp::Comp, Float(l), Float(r)) => Bool(l > r),
+        (IROp::EComp, Int(l), Int(r)) => Bool(l >= r),
+        (IROp::EComp, Float(l), Float(r)) => Bool(l >= r),
+        (IROp::Eq, Int(l), Int(r)) => Bool(l == r),
+        (IROp::Eq, Float(l), Float(r)) => Bool(l == r),
+        (IROp::Eq, Bool(l), Bool(r)) => Bool(l == r),
+        (IROp::And, Bool(l), Bool(r)) => Bool(*l && *r),
+        (IROp::Or, Bool(l), Bool(r)) => Bool(*l || *r),
+        _ => return None,
+    };
+
+    // C has no literals for inf and nan
+    match lit {
+        Float(f) if !f.is_finite() => None,
+        lit => Some(lit),
+    }
+}
+
+// the C casts between basic types
+pub fn fold_conv(lit: &Literal, into: &AtomType) -> Option<Literal> {
+    use Literal::*;
+
+    let AtomKind::Basic(into) = into.kind else {
+        return None;
+    };
+    Some(match (lit, into) {
+        (Int(i), BasicType::Float) => Float(*i as f32),
+        (Int(i), BasicType::Bool) => Bool(*i != 0),
+        // out of range casts are undefined in C
+        (Float(f), BasicType::Int) if f.abs() < i32::MAX as f32 => Int(*f as i32),
+        (Float(f), BasicType::Bool) => Bool(*f != 0.0),
+        (Bool(b), BasicType::Int) => Int(*b as i32),
+        (Bool(b), BasicType::Float) => Float(*b as u8 as f32),
+        _ => return None,
+    })
+}
+
+// removes the code after a ret, or after an if that rets in both branches, declarations in
+// top level code are kept
+pub struct DeadCode;
+
+impl Pass for DeadCode {
+    fn name(&self) -> &'static str {
+        "dead_code"
+    }
+
+    fn run(&self, ir: &mut Vec<IROp>) -> usize {
+        let mut changes = 0;
+        each_body(ir, &mut |body| {
+            let Some(end) = body.iter().position(returns) else {
+                return;
+            };
+            let dead: Vec<IROp> = body.drain(end + 1..).collect();
+            for op in dead {
+                if matches!(
+                    op,
+                    IROp::Def(..) | IROp::Import(..) | IROp::Extern(..) | IROp::Atom(..)
+                ) {
+                    body.push(op);
+                } else {
+                    changes += count(std::slice::from_ref(&op));
+                }
+            }
+        });
+        changes
+    }
+}
+
+// inner bodies are cleaned before the ifs they are in, so a branch that rets ends with the ret
+fn returns(op: &IROp) -> bool {
+    match op {
+        IROp::Ret(_) => true,
+        IROp::If(_, body, alt) => {
+            body.last().is_some_and(returns) && alt.last().is_some_and(returns)
+        }
+        _ => false,
+    }
+}
+
+// replaces calls of small instances that only compute their ret value by their body, the
+// args have to be consts or loads so using them in place of each param is the same as the call
+pub struct Inline;
+
+type Candidate = (Vec<(AtomType, String)>, Vec<IROp>); // params, body without the ret
+
+impl Pass for Inline {
+    fn name(&self) -> &'static str {
+        "inline"
+    }
+
+    fn run(&self, ir: &mut Vec<IROp>) -> usize {
+        let mut candidates = HashMap::new();
+        for op in ir.iter() {
+            if let IROp::Def(_, name, params, body, _) = op {
+                if let Some(body) = candidate(name, body) {
+                    let params = params.iter().map(|param| param.clone().tuple()).collect();
+                    candidates.insert(name.clone(), (params, body));
+                }
+            }
+        }
+
+        let mut changes = 0;
+        each_body(ir, &mut |body| {
+            let mut inlined = Vec::with_capacity(body.len());
+            for op in body.drain(..) {
+                if let IROp::Call(_, count) = op {
+                    if let Some(ops) = inline(&inlined, count as usize, &candidates) {
+                        inlined.truncate(inlined.len() - count as usize - 1);
+                        inlined.extend(ops);
+                        changes += 1;
+                        continue;
+                    }
+                }
+                inlined.push(op);
+            }
+            *body = inlined;
+        });
+        changes
+    }
+}
+
+// the body of an instance that is a single ret expression
+fn candidate(name: &str, body: &[IROp]) -> Option<Vec<IROp>> {
+    let ret = body.iter().position(|op| matches!(op, IROp::Ret(_)))?;
+    // ops after the ret never run
+    let body = &body[..ret];
+    if count(body) > INLINE_LIMIT || !body.iter().all(|op| expression(op, name)) {
+        return None;
+    }
+    Some(body.to_vec())
+}
+
+// ops that push a value and do nothing else, recursive instances aren't inlined
+fn expression(op: &IROp, name: &str) -> bool {
+    match op {
+        IROp::Load(_, load) => load != name,
+        IROp::Call(ty, _) => ty.kind != AtomKind::Basic(BasicType::Void),
+        IROp::List(_, items) => items.iter().flatten().all(|op| expression(op, name)),
+        IROp::Add(_)
+        | IROp::Sub(_)
+        | IROp::Mul(_)
+        | IROp::Div(_)
+        | IROp::Mod(_)
+        | IROp::Comp
+        | IROp::EComp
+        | IROp::Eq
+        | IROp::And
+        | IROp::Or
+        | IROp::Const(_)
+        | IROp::New(..)
+        | IROp::Conv(..)
+        | IROp::Is(_)
+        | IROp::TypeOf
+        | IROp::LoadProp(..)
+        | IROp::LoadIdx(_) => true,
+        _ => false,
+    }
+}
+
+// the body of the called candidate with its params replaced by the args before the call
+fn inline(
+    before: &[IROp],
+    count: usize,
+    candidates: &HashMap<String, Candidate>,
+) -> Option<Vec<IROp>> {
+    let start = before.len().checked_sub(count + 1)?;
+    let IROp::Load(_, name) = before.last()? else {
+        return None;
+    };
+    let (params, body) = candidates.get(name)?;
+    let args = &before[start..before.len() - 1];
+    if params.len() != count {
+        return None;
+    }
+    for (arg, (ty, _)) in args.iter().zip(params) {
+        if !matches!(arg, IROp::Const(_) | IROp::Load(..)) || get_op_type(arg) != *ty {
+            return None;
+        }
+    }
+    Some(body.iter().map(|op| substitute(op, params, args)).collect())
+}
+
+fn substitute(op: &IROp, params: &[(AtomType, String)], args: &[IROp]) -> IROp {
+    match op {
+        IROp::Load(_, name) => match params.iter().position(|(_, param)| param == name) {
+            Some(i) => args[i].clone(),
+            None => op.clone(),
+        },
+        IROp::List(ty, items) => IROp::List(
+            ty.clone(),
+            items
+                .iter()
+                .map(|item| item.iter().map(|op| substitute(op, params, args)).collect())
+                .collect(),
+        ),
+        _ => op.clone(),
+    }
+}
+
+// removes the defs top level code never reaches through loads
+pub struct DeadFunctions;
+
+impl Pass for DeadFunctions {
+    fn name(&self) -> &'static str {
+        "dead_functions"
+    }
+
+    fn run(&self, ir: &mut Vec<IROp>) -> usize {
+        let mut defs = HashMap::new();
+        let mut reached = Vec::new();
+        for op in ir.iter() {
+            match op {
+                IROp::Def(_, name, _, body, _) => {
+                    defs.insert(name.as_str(), body);
+                }
+                op => loads(op, &mut reached),
+            }
+        }
+
+        let mut used = HashSet::new();
+        while let Some(name) = reached.pop() {
+            if !used.insert(name.clone()) {
+                continue;
+            }
+            if let Some(body) = defs.get(name.as_str()) {
+                for op in body.iter() {
+                    loads(op, &mut reached);
+                }
+            }
+        }
+
+        let len = ir.len();
+        ir.retain(|op| !matches!(op, IROp::Def(_, name, ..) if !used.contains(name)));
+        len - ir.len()
+    }
+}
+
+fn loads(op: &IROp, names: &mut Vec<String>) {
+    if let IROp::Load(_, name) = op {
+        names.push(name.clone());
+    }
+    for body in bodies(op) {
+        for op in body {
+            loads(op, names);
+        }
+    }
+}
@@ -0,0 +1,400 @@
+use std::collections::HashMap;
+
+use crate::err;
+use crate::err::{ATErr, ErrKind};
+use crate::ir::text::print_head;
+use crate::ir::{get_op_type, IROp};
+use crate::types::{self, demangle, AtomKind, AtomType, BasicType, FunctionType};
+
+// methods of built-in atoms that the runtime implements
+const RUNTIME: [&str; 2] = ["Listdotpush", "Listdotpop"];
+
+// checks the IR before it reaches a backend, the stack effect of every op, operand types,
+// call arity, that loaded names are defined and that rets return the def type.
+// errors are at the line of the op in the IR printed by --emit=ir, every def is checked
+// up to its first error
+pub fn verify(ir: &[IROp]) -> Result<(), ErrKind> {
+    let mut verifier = Verifier {
+        globals: HashMap::new(),
+        scopes: Vec::new(),
+        ret: basic(BasicType::Int),
+        func: String::new(),
+        line: 0,
+        column: 0,
+        failed: false,
+    };
+    for op in ir {
+        verifier.global(op);
+    }
+
+    // top level code is checked like the body of main, defs in it are checked on their own
+    let mut stack = Vec::new();
+    let mut top_failed = false;
+    verifier.scopes.push(Vec::new());
+    for op in ir {
+        // an error stops checking in the middle of an op, the next op starts after it
+        let start = verifier.line;
+        if let IROp::Def(ret, name, params, body, _) = op {
+            verifier.line += 1;
+            let scopes = std::mem::take(&mut verifier.scopes);
+            verifier.scopes = vec![params.iter().map(|param| param.val().to_string()).collect()];
+            verifier.ret = ret.clone();
+            verifier.func = demangle(name);
+            if verifier.block(body).is_err() {
+                verifier.failed = true;
+            }
+
+            verifier.scopes = scopes;
+            verifier.ret = basic(BasicType::Int);
+        } else if !top_failed {
+            verifier.func = "top level code".to_string();
+            if verifier.op(op, &mut stack).is_err() {
+                verifier.failed = true;
+                top_failed = true;
+            }
+        }
+        verifier.line = start + lines(op);
+    }
+    if !top_failed && !stack.is_empty() {
+        verifier.func = "top level code".to_string();
+        verifier.failed = true;
+        let _ = verifier.leftover(&stack);
+    }
+
+    if verifier.failed {
+        Err(ErrKind::IRVerify)
+    } else {
+        Ok(())
+    }
+}
+
+fn basic(basic: BasicType) -> AtomType {
+    AtomType {
+        kind: AtomKind::Basic(basic),
+        details: None,
+    }
+}
+
+fn function(params: Vec<AtomType>, ret: AtomType) -> AtomType {
+    AtomType {
+        kind: AtomKind::Function(FunctionType {
+            params,
+            return_type: Box::new(ret),
+        }),
+        details: None,
+    }
+}
+
+// the lines an op takes in the printed IR
+fn lines(op: &IROp) -> u16 {
+    let body = |body: &[IROp]| body.iter().map(lines).sum::<u16>();
+    match op {
+        IROp::Def(_, _, _, ops, _) => body(ops) + 2,
+        IROp::While(cond, ops) => body(cond) + body(ops) + 3,
+        IROp::If(_, ops, alt) if alt.is_empty() => body(ops) + 2,
+        IROp::If(_, ops, alt) => body(ops) + body(alt) + 3,
+        IROp::List(_, items) => items.iter().map(|item| body(item) + 2).sum::<u16>() + 2,
+        _ => 1,
+    }
+}
+
+struct Verifier {
+    globals: HashMap<String, AtomType>, // defs, imports and externs, by name
+    scopes: Vec<Vec<String>>,           // variables of the open blocks of the current def
+    ret: AtomType,                      // the def return type, top level code returns the exit code
+    func: String,
+    line: u16,
+    column: u16,
+    failed: bool,
+}
+
+impl Verifier {
+    fn global(&mut self, op: &IROp) {
+        let (name, ty) = match op {
+            IROp::Def(ret, name, params, _, _) | IROp::Extern(ret, name, params) => {
+                let params = params.iter().map(|param| param.clone().tuple().0).collect();
+                (name, function(params, ret.clone()))
+            }
+            IROp::Import(ty, _, name, _) => (name, ty.clone()),
+            _ => return,
+        };
+        self.globals.insert(name.clone(), ty);
+    }
+
+    fn fail<T>(&self, op: &IROp, msg: String) -> Result<T, ErrKind> {
+        err!(
+            self,
+            ErrKind::IRVerify,
+            format!("{} in {}: {msg}", print_head(op), self.func)
+        );
+    }
+
+    fn leftover(&self, stack: &[AtomType]) -> Result<(), ErrKind> {
+        let left: Vec<String> = stack.iter().map(|ty| ty.to_string()).collect();
+        err!(
+            self,
+            ErrKind::IRVerify,
+            format!(
+                "the end of a block in {} leaves {} on the stack",
+                self.func,
+                left.join(", ")
+            )
+        );
+    }
+
+    // a body of its own scope that starts and ends with an empty stack
+    fn block(&mut self, body: &[IROp]) -> Result<(), ErrKind> {
+        self.scopes.push(Vec::new());
+        let mut stack = Vec::new();
+        for op in body {
+            self.op(op, &mut stack)?;
+        }
+        self.scopes.pop();
+
+        self.line += 1;
+        if !stack.is_empty() {
+            return self.leftover(&stack);
+        }
+        Ok(())
+    }
+
+    fn pop(
+        &self,
+        op: &IROp,
+        stack: &mut Vec<AtomType>,
+        count: usize,
+    ) -> Result<Vec<AtomType>, ErrKind> {
+        if stack.len() < count {
+            return self.fail(
+                op,
+                format!("pops {count} values but the stack has {}", stack.len()),
+            );
+        }
+        Ok(stack.split_off(stack.len() - count))
+    }
+
+    fn defined(&self, name: &str) -> bool {
+        self.scopes
+            .iter()
+            .any(|scope| scope.iter().any(|var| var == name))
+            || self.globals.contains_key(name)
+            || RUNTIME.contains(&name)
+    }
+
+    fn op(&mut self, op: &IROp, stack: &mut Vec<AtomType>) -> Result<(), ErrKind> {
+        self.line += 1;
+        let bool = basic(BasicType::Bool);
+        let int = basic(BasicType::Int);
+        let dynamic = AtomType {
+            kind: AtomKind::Dynamic,
+            details: None,
+        };
+
+        match op {
+            IROp::Import(..) | IROp::Extern(..) | IROp::Atom(..) => (),
+            IROp::Def(..) => {
+                return self.fail(op, "defs are only allowed at the top level".to_string())
+            }
+
+            IROp::Call(ty, count) => {
+                let callee = self.pop(op, stack, 1)?.remove(0);
+                let AtomKind::Function(func) = &callee.kind else {
+                    return self.fail(op, format!("calls {callee}, not a function"));
+                };
+                if func.params.len() != *count as usize {
+                    return self.fail(
+                        op,
+                        format!(
+                            "passes {count} args to {callee} that takes {}",
+                            func.params.len()
+                        ),
+                    );
+                }
+                if *func.return_type != *ty {
+                    return self.fail(op, format!("{callee} returns {}", func.return_type));
+                }
+
+                let args = self.pop(op, stack, *count as usize)?;
+                for (arg, param) in args.iter().zip(&func.params) {
+                    if param.kind != AtomKind::Any && arg != param {
+                        return self
+                            .fail(op, format!("passes {arg} for a {param} param of {callee}"));
+                    }
+                }
+                if ty.kind != AtomKind::Basic(BasicType::Void) {
+                    stack.push(ty.clone());
+                }
+            }
+            IROp::Ret(ty) => {
+                let val = self.pop(op, stack, 1)?.remove(0);
+                if *ty != self.ret {
+                    return self.fail(
+                        op,
+                        format!("returns {ty} from a def that returns {}", self.ret),
+                    );
+                }
+                if val != *ty {
+                    return self.fail(op, format!("returns {val}"));
+                }
+            }
+
+            IROp::Add(ty) | IROp::Sub(ty) | IROp::Mul(ty) | IROp::Div(ty) | IROp::Mod(ty) => {
+                let operands = self.pop(op, stack, 2)?;
+                if operands.iter().any(|operand| operand != ty) {
+                    return self.fail(
+                        op,
+                        format!(
+                            "takes {ty} operands, got {} and {}",
+                            operands[0], operands[1]
+                        ),
+                    );
+                }
+                stack.push(ty.clone());
+            }
+            IROp::Comp | IROp::EComp | IROp::Eq | IROp::And | IROp::Or => {
+                let operands = self.pop(op, stack, 2)?;
+                if operands[0] != operands[1] {
+                    return self.fail(op, format!("compares {} with {}", operands[0], operands[1]));
+                }
+                let logic = matches!(op, IROp::And | IROp::Or);
+                if logic && operands[0] != bool && operands[0] != dynamic {
+                    return self.fail(op, format!("takes bool operands, got {}", operands[0]));
+                }
+                stack.push(bool);
+            }
+
+            IROp::Const(lit) => stack.push(lit.get_ty()),
+            IROp::List(ty, items) => {
+                for item in items {
+                    self.line += 1;
+                    let mut item_stack = Vec::new();
+                    for op in item {
+                        self.op(op, &mut item_stack)?;
+                    }
+                    self.line += 1;
+                    if item_stack.len() != 1 || item_stack[0] != *ty {
+                        let left: Vec<String> =
+                            item_stack.iter().map(|ty| ty.to_string()).collect();
+                        return self
+                            .fail(op, format!("has an item that leaves [{}]", left.join(", ")));
+                    }
+                }
+                self.line += 1;
+                stack.push(AtomType {
+                    kind: AtomKind::Atom(types::List.spec(std::slice::from_ref(ty))),
+                    details: None,
+                });
+            }
+            IROp::New(ty, count) => {
+                let AtomKind::Atom(atom) = &ty.kind else {
+                    return self.fail(op, format!("{ty} isn't an atom"));
+                };
+                if atom.fields.len() != *count as usize {
+                    return self.fail(op, format!("{ty} has {} fields", atom.fields.len()));
+                }
+                let args = self.pop(op, stack, *count as usize)?;
+                for (arg, (field, field_ty)) in args.iter().zip(&atom.fields) {
+                    if arg != field_ty {
+                        return self
+                            .fail(op, format!("passes {arg} for the {field_ty} field {field}"));
+                    }
+                }
+                stack.push(ty.clone());
+            }
+            IROp::Conv(into, from) => {
+                let val = self.pop(op, stack, 1)?.remove(0);
+                if val != *from {
+                    return self.fail(op, format!("converts from {from} but the value is {val}"));
+                }
+                stack.push(into.clone());
+            }
+            IROp::Is(_) | IROp::TypeOf => {
+                let val = self.pop(op, stack, 1)?.remove(0);
+                if val != dynamic {
+                    return self.fail(op, format!("inspects {val}, not Dynamic"));
+                }
+                stack.push(get_op_type(op));
+            }
+            IROp::Args => stack.push(get_op_type(op)),
+
+            IROp::Alloc(_, name) => {
+                let scope = self.scopes.last_mut().unwrap();
+                if scope.contains(name) {
+                    return self.fail(op, format!("{name} is already allocated in this block"));
+                }
+                scope.push(name.clone());
+            }
+            IROp::Dealloc(_, name) => {
+                if !self.defined(name) {
+                    return self.fail(op, format!("{name} isn't defined"));
+                }
+            }
+            IROp::Store(_, name) => {
+                self.pop(op, stack, 1)?;
+                if !self.scopes.iter().any(|scope| scope.contains(name)) {
+                    return self.fail(op, format!("{name} isn't allocated"));
+                }
+            }
+            IROp::Set(ty) => {
+                let operands = self.pop(op, stack, 2)?;
+                if operands[1] != *ty {
+                    return self.fail(op, format!("sets {ty} to {}", operands[1]));
+                }
+            }
+            IROp::Load(ty, name) => {
+                if !self.defined(name) {
+                    return self.fail(op, format!("{name} isn't defined"));
+                }
+                stack.push(ty.clone());
+            }
+            IROp::LoadProp(ty, name) => {
+                let parent = self.pop(op, stack, 1)?.remove(0);
+                match parent.get(name) {
+                    Some(field) if field.kind == AtomKind::Unknown || field == ty => (),
+                    Some(field) => return self.fail(op, format!("{parent}.{name} is a {field}")),
+                    None => return self.fail(op, format!("{parent} has no field {name}")),
+                }
+                stack.push(ty.clone());
+            }
+            IROp::LoadIdx(ty) => {
+                let operands = self.pop(op, stack, 2)?;
+                if operands[1] != int {
+                    return self.fail(op, format!("indexes with {}", operands[1]));
+                }
+                if !types::is_list(&operands[0])
+                    && operands[0].kind != AtomKind::Atom(types::Str.clone())
+                {
+                    return self.fail(op, format!("indexes into {}", operands[0]));
+                }
+                stack.push(ty.clone());
+            }
+
+            IROp::If(_, body, alt) => {
+                let cond = self.pop(op, stack, 1)?.remove(0);
+                if cond != bool {
+                    return self.fail(op, format!("branches on {cond}"));
+                }
+                self.block(body)?;
+                if !alt.is_empty() {
+                    self.block(alt)?;
+                }
+            }
+            IROp::While(cond, body) => {
+                let mut cond_stack = Vec::new();
+                for cond_op in cond {
+                    self.op(cond_op, &mut cond_stack)?;
+                }
+                self.line += 1;
+                if cond_stack != [bool.clone()] {
+                    let left: Vec<String> = cond_stack.iter().map(|ty| ty.to_string()).collect();
+                    return self.fail(op, format!("loops on [{}]", left.join(", ")));
+                }
+                self.block(body)?;
+            }
+            IROp::Pop => {
+                self.pop(op, stack, 1)?;
+            }
+        }
+        Ok(())
+    }
+}
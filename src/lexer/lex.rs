@@ -12,9 +12,18 @@ pub fn is_id(c: char) -> bool {
 impl Lexer {
     pub fn parse_num(&mut self, x: String) -> Token {
         if x.contains('.') {
-            return Token::Float(x.parse().unwrap());
+            return match x.parse() {
+                Ok(float) => Token::Float(float),
+                Err(_) => self.err(format!("invalid float {x}"), ErrKind::UnexceptedTokenE),
+            };
+        }
+        match x.parse() {
+            Ok(int) => Token::Int(int),
+            Err(_) => self.err(
+                format!("int {x} does not fit in an int"),
+                ErrKind::UnexceptedTokenE,
+            ),
         }
-        return Token::Int(x.parse().unwrap());
     }
 
     pub fn tokenize(&mut self) -> Token {
@@ -23,21 +32,41 @@ impl Lexer {
                 return Token::EOF;
             }
             match self.at() {
-                ' ' | '\t' => {
-                    self.eat();
-                }
-                '\n' => {
+                ' ' | '\t' | '\n' => {
                     self.eat();
-                    self.line += 1;
-                    self.column = 0;
                 }
                 _ => break,
             }
         }
+        self.start = (self.line, self.column);
 
         match self.at() {
             '#' => {
                 self.eat();
+                if self.code[self.pos..].starts_with("allow(") {
+                    for _ in 0.."allow(".len() {
+                        self.eat();
+                    }
+                    let mut lints = String::new();
+                    while self.not_eof() && self.at() != ')' && self.at() != '\n' {
+                        lints.push(self.eat());
+                    }
+                    if !self.not_eof() || self.at() != ')' {
+                        return self.err(
+                            "expected ) to close #allow(".to_string(),
+                            ErrKind::UnexceptedTokenE,
+                        );
+                    }
+                    self.eat();
+
+                    return Token::Allow(
+                        lints
+                            .split(',')
+                            .map(|lint| lint.trim().to_string())
+                            .collect(),
+                    );
+                }
+
                 if self.at() == '*' {
                     while self.not_eof() {
                         // eats then checks if its * and the next char is #
@@ -206,6 +235,18 @@ impl Lexer {
                         "break" => Token::BreakKw,
                         "continue" => Token::Continuekw,
                         "ret" => Token::RetKw,
+                        "as" => Token::AsKw,
+                        "atom" => Token::AtomKw,
+                        "iface" => Token::IfaceKw,
+                        "impl" => Token::ImplKw,
+                        "for" => Token::ForKw,
+                        "type" => Token::TypeKw,
+                        "is" => Token::IsKw,
+                        "part" => Token::PartKw,
+                        "state" => Token::StateKw,
+                        "const" => Token::ConstKw,
+                        "pub" => Token::PubKw,
+                        "typeof" => Token::TypeofKw,
                         // bools
                         "true" => Token::Bool(true),
                         "false" => Token::Bool(false),
@@ -1,4 +1,4 @@
-use crate::err::{ATErr, ErrKind};
+use crate::err::{ATErr, ErrKind, Severity};
 
 pub mod lex;
 pub mod token;
@@ -8,6 +8,7 @@ use self::token::Token;
 pub struct Lexer {
     line: u16,
     column: u16,
+    pub start: (u16, u16), // line and column the last token starts at
     code: String,
     pos: usize,
     pub errors: Vec<ATErr>,
@@ -16,28 +17,29 @@ pub struct Lexer {
 impl Lexer {
     pub fn new(code: String) -> Self {
         Self {
-            line: 0,
+            line: 1,
             column: 0,
+            start: (1, 0),
             pos: 0,
             code,
             errors: Vec::new(),
         }
     }
 
-    fn at(&mut self) -> char {
-        let c = (&self.code).as_bytes()[self.pos].clone() as char;
-        if &c == &'\n' {
-            self.line += 1;
-            self.column = 0;
-        }
-
-        c.clone()
+    fn at(&self) -> char {
+        self.code.as_bytes()[self.pos] as char
     }
 
     fn eat(&mut self) -> char {
         self.pos += 1;
-        self.column += 1;
-        (&self.code).as_bytes()[self.pos - 1].clone() as char
+        let c = self.code.as_bytes()[self.pos - 1] as char;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 0;
+        } else {
+            self.column += 1;
+        }
+        c
     }
 
     fn not_eof(&self) -> bool {
@@ -50,6 +52,7 @@ impl Lexer {
             msg,
             line: self.line,
             column: self.column,
+            severity: Severity::Error,
         };
         self.errors.push(err.clone());
         err.out_error();
@@ -29,5 +29,18 @@ pub enum Token {
     RetKw,
     UseKw,
     ExternKw,
+    AsKw,
+    AtomKw,
+    IfaceKw,
+    ImplKw,
+    ForKw,
+    TypeKw,
+    IsKw,
+    Allow(Vec<String>), // #allow(lint, ...) attribute
+    PartKw,             // immutable binding
+    StateKw,            // mutable binding, same as set
+    TypeofKw,
+    ConstKw, // compile time constant
+    PubKw,   // makes a module item visible to other modules
     EOF,
 }
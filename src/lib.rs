@@ -7,4 +7,5 @@ pub mod ir;
 pub mod lexer;
 pub mod parser;
 pub mod scope;
+pub mod symbol;
 pub mod types;
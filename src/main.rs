@@ -1,5 +1,5 @@
 use std::env::current_exe;
-use std::io::{self, Write};
+use std::io::{self, BufRead, Write};
 mod analysis;
 mod backend;
 mod compiler;
@@ -9,28 +9,19 @@ mod ir;
 mod lexer;
 mod parser;
 mod scope;
+mod symbol;
+#[cfg(test)]
+mod tests;
 mod types;
 
 use std::path::Path;
 // use std::process::Command;
-use crate::compiler::{Backend, CSettings, CompilerConfig};
-use std::{env, fs, process::Command};
-#[test]
-fn test() {
-    let path = "TestProj/main.atoms";
-    let prog = fs::read_to_string(path).unwrap();
-
-    CompilerConfig::new(
-        prog,
-        Backend::C(CSettings::new(None, Vec::new())),
-        true,
-        "__tmp_test".to_string(),
-        "TestProj".to_string(),
-    )
-    .compile();
-}
+use crate::analysis::lint::Level;
+use crate::compiler::{Backend, CSettings, CompilerConfig, Emit};
+use std::{env, fs};
 
-fn repl(is_debug: bool) {
+// runs the lines read so far after each line, with the interpreter or compiled with gcc
+fn repl(is_debug: bool, interp: bool) {
     let mut buffer = String::with_capacity(4096);
     let stdin = io::stdin();
 
@@ -41,39 +32,203 @@ fn repl(is_debug: bool) {
         print!(">> ");
         io::stdout().flush().unwrap();
 
-        let _ = stdin.read_line(&mut buffer);
-        CompilerConfig::new(
+        let before = buffer.len();
+        if let Ok(0) | Err(_) = stdin.read_line(&mut buffer) {
+            return;
+        }
+        let config = CompilerConfig::new(
             buffer.clone(),
             Backend::C(CSettings::new(None, Vec::new())),
             is_debug,
             "/tmp/covalent/repl".to_string(),
             current_exe().unwrap().to_str().unwrap().to_string(),
-        )
-        .compile();
-        let _ = Command::new("/tmp/covalent/repl")
-            .spawn()
-            .expect("failed to execute repl exe")
-            .wait();
+        );
+        // the error is already printed, the line that caused it is dropped
+        let Ok(ir) = config.try_gen_ir() else {
+            buffer.truncate(before);
+            continue;
+        };
+        config.run(ir, Vec::new(), interp);
+    }
+}
+
+// covalent demangle [names], demangles every mangled name in stdin if no names are given
+fn demangle(names: Vec<String>) {
+    if !names.is_empty() {
+        for name in names {
+            println!("{}", types::demangle(&name));
+        }
+        return;
+    }
+
+    for line in io::stdin().lock().lines() {
+        let line = line.unwrap();
+        let mut out = String::new();
+        let mut id = String::new();
+
+        // mangles are C identifiers, each identifier in the line is demangled
+        for c in line.chars().chain(std::iter::once('\n')) {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                id.push(c);
+                continue;
+            }
+
+            out.push_str(&types::demangle(&id));
+            id.clear();
+            out.push(c);
+        }
+        print!("{out}");
     }
 }
 
+// --emit=exe (the default), --emit=ir to write the IR as a .air file or --emit=ssa to write it
+// lowered to SSA as a .ssa file instead of compiling it
+fn emit_flag(arg: &str, emit: &mut Emit) -> bool {
+    let Some(kind) = arg.strip_prefix("--emit=") else {
+        return false;
+    };
+    *emit = match kind {
+        "exe" => Emit::Exe,
+        "ir" => Emit::Ir,
+        "ssa" => Emit::Ssa,
+        _ => {
+            println!("unknown --emit={kind}, expected exe, ir or ssa");
+            std::process::exit(1);
+        }
+    };
+    true
+}
+
+// --target=native (the default) builds an executable with the backend, --target=bytecode
+// writes the program as a .atomc file that covalent vm runs
+fn target_flag(arg: &str, emit: &mut Emit) -> bool {
+    let Some(target) = arg.strip_prefix("--target=") else {
+        return false;
+    };
+    *emit = match target {
+        "native" => Emit::Exe,
+        "bytecode" => Emit::Bytecode,
+        _ => {
+            println!("unknown --target={target}, expected native or bytecode");
+            std::process::exit(1);
+        }
+    };
+    true
+}
+
+// covalent vm file.atomc [args], runs bytecode with the interpreter, the args after the file
+// are the args of the program
+fn vm(args: Vec<String>) {
+    let Some(file) = args.first() else {
+        println!("covalent vm expects a .atomc file");
+        std::process::exit(1);
+    };
+    let bytes = fs::read(file).expect("invaild file name");
+    let Ok(ir) = ir::bytecode::read(&bytes) else {
+        std::process::exit(1);
+    };
+    // bytecode isn't always written by this covalent, it is checked in release builds too
+    if ir::tools::verify(&ir).is_err() {
+        std::process::exit(1);
+    }
+    std::process::exit(compiler::interpret(&ir, args));
+}
+
+// -O0 (the default), -O1 or -O2 picks the optimization passes, --stats prints what each did
+fn opt_flag(arg: &str, opt: &mut u8, stats: &mut bool) -> bool {
+    if arg == "--stats" {
+        *stats = true;
+        return true;
+    }
+    let Some(level) = arg.strip_prefix("-O") else {
+        return false;
+    };
+    *opt = match level {
+        "0" => 0,
+        "1" => 1,
+        "2" => 2,
+        _ => {
+            println!("unknown -O{level}, expected -O0, -O1 or -O2");
+            std::process::exit(1);
+        }
+    };
+    true
+}
+
+// --interp runs programs with the interpreter (the default of the repl), --native compiles
+// them with the backend and runs the executable (the default of run)
+fn engine_flag(arg: &str, interp: &mut Option<bool>) -> bool {
+    *interp = match arg {
+        "--interp" => Some(true),
+        "--native" => Some(false),
+        _ => return false,
+    };
+    true
+}
+
+// --warn=a,b, --deny=a,b and --allow=a,b set lint levels, later flags override earlier ones
+fn lint_flag(arg: &str, lints: &mut Vec<(String, Level)>) -> bool {
+    let Some((flag, names)) = arg.split_once('=') else {
+        return false;
+    };
+    let level = match flag {
+        "--warn" => Level::Warn,
+        "--deny" => Level::Deny,
+        "--allow" => Level::Allow,
+        _ => return false,
+    };
+
+    for name in names.split(',') {
+        lints.push((name.trim().to_string(), level));
+    }
+    true
+}
+
 fn main() {
     let mut is_debug = false;
-    let mut args = env::args();
+    let mut lints = Vec::new();
+    let mut emit = Emit::Exe;
+    let (mut opt, mut stats) = (0, false);
+    let mut interp = None;
+    let mut run = false;
+    let mut args = env::args()
+        .filter(|arg| {
+            !lint_flag(arg, &mut lints)
+                && !emit_flag(arg, &mut emit)
+                && !target_flag(arg, &mut emit)
+                && !opt_flag(arg, &mut opt, &mut stats)
+                && !engine_flag(arg, &mut interp)
+        })
+        .collect::<Vec<_>>()
+        .into_iter();
 
     if args.len() <= 1 {
-        return repl(is_debug);
+        return repl(is_debug, interp.unwrap_or(true));
     }
 
     let file = {
         let arg = args.nth(1).unwrap();
-        if arg == "test" {
+        if arg == "demangle" {
+            return demangle(args.collect());
+        }
+        if arg == "vm" {
+            return vm(args.collect());
+        }
+
+        // build is the same as passing the file alone
+        if arg == "build" && args.len() > 0 {
+            args.nth(0).unwrap()
+        } else if arg == "test" {
             is_debug = true;
 
             if args.len() == 0 {
-                return repl(is_debug);
+                return repl(is_debug, interp.unwrap_or(true));
             }
 
+            args.nth(0).unwrap()
+        } else if arg == "run" && args.len() > 0 {
+            // covalent run file [args], the args after the file are the args of the program
+            run = true;
             args.nth(0).unwrap()
         } else {
             arg
@@ -91,16 +246,39 @@ fn main() {
         .unwrap()
         .to_string();
 
-    CompilerConfig::new(
+    // .air files are textual IR that goes straight to the backend
+    let is_ir = filename.ends_with(".air");
+    if is_ir && emit == Emit::Ir {
+        println!("{file} is already IR");
+        std::process::exit(1);
+    }
+
+    let mut config = CompilerConfig::new(
         prog.expect("invaild file name"),
         Backend::C(CSettings::new(None, Vec::new())),
         is_debug,
-        filename.replace(".atoms", ""),
+        filename.replace(".atoms", "").replace(".air", ""),
         path.parent()
             .unwrap_or(&Path::new(""))
             .to_str()
             .unwrap()
             .to_string(),
-    )
-    .compile();
+    );
+    config.lints = lints;
+    config.emit = emit;
+    config.opt = opt;
+    config.stats = stats;
+    if run {
+        let ir = if is_ir {
+            config.parse_ir()
+        } else {
+            config.gen_ir()
+        };
+        std::process::exit(config.run(ir, args.collect(), interp.unwrap_or(false)));
+    }
+    if is_ir {
+        config.compile_ir();
+    } else {
+        config.compile();
+    }
 }
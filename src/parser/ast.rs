@@ -1,5 +1,7 @@
 use core::panic;
+use std::fmt;
 
+use crate::symbol::SymbolId;
 use crate::types::{self, AtomKind, AtomType, BasicType};
 #[derive(Debug, Clone, PartialEq)]
 pub enum Literal {
@@ -23,14 +25,239 @@ impl Literal {
     }
 }
 
-pub fn get_operator_level(op: &str) -> u8 {
-    match op {
-        "&&" | "||" => 1,
-        "==" => 2,
-        "<" | ">" | ">=" | "<=" => 3,
-        "+" | "-" => 4,
-        "*" | "/" | "%" => 5,
-        _ => todo!(),
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Eq,
+    Gt,
+    Lt,
+    Ge,
+    Le,
+    And,
+    Or,
+}
+
+impl BinOp {
+    // the operator of a Token::Operator, None for the ones that aren't binary operators
+    pub fn parse(op: &str) -> Option<Self> {
+        Some(match op {
+            "+" => BinOp::Add,
+            "-" => BinOp::Sub,
+            "*" => BinOp::Mul,
+            "/" => BinOp::Div,
+            "%" => BinOp::Mod,
+            "==" => BinOp::Eq,
+            ">" => BinOp::Gt,
+            "<" => BinOp::Lt,
+            ">=" => BinOp::Ge,
+            "<=" => BinOp::Le,
+            "&&" => BinOp::And,
+            "||" => BinOp::Or,
+            _ => return None,
+        })
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Mod => "%",
+            BinOp::Eq => "==",
+            BinOp::Gt => ">",
+            BinOp::Lt => "<",
+            BinOp::Ge => ">=",
+            BinOp::Le => "<=",
+            BinOp::And => "&&",
+            BinOp::Or => "||",
+        }
+    }
+
+    // operators with a higher level bind tighter
+    pub fn level(self) -> u8 {
+        match self {
+            BinOp::And | BinOp::Or => 1,
+            BinOp::Eq => 2,
+            BinOp::Gt | BinOp::Lt | BinOp::Ge | BinOp::Le => 3,
+            BinOp::Add | BinOp::Sub => 4,
+            BinOp::Mul | BinOp::Div | BinOp::Mod => 5,
+        }
+    }
+
+    // comparisons give a bool whatever they compare
+    pub fn is_comparison(self) -> bool {
+        matches!(
+            self,
+            BinOp::Eq | BinOp::Gt | BinOp::Lt | BinOp::Ge | BinOp::Le
+        )
+    }
+}
+
+impl fmt::Display for BinOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// an index into the Ast, nodes refer to their children by id
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId(u32);
+
+impl fmt::Debug for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+// every parsed and analyzed node of a program, types are a side table next to the exprs.
+// parsed nodes are Unknown, analysis pushes new typed nodes that point to already analyzed
+// children, so blueprint bodies are analyzed once per instance without copying them
+#[derive(Debug, Clone)]
+pub struct Ast {
+    exprs: Vec<Expr>,
+    types: Vec<u32>,      // index into tys for every node
+    tys: Vec<AtomType>,   // the first SHARED types are used by every node of that type
+    pos: Vec<(u16, u16)>, // line and column of every node, for diagnostics
+    pub at: (u16, u16), // position of the nodes pushed next, the statement being parsed or analyzed
+}
+
+// Unknown, the basic types and Dynamic
+const SHARED: usize = 6;
+
+impl Default for Ast {
+    fn default() -> Self {
+        let ty = |kind| AtomType {
+            kind,
+            details: None,
+        };
+        let tys = vec![
+            ty(AtomKind::Unknown),
+            ty(AtomKind::Basic(BasicType::Int)),
+            ty(AtomKind::Basic(BasicType::Float)),
+            ty(AtomKind::Basic(BasicType::Bool)),
+            ty(AtomKind::Basic(BasicType::Void)),
+            ty(AtomKind::Dynamic),
+        ];
+        Self {
+            exprs: Vec::new(),
+            types: Vec::new(),
+            tys,
+            pos: Vec::new(),
+            at: (1, 0),
+        }
+    }
+}
+
+impl Ast {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, expr: Expr, ty: AtomType) -> NodeId {
+        let ty = match self.shared(&ty) {
+            Some(shared) => shared,
+            None => {
+                self.tys.push(ty);
+                self.tys.len() as u32 - 1
+            }
+        };
+        self.exprs.push(expr);
+        self.types.push(ty);
+        self.pos.push(self.at);
+        NodeId(self.exprs.len() as u32 - 1)
+    }
+
+    fn shared(&self, ty: &AtomType) -> Option<u32> {
+        if ty.details.is_some() {
+            return None;
+        }
+        self.tys[..SHARED]
+            .iter()
+            .position(|shared| shared.kind == ty.kind)
+            .map(|idx| idx as u32)
+    }
+
+    pub fn untyped(&mut self, expr: Expr) -> NodeId {
+        self.push(
+            expr,
+            AtomType {
+                kind: AtomKind::Unknown,
+                details: None,
+            },
+        )
+    }
+
+    pub fn expr(&self, id: NodeId) -> &Expr {
+        &self.exprs[id.0 as usize]
+    }
+
+    pub fn expr_mut(&mut self, id: NodeId) -> &mut Expr {
+        &mut self.exprs[id.0 as usize]
+    }
+
+    pub fn ty(&self, id: NodeId) -> &AtomType {
+        &self.tys[self.types[id.0 as usize] as usize]
+    }
+
+    // a node that isn't shared owns its type, so it is replaced in place
+    pub fn set_ty(&mut self, id: NodeId, ty: AtomType) {
+        let idx = self.types[id.0 as usize];
+        match self.shared(&ty) {
+            Some(shared) => self.types[id.0 as usize] = shared,
+            None if idx as usize >= SHARED => self.tys[idx as usize] = ty,
+            None => {
+                self.tys.push(ty);
+                self.types[id.0 as usize] = self.tys.len() as u32 - 1;
+            }
+        }
+    }
+
+    pub fn pos(&self, id: NodeId) -> (u16, u16) {
+        self.pos[id.0 as usize]
+    }
+
+    pub fn set_pos(&mut self, id: NodeId, pos: (u16, u16)) {
+        self.pos[id.0 as usize] = pos;
+    }
+
+    // nodes directly under id, see Expr::children
+    pub fn children(&self, id: NodeId) -> impl DoubleEndedIterator<Item = NodeId> + '_ {
+        self.expr(id).children()
+    }
+}
+
+// debug view of nodes and everything under them, one node per line
+pub struct Tree<'a>(pub &'a Ast, pub &'a [NodeId]);
+
+impl Tree<'_> {
+    fn write(&self, f: &mut fmt::Formatter<'_>, id: NodeId, depth: usize) -> fmt::Result {
+        let Tree(ast, _) = self;
+        writeln!(
+            f,
+            "{:indent$}{id:?} {:?}: {}",
+            "",
+            ast.expr(id),
+            ast.ty(id),
+            indent = depth * 2
+        )?;
+        for child in ast.children(id) {
+            self.write(f, child, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Tree<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for id in self.1 {
+            self.write(f, *id, 0)?;
+        }
+        Ok(())
     }
 }
 
@@ -38,127 +265,205 @@ pub fn get_operator_level(op: &str) -> u8 {
 pub enum Expr {
     Use(String),
     Literal(Literal),
-    ListExpr(Vec<Node>),
+    ListExpr(Vec<NodeId>),
 
     BinaryExpr {
-        op: String,
-        left: Box<Node>,
-        right: Box<Node>,
+        op: BinOp,
+        left: NodeId,
+        right: NodeId,
     },
 
     Ident(Ident),
     VarDeclare {
         name: Ident,
-        val: Box<Node>,
+        val: NodeId,
+        mutable: bool, // false for part bindings
+    },
+
+    // evaluated at compile time, uses of name are replaced by the value
+    Const {
+        name: Ident,
+        val: NodeId,
+        public: bool,
     },
 
     VarAssign {
-        name: Box<Node>,
-        val: Box<Node>,
+        name: NodeId,
+        val: NodeId,
     },
     // fn declare ast is genereated in parser.functions
     FnCall {
-        name: Box<Node>,
-        args: Vec<Node>,
+        name: NodeId,
+        args: Vec<NodeId>,
     },
 
     Import {
         module: String,
-        name: String,
+        name: SymbolId,
         params: Vec<AtomType>,
     },
 
     Func {
-        ret: AtomType,
-        name: String,
+        ret: Box<AtomType>,
+        name: SymbolId,
         args: Vec<Ident>,
-        body: Vec<Node>,
+        body: Vec<NodeId>,
+        public: bool, // instances of blueprints without pub are static in C
     },
 
     Extern {
         name: Ident,
         params: Vec<Ident>,
+        public: bool,
+    },
+
+    Atom {
+        name: SymbolId,
+        fields: Vec<Ident>,
+        public: bool,
+    },
+
+    // interface methods are blueprints without a body
+    Iface {
+        name: SymbolId,
+        methods: Vec<Blueprint>,
+    },
+
+    Impl {
+        iface: SymbolId,
+        target: NodeId,
+        methods: Vec<Blueprint>,
+    },
+
+    // type Name(generics) = ty
+    Alias {
+        name: SymbolId,
+        generics: Vec<SymbolId>,
+        ty: NodeId,
     },
 
     IfExpr {
-        condition: Box<Node>,
-        body: Vec<Node>,
-        alt: Option<Box<Node>>,
+        condition: NodeId,
+        body: Vec<NodeId>,
+        alt: Option<NodeId>,
     },
 
     WhileExpr {
-        condition: Box<Node>,
-        body: Vec<Node>,
+        condition: NodeId,
+        body: Vec<NodeId>,
     },
 
     MemberExpr {
-        parent: Box<Node>,
-        child: String,
+        parent: NodeId,
+        child: SymbolId,
     },
 
     IndexExpr {
-        parent: Box<Node>,
-        index: Box<Node>,
+        parent: NodeId,
+        index: NodeId,
     },
     SpecExpr {
-        parent: Box<Node>,
-        spec: Vec<Node>,
+        parent: NodeId,
+        spec: Vec<NodeId>,
     },
 
-    Discard(Box<Node>),
-    Block(Vec<Node>),
-    PosInfo(String, u16, u16), // debugging
-    RetExpr(Box<Node>),
-    As(Box<Node>),
-}
-
-#[derive(Debug, Clone, PartialEq)]
-pub struct Node {
-    pub expr: Expr,
-    pub ty: AtomType,
+    Discard(NodeId),
+    Block(Vec<NodeId>),
+    PosInfo(SymbolId, u16, u16), // debugging
+    RetExpr(NodeId),
+    As(NodeId),           // conversion into node type, built by the analyzer
+    Cast(NodeId, NodeId), // expr as T
+    Is(NodeId, NodeId),   // expr is T
+    TypeOf(NodeId),
+    New(Vec<NodeId>), // constructs the atom of node type from fields, built by the analyzer
+    Args,             // the command line args passed to main, built by the analyzer
 }
 
-pub fn untyped(expr: Expr) -> Node {
-    Node {
-        expr,
-        ty: AtomType {
-            kind: AtomKind::Unknown,
-            details: None,
-        },
+impl Expr {
+    // nodes directly under this expr, blueprints that weren't built yet aren't included
+    pub fn children(&self) -> impl DoubleEndedIterator<Item = NodeId> + '_ {
+        // the nodes before a list of nodes, the list and the node after it
+        let (first, items, last): ([Option<NodeId>; 2], &[NodeId], Option<NodeId>) = match self {
+            Expr::ListExpr(items) | Expr::Block(items) | Expr::New(items) => {
+                ([None, None], items, None)
+            }
+            Expr::BinaryExpr { left, right, .. } => ([Some(*left), Some(*right)], &[], None),
+            Expr::VarDeclare { val, .. } | Expr::Const { val, .. } => {
+                ([Some(*val), None], &[], None)
+            }
+            Expr::VarAssign { name, val } => ([Some(*name), Some(*val)], &[], None),
+            Expr::FnCall { name, args } => ([Some(*name), None], args, None),
+            Expr::Func { body, .. } => ([None, None], body, None),
+            Expr::Impl { target, .. } => ([Some(*target), None], &[], None),
+            Expr::IfExpr {
+                condition,
+                body,
+                alt,
+            } => ([Some(*condition), None], body, *alt),
+            Expr::WhileExpr { condition, body } => ([Some(*condition), None], body, None),
+            Expr::MemberExpr { parent, .. } => ([Some(*parent), None], &[], None),
+            Expr::IndexExpr { parent, index } => ([Some(*parent), Some(*index)], &[], None),
+            Expr::SpecExpr { parent, spec } => ([Some(*parent), None], spec, None),
+            Expr::Discard(node) | Expr::RetExpr(node) | Expr::As(node) | Expr::TypeOf(node) => {
+                ([Some(*node), None], &[], None)
+            }
+            Expr::Cast(node, into) | Expr::Is(node, into) => {
+                ([Some(*node), Some(*into)], &[], None)
+            }
+            Expr::Use(_)
+            | Expr::Literal(_)
+            | Expr::Ident(_)
+            | Expr::Import { .. }
+            | Expr::Extern { .. }
+            | Expr::Atom { .. }
+            | Expr::Iface { .. }
+            | Expr::Alias { .. }
+            | Expr::Args
+            | Expr::PosInfo(..) => ([None, None], &[], None),
+        };
+        first
+            .into_iter()
+            .flatten()
+            .chain(items.iter().copied())
+            .chain(last)
     }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Ident {
-    Tagged(Box<Node>, String),
-    Typed(AtomType, String),
-    UnTagged(String),
+    Tagged(NodeId, SymbolId),
+    Typed(Box<AtomType>, SymbolId),
+    UnTagged(SymbolId),
 }
 
 impl Ident {
-    pub fn val(&self) -> &String {
+    pub fn sym(&self) -> SymbolId {
         match self {
-            Ident::Tagged(_, ref val) | Ident::UnTagged(ref val) | Ident::Typed(_, ref val) => val,
+            Ident::Tagged(_, val) | Ident::UnTagged(val) | Ident::Typed(_, val) => *val,
         }
     }
 
-    pub fn val_mut(&mut self) -> &mut String {
+    pub fn val(&self) -> &'static str {
+        self.sym().as_str()
+    }
+
+    pub fn rename(&mut self, name: &str) {
         match self {
-            Ident::Tagged(_, ref mut val)
-            | Ident::UnTagged(ref mut val)
-            | Ident::Typed(_, ref mut val) => val,
+            Ident::Tagged(_, val) | Ident::UnTagged(val) | Ident::Typed(_, val) => {
+                *val = SymbolId::intern(name)
+            }
         }
     }
 
     pub fn tuple(self) -> (AtomType, String) {
         match self {
-            Ident::Typed(ty, val) => (ty, val),
+            Ident::Typed(ty, val) => (*ty, val.to_string()),
             Ident::UnTagged(val) => (
                 AtomType {
                     kind: AtomKind::Any,
                     details: None,
                 },
-                val,
+                val.to_string(),
             ),
             _ => panic!(),
         }
@@ -176,12 +481,13 @@ impl Ident {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Blueprint {
     pub name: Ident,
     pub args: Vec<Ident>,
-    pub body: Vec<Node>,
-    // pub line: u16,
-    // pub column: u16,
-    // pub width: u16,
+    pub body: Vec<NodeId>,
+    pub allow: Vec<String>, // lints allowed by #allow(...) on the declaration
+    pub public: bool,       // declared with pub, other modules can use it
+    pub line: u16,
+    pub column: u16,
 }
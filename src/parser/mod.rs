@@ -1,11 +1,11 @@
 pub mod ast;
 pub mod parse;
 use self::ast::{Blueprint, Ident};
-use crate::err::{ATErr, ErrKind};
+use crate::err::{ATErr, ErrKind, Severity};
 use crate::lexer::token::Token;
 use crate::lexer::Lexer;
 use crate::scope::Scope;
-use ast::Node;
+use ast::{Ast, NodeId};
 
 #[derive(Debug, Clone)]
 pub struct Parser {
@@ -14,20 +14,29 @@ pub struct Parser {
     column: u16,
     current_tok: Option<Token>,
     pub functions: Vec<Blueprint>,
+    pub ast: Ast, // the nodes of every parsed expr and blueprint body
     current_scope: Scope,
     pub errors: Vec<ATErr>,
+    pub allows: Vec<String>, // lints allowed in the whole file
 }
 
 impl Parser {
     pub fn new(code: String) -> Self {
+        Self::with_ast(code, Ast::new())
+    }
+
+    // parses into an existing ast, used files share the ast of the main file
+    pub fn with_ast(code: String, ast: Ast) -> Self {
         Self {
             lexer: Lexer::new(code),
             line: 1,
             column: 0,
             current_tok: None,
             functions: vec![],
+            ast,
             current_scope: Scope::Top,
             errors: Vec::new(),
+            allows: Vec::new(),
         }
     }
 
@@ -37,13 +46,33 @@ impl Parser {
             msg,
             line: self.line,
             column: self.column,
+            severity: Severity::Error,
         };
         self.errors.push(err.clone());
         err.out_error();
     }
 
-    pub fn push_function(&mut self, name: Ident, args: Vec<Ident>, body: Vec<Node>) {
-        self.functions.push(Blueprint { name, args, body });
+    // the kind of the first error lexing or parsing reported, the parser skips past errors so
+    // it reports every one of them before the program is rejected
+    pub fn failed(&self) -> Option<ErrKind> {
+        let errors = self.lexer.errors.iter().chain(&self.errors);
+        errors
+            .min_by_key(|err| (err.line, err.column))
+            .map(|err| err.kind.clone())
+    }
+
+    // blueprints are at the statement declaring them
+    pub fn push_function(&mut self, name: Ident, args: Vec<Ident>, body: Vec<NodeId>) {
+        let (line, column) = self.ast.at;
+        self.functions.push(Blueprint {
+            name,
+            args,
+            body,
+            allow: Vec::new(),
+            public: false,
+            line,
+            column,
+        });
     }
     fn current(&mut self) -> Token {
         if self.current_tok.is_none() {
@@ -53,6 +82,7 @@ impl Parser {
     }
     fn next(&mut self) -> Token {
         let next = self.lexer.tokenize();
+        (self.line, self.column) = self.lexer.start;
         self.current_tok = Some(next.clone());
         next
     }
@@ -1,3 +1,4 @@
+use crate::analysis::lint::Linter;
 use crate::scope::Scope;
 
 use super::ast::*;
@@ -5,56 +6,61 @@ use super::Parser;
 use crate::err::ErrKind;
 
 use crate::lexer::token::Token;
+use crate::symbol::SymbolId;
 
-use crate::types::{AtomKind, AtomType};
 macro_rules! untyped {
-    ($expr: expr) => {
-        Ok(Node {
-            expr: $expr,
-            ty: AtomType {
-                kind: AtomKind::Unknown,
-                details: None,
-            },
-        })
-    };
+    ($self: ident, $expr: expr) => {{
+        let expr = $expr;
+        Ok($self.ast.untyped(expr))
+    }};
 }
 
 pub trait Parse {
-    fn parse_prog(&mut self) -> Vec<Node>;
-    fn parse_level(&mut self, level: u8) -> Result<Node, ()>;
+    fn parse_prog(&mut self) -> Vec<NodeId>;
+    fn parse_level(&mut self, level: u8) -> Result<NodeId, ()>;
 
-    fn parse_index(&mut self) -> Result<Node, ()>;
-    fn parse_spec(&mut self) -> Result<Node, ()>;
-    fn parse_call_fn(&mut self) -> Result<Node, ()>;
+    fn parse_cast(&mut self) -> Result<NodeId, ()>;
+    fn parse_index(&mut self) -> Result<NodeId, ()>;
+    fn parse_spec(&mut self) -> Result<NodeId, ()>;
+    fn parse_call_fn(&mut self) -> Result<NodeId, ()>;
 
-    fn parse_spec_list(&mut self) -> Result<Vec<Node>, ()>;
-    fn parse_member(&mut self) -> Result<Node, ()>;
+    fn parse_spec_list(&mut self) -> Result<Vec<NodeId>, ()>;
+    fn parse_member(&mut self) -> Result<NodeId, ()>;
 
-    fn parse_expr(&mut self) -> Result<Node, ()>;
+    fn parse_expr(&mut self) -> Result<NodeId, ()>;
 
-    fn parse_extern(&mut self) -> Result<Node, ()>;
-    fn parse_declare(&mut self) -> Result<Node, ()>;
-    fn parse_declare_fn(&mut self, id: Ident) -> Result<Node, ()>;
+    fn parse_extern(&mut self) -> Result<NodeId, ()>;
+    fn parse_declare(&mut self, mutable: bool) -> Result<NodeId, ()>;
+    fn parse_declare_fn(&mut self, id: Ident) -> Result<NodeId, ()>;
+    fn parse_fn_args(&mut self) -> Result<Vec<Ident>, ()>;
+    fn parse_id(&mut self) -> Result<Ident, ()>;
 
-    fn parse_if_expr(&mut self) -> Result<Node, ()>;
-    fn parse_while_expr(&mut self) -> Result<Node, ()>;
-    fn parse_ret_expr(&mut self) -> Result<Node, ()>;
+    fn parse_atom(&mut self) -> Result<NodeId, ()>;
+    fn parse_iface(&mut self) -> Result<NodeId, ()>;
+    fn parse_impl(&mut self) -> Result<NodeId, ()>;
+    fn parse_alias(&mut self) -> Result<NodeId, ()>;
+    fn parse_const(&mut self) -> Result<NodeId, ()>;
 
-    fn parse_body(&mut self) -> Vec<Node>;
-    fn parse_list(&mut self) -> Result<Vec<Node>, ()>;
+    fn parse_if_expr(&mut self) -> Result<NodeId, ()>;
+    fn parse_while_expr(&mut self) -> Result<NodeId, ()>;
+    fn parse_ret_expr(&mut self) -> Result<NodeId, ()>;
+
+    fn parse_body(&mut self) -> Vec<NodeId>;
+    fn parse_list(&mut self) -> Result<Vec<NodeId>, ()>;
 }
 
 impl Parse for Parser {
-    fn parse_prog(&mut self) -> Vec<Node> {
+    fn parse_prog(&mut self) -> Vec<NodeId> {
         let mut body = Vec::new();
         while self.current() != Token::EOF {
             self.current_scope = Scope::Top;
+            self.ast.at = (self.line, self.column);
             let expr = self.parse_level(0);
             if expr.is_ok() {
                 let mut expr = expr.unwrap();
 
                 if !self.current_scope.is_used() {
-                    expr = untyped(Expr::Discard(Box::new(expr)));
+                    expr = self.ast.untyped(Expr::Discard(expr));
                 }
 
                 body.push(expr);
@@ -64,103 +70,119 @@ impl Parse for Parser {
         body
     }
 
-    fn parse_level(&mut self, level: u8) -> Result<Node, ()> {
-        let mut left = self.parse_index()?;
+    fn parse_level(&mut self, level: u8) -> Result<NodeId, ()> {
+        let mut left = self.parse_cast()?;
         let mut right;
 
-        loop {
-            // 5 (2*) 5 nothing (1+) 5
-            if let Token::Operator(c) = self.current() {
-                if c == "=" {
-                    self.next();
-                    self.current_scope = Scope::Value;
-                    let right = self.parse_level(0)?;
-
-                    left = untyped(Expr::VarAssign {
-                        name: Box::new(left),
-                        val: Box::new(right),
-                    });
-                    break;
-                }
-
-                let current_op_level = get_operator_level(c.as_str());
-                if current_op_level < level {
-                    break;
-                }
-
+        // 5 (2*) 5 nothing (1+) 5
+        while let Token::Operator(c) = self.current() {
+            if c == "=" {
                 self.next();
-                right = self.parse_level(current_op_level + 1)?;
+                self.current_scope = Scope::Value;
+                let right = self.parse_level(0)?;
 
-                left = untyped(Expr::BinaryExpr {
-                    op: c,
-                    left: Box::new(left),
-                    right: Box::new(right),
+                left = self.ast.untyped(Expr::VarAssign {
+                    name: left,
+                    val: right,
                 });
-            } else {
                 break;
             }
+
+            let Some(op) = BinOp::parse(&c) else {
+                self.err(
+                    ErrKind::UnexceptedTokenE,
+                    format!("{c} is not a binary operator"),
+                );
+                self.next();
+                return Err(());
+            };
+            if op.level() < level {
+                break;
+            }
+
+            self.next();
+            right = self.parse_level(op.level() + 1)?;
+
+            left = self.ast.untyped(Expr::BinaryExpr { op, left, right });
         }
 
         Ok(left)
     }
 
-    fn parse_index(&mut self) -> Result<Node, ()> {
+    fn parse_cast(&mut self) -> Result<NodeId, ()> {
+        let mut expr = self.parse_index()?;
+
+        while self.current() == Token::AsKw || self.current() == Token::IsKw {
+            let is = self.current() == Token::IsKw;
+            self.next();
+            let ty = self.parse_spec()?;
+
+            expr = self.ast.untyped(if is {
+                Expr::Is(expr, ty)
+            } else {
+                Expr::Cast(expr, ty)
+            });
+        }
+        Ok(expr)
+    }
+
+    fn parse_index(&mut self) -> Result<NodeId, ()> {
         let expr = self.parse_call_fn()?;
 
         if self.current() == Token::LeftBrace {
             self.next();
-            let index = Box::new(self.parse_level(0)?);
+            let index = self.parse_level(0)?;
             self.except(Token::RightBrace);
 
-            return untyped!(Expr::IndexExpr {
-                parent: Box::new(expr),
-                index,
-            });
+            return untyped!(
+                self,
+                Expr::IndexExpr {
+                    parent: expr,
+                    index,
+                }
+            );
         }
         Ok(expr)
     }
 
-    fn parse_call_fn(&mut self) -> Result<Node, ()> {
+    fn parse_call_fn(&mut self) -> Result<NodeId, ()> {
         let call = self.parse_spec()?;
         if self.current() == Token::Colon {
             self.next();
             let args = self.parse_list()?;
-            return untyped!(Expr::FnCall {
-                name: Box::new(call),
-                args,
-            });
+            return untyped!(self, Expr::FnCall { name: call, args });
         }
 
         if self.current() == Token::Exec {
             self.next();
-            return untyped!(Expr::FnCall {
-                name: Box::new(call),
-                args: Vec::new(),
-            });
+            return untyped!(
+                self,
+                Expr::FnCall {
+                    name: call,
+                    args: Vec::new(),
+                }
+            );
         }
 
         Ok(call)
     }
 
-    fn parse_spec(&mut self) -> Result<Node, ()> {
+    fn parse_spec(&mut self) -> Result<NodeId, ()> {
         let mut left = self.parse_member()?;
 
         if self.current() == Token::LeftParen {
             self.next();
             let spec = self.parse_spec_list()?;
 
-            left = untyped(Expr::SpecExpr {
-                parent: Box::new(left),
-                spec,
-            });
+            left = self.ast.untyped(Expr::SpecExpr { parent: left, spec });
             self.except(Token::RightParen);
         }
 
         Ok(left)
     }
 
-    fn parse_spec_list(&mut self) -> Result<Vec<Node>, ()> {
-        let mut items: Vec<Node> = Vec::new();
+    fn parse_spec_list(&mut self) -> Result<Vec<NodeId>, ()> {
+        let mut items: Vec<NodeId> = Vec::new();
 
         items.push(self.parse_spec()?);
         while self.current() == Token::Comma {
@@ -171,30 +193,34 @@ impl Parse for Parser {
         Ok(items)
     }
 
-    fn parse_member(&mut self) -> Result<Node, ()> {
+    fn parse_member(&mut self) -> Result<NodeId, ()> {
         let left = self.parse_expr()?;
         if self.current() == Token::Dot {
             self.next();
             let right = self.parse_expr()?;
-            if let Expr::Ident(id) = right.expr {
-                untyped!(Expr::MemberExpr {
-                    parent: Box::new(left),
-                    child: id.val().clone(),
-                })
+            if let Expr::Ident(id) = self.ast.expr(right) {
+                let child = id.sym();
+                untyped!(
+                    self,
+                    Expr::MemberExpr {
+                        parent: left,
+                        child,
+                    }
+                )
             } else {
                 self.err(
                     ErrKind::UnexceptedTokenE,
-                    format!("expected id in member expr got {:?}", right),
+                    format!("expected id in member expr got {:?}", self.ast.expr(right)),
                 );
-                untyped!(Expr::Literal(Literal::Int(0)))
+                untyped!(self, Expr::Literal(Literal::Int(0)))
             }
         } else {
             Ok(left)
         }
     }
 
-    fn parse_list(&mut self) -> Result<Vec<Node>, ()> {
-        let mut items: Vec<Node> = Vec::new();
+    fn parse_list(&mut self) -> Result<Vec<NodeId>, ()> {
+        let mut items: Vec<NodeId> = Vec::new();
 
         items.push(self.parse_level(0)?);
         while self.current() == Token::Comma {
@@ -205,35 +231,40 @@ impl Parse for Parser {
         Ok(items)
     }
 
-    fn parse_expr(&mut self) -> Result<Node, ()> {
+    fn parse_expr(&mut self) -> Result<NodeId, ()> {
         let tok = self.current();
         match tok {
             Token::Int(i) => {
                 self.next();
-                untyped!(Expr::Literal(Literal::Int(i)))
+                untyped!(self, Expr::Literal(Literal::Int(i)))
             }
             Token::Float(f) => {
                 self.next();
-                untyped!(Expr::Literal(Literal::Float(f)))
+                untyped!(self, Expr::Literal(Literal::Float(f)))
             }
             Token::Bool(val) => {
                 self.next();
-                untyped!(Expr::Literal(Literal::Bool(val)))
+                untyped!(self, Expr::Literal(Literal::Bool(val)))
             }
             Token::Str(s) => {
                 self.next();
-                untyped!(Expr::Literal(Literal::Str(s)))
+                untyped!(self, Expr::Literal(Literal::Str(s)))
             }
 
-            Token::Err(_) => Err(()),
+            // the lexer already reported it
+            Token::Err(_) => {
+                self.next();
+                Err(())
+            }
 
             Token::Ident(id) => {
+                let id = SymbolId::intern(&id);
                 self.next();
                 if self.current() == Token::Dash {
                     self.next();
-                    untyped!(Expr::Ident(Ident::Tagged(Box::new(self.parse_spec()?), id)))
+                    untyped!(self, Expr::Ident(Ident::Tagged(self.parse_spec()?, id)))
                 } else {
-                    untyped!(Expr::Ident(Ident::UnTagged(id)))
+                    untyped!(self, Expr::Ident(Ident::UnTagged(id)))
                 }
             }
             // Token::Tag(tag) => {
@@ -255,16 +286,20 @@ impl Parse for Parser {
             }
 
             Token::LeftBrace => {
-                self.next();
-                let values = self.parse_list()?;
+                // [] is an empty list
+                let values = if self.next() == Token::RightBrace {
+                    Vec::new()
+                } else {
+                    self.parse_list()?
+                };
                 self.except(Token::RightBrace);
-                untyped!(Expr::ListExpr(values))
+                untyped!(self, Expr::ListExpr(values))
             }
             Token::UseKw => {
                 if let Token::Str(path) = self.next() {
                     self.current_scope = Scope::Use;
                     self.next();
-                    untyped!(Expr::Use(path))
+                    untyped!(self, Expr::Use(path))
                 } else {
                     let tok = self.current();
                     self.err(
@@ -275,8 +310,58 @@ impl Parse for Parser {
                 }
             }
             Token::ExternKw => self.parse_extern(),
+            Token::AtomKw => self.parse_atom(),
+            Token::IfaceKw => self.parse_iface(),
+            Token::ImplKw => self.parse_impl(),
+            Token::TypeKw => self.parse_alias(),
+            Token::ConstKw => self.parse_const(),
+            Token::TypeofKw => {
+                self.next();
+                let expr = self.parse_index()?;
+                untyped!(self, Expr::TypeOf(expr))
+            }
+
+            // #allow(lint, ...) on a blueprint declaration only applies to it, anywhere else to the whole file
+            Token::Allow(lints) => {
+                let linter = Linter::new();
+                for lint in &lints {
+                    if let Err(msg) = linter.check(lint) {
+                        self.err(ErrKind::Lint, msg);
+                    }
+                }
+                self.next();
+                let count = self.functions.len();
+                let node = self.parse_level(0)?;
 
-            Token::SetKw => self.parse_declare(),
+                if self.functions.len() > count {
+                    self.functions.last_mut().unwrap().allow.extend(lints);
+                } else {
+                    self.allows.extend(lints);
+                }
+                Ok(node)
+            }
+            // pub before a blueprint, extern, atom or const lets other modules use it
+            Token::PubKw => {
+                self.next();
+                let count = self.functions.len();
+                let node = self.parse_level(0)?;
+
+                match self.ast.expr_mut(node) {
+                    Expr::Extern { public, .. }
+                    | Expr::Atom { public, .. }
+                    | Expr::Const { public, .. } => *public = true,
+                    _ if self.functions.len() > count => {
+                        self.functions.last_mut().unwrap().public = true
+                    }
+                    _ => self.err(
+                        ErrKind::UnexceptedTokenE,
+                        "pub can only be used on blueprints, externs, atoms and consts".to_string(),
+                    ),
+                }
+                Ok(node)
+            }
+            Token::SetKw | Token::StateKw => self.parse_declare(true),
+            Token::PartKw => self.parse_declare(false),
             Token::WhileKw => self.parse_while_expr(),
             Token::IfKw => self.parse_if_expr(),
             Token::RetKw => self.parse_ret_expr(),
@@ -293,14 +378,14 @@ impl Parse for Parser {
         }
     }
 
-    fn parse_extern(&mut self) -> Result<Node, ()> {
+    fn parse_extern(&mut self) -> Result<NodeId, ()> {
         self.next();
 
         let name = self.parse_expr()?;
 
-        if let Expr::Ident(id) = name.expr {
+        if let Expr::Ident(id) = self.ast.expr(name) {
             if let Ident::Tagged(_, _) = id {
-                let name = id;
+                let name = id.clone();
 
                 self.except(Token::Colon);
 
@@ -309,7 +394,7 @@ impl Parse for Parser {
                 let mut id_params = Vec::new();
 
                 for (i, node) in params.iter().enumerate() {
-                    if let Expr::Ident(ref id) = node.expr {
+                    if let Expr::Ident(id) = self.ast.expr(*node) {
                         if let Ident::Tagged(_, _) = id {
                             id_params.push(id.clone());
                             continue;
@@ -323,7 +408,14 @@ impl Parse for Parser {
                 }
                 let params = id_params;
 
-                untyped!(Expr::Extern { name, params })
+                untyped!(
+                    self,
+                    Expr::Extern {
+                        name,
+                        params,
+                        public: false
+                    }
+                )
             } else {
                 self.err(
                     ErrKind::UnexceptedTokenE,
@@ -342,36 +434,61 @@ impl Parse for Parser {
         }
     }
 
-    fn parse_declare(&mut self) -> Result<Node, ()> {
+    fn parse_declare(&mut self, mutable: bool) -> Result<NodeId, ()> {
+        let kw = self.current();
         self.next();
 
         let left = self.parse_expr()?;
         self.current_scope = Scope::Value;
-        if let Expr::Ident(name) = left.expr {
+        if let Expr::Ident(name) = self.ast.expr(left).clone() {
             if Token::Operator("=".to_string()) == self.current() {
                 self.next();
 
                 let expr = self.parse_level(0)?;
-                return untyped!(Expr::VarDeclare {
-                    name,
-                    val: Box::new(expr),
-                });
+                return untyped!(
+                    self,
+                    Expr::VarDeclare {
+                        name,
+                        val: expr,
+                        mutable,
+                    }
+                );
             }
 
+            if kw != Token::SetKw {
+                let kw = if mutable { "state" } else { "part" };
+                self.err(
+                    ErrKind::UnexceptedTokenE,
+                    format!("blueprints are declared with set, {kw} only declares values"),
+                );
+            }
             self.parse_declare_fn(name)
         } else {
             self.err(
                 ErrKind::UnexceptedTokenE,
                 format!(
                     "unexcept token in set expression [{:?}] excepted an id",
-                    left
+                    self.ast.expr(left)
                 ),
             );
 
             Ok(left)
         }
     }
-    fn parse_declare_fn(&mut self, id: Ident) -> Result<Node, ()> {
+    fn parse_declare_fn(&mut self, id: Ident) -> Result<NodeId, ()> {
+        let id_args = match self.parse_fn_args() {
+            Ok(args) => args,
+            Err(()) => return self.parse_level(0),
+        };
+        let body = self.parse_body();
+
+        self.push_function(id.clone(), id_args, body);
+        self.current_scope = Scope::Value;
+        untyped!(self, Expr::PosInfo(id.sym(), self.line, self.column))
+    }
+
+    // parses `: arg, arg` or `!`
+    fn parse_fn_args(&mut self) -> Result<Vec<Ident>, ()> {
         let mut id_args: Vec<Ident> = Vec::new();
 
         if self.current() == Token::Colon {
@@ -379,87 +496,244 @@ impl Parse for Parser {
             let args = self.parse_list()?;
 
             for arg in args {
-                if let Expr::Ident(id) = arg.expr {
+                if let Expr::Ident(id) = self.ast.expr(arg).clone() {
                     id_args.push(id);
                 } else {
                     self.err(
                         ErrKind::UnexceptedArgs,
                         "excepted an id for arg".to_string(),
                     );
-                    return self.parse_level(0);
+                    return Err(());
                 }
             }
         } else {
             self.except(Token::Exec);
         }
-        let body = self.parse_body();
 
-        self.push_function(id.clone(), id_args, body);
+        Ok(id_args)
+    }
+
+    fn parse_id(&mut self) -> Result<Ident, ()> {
+        let node = self.parse_expr()?;
+        if let Expr::Ident(id) = self.ast.expr(node) {
+            Ok(id.clone())
+        } else {
+            self.err(
+                ErrKind::UnexceptedTokenE,
+                format!("expected an id got {:?}", self.ast.expr(node)),
+            );
+            Err(())
+        }
+    }
+
+    fn parse_atom(&mut self) -> Result<NodeId, ()> {
+        self.next();
+        let name = self.parse_id()?.sym();
+
+        let mut fields = Vec::new();
+        self.except(Token::LeftBracket);
+        while self.current() != Token::RightBracket && self.current() != Token::EOF {
+            let field = self.parse_id()?;
+            if let Ident::UnTagged(_) = field {
+                self.err(
+                    ErrKind::UnexceptedTokenE,
+                    format!("expected a typed id as field of atom {name}"),
+                );
+                return Err(());
+            }
+            fields.push(field);
+
+            if self.current() == Token::Comma {
+                self.next();
+            }
+        }
+        self.except(Token::RightBracket);
+
+        self.current_scope = Scope::Value;
+        untyped!(
+            self,
+            Expr::Atom {
+                name,
+                fields,
+                public: false
+            }
+        )
+    }
+
+    fn parse_iface(&mut self) -> Result<NodeId, ()> {
+        self.next();
+        let name = self.parse_id()?.sym();
+
+        let mut methods = Vec::new();
+        self.except(Token::LeftBracket);
+        while self.current() != Token::RightBracket && self.current() != Token::EOF {
+            self.except(Token::SetKw);
+            let (line, column) = (self.line, self.column);
+            let name = self.parse_id()?;
+            let args = self.parse_fn_args()?;
+
+            methods.push(Blueprint {
+                name,
+                args,
+                body: Vec::new(),
+                allow: Vec::new(),
+                public: true,
+                line,
+                column,
+            });
+        }
+        self.except(Token::RightBracket);
+
         self.current_scope = Scope::Value;
-        untyped!(Expr::PosInfo(id.val().clone(), self.line, self.column))
+        untyped!(self, Expr::Iface { name, methods })
     }
 
-    fn parse_if_expr(&mut self) -> Result<Node, ()> {
+    fn parse_impl(&mut self) -> Result<NodeId, ()> {
+        self.next();
+        let iface = self.parse_id()?.sym();
+        self.except(Token::ForKw);
+        let target = self.parse_spec()?;
+
+        let mut methods = Vec::new();
+        self.except(Token::LeftBracket);
+        while self.current() != Token::RightBracket && self.current() != Token::EOF {
+            self.except(Token::SetKw);
+            let (line, column) = (self.line, self.column);
+            let name = self.parse_id()?;
+            let args = self.parse_fn_args()?;
+            let body = self.parse_body();
+
+            methods.push(Blueprint {
+                name,
+                args,
+                body,
+                allow: Vec::new(),
+                public: true,
+                line,
+                column,
+            });
+        }
+        self.except(Token::RightBracket);
+
+        self.current_scope = Scope::Value;
+        untyped!(
+            self,
+            Expr::Impl {
+                iface,
+                target,
+                methods
+            }
+        )
+    }
+
+    fn parse_alias(&mut self) -> Result<NodeId, ()> {
+        self.next();
+        let name = self.parse_id()?.sym();
+
+        let mut generics = Vec::new();
+        if self.current() == Token::LeftParen {
+            self.next();
+            while self.current() != Token::RightParen && self.current() != Token::EOF {
+                generics.push(self.parse_id()?.sym());
+
+                if self.current() == Token::Comma {
+                    self.next();
+                }
+            }
+            self.except(Token::RightParen);
+        }
+
+        self.except(Token::Operator("=".to_string()));
+        let ty = self.parse_spec()?;
+
+        self.current_scope = Scope::Value;
+        untyped!(self, Expr::Alias { name, generics, ty })
+    }
+
+    // const NAME = expr
+    fn parse_const(&mut self) -> Result<NodeId, ()> {
+        self.next();
+        let name = self.parse_id()?;
+
+        self.except(Token::Operator("=".to_string()));
+        self.current_scope = Scope::Value;
+        let val = self.parse_level(0)?;
+
+        untyped!(
+            self,
+            Expr::Const {
+                name,
+                val,
+                public: false
+            }
+        )
+    }
+
+    fn parse_if_expr(&mut self) -> Result<NodeId, ()> {
         self.next(); // remove if
         self.current_scope = Scope::Value;
         let condition = self.parse_level(0)?;
         let body = self.parse_body();
 
-        let mut alt: Option<Box<Node>> = None;
+        let mut alt: Option<NodeId> = None;
         if self.current() == Token::ElseKw {
             self.next();
             if self.current() == Token::IfKw {
-                alt = Some(Box::new(self.parse_if_expr()?));
+                alt = Some(self.parse_if_expr()?);
             } else {
-                alt = Some(Box::new(untyped(Expr::Block(self.parse_body()))));
+                let body = self.parse_body();
+                alt = Some(self.ast.untyped(Expr::Block(body)));
             }
         }
 
-        untyped!(Expr::IfExpr {
-            condition: Box::new(condition),
-            body,
-            alt,
-        })
+        untyped!(
+            self,
+            Expr::IfExpr {
+                condition,
+                body,
+                alt,
+            }
+        )
     }
-    fn parse_while_expr(&mut self) -> Result<Node, ()> {
+    fn parse_while_expr(&mut self) -> Result<NodeId, ()> {
         self.next();
         self.current_scope = Scope::Value;
         let condition = self.parse_level(0)?;
         let body = self.parse_body();
 
-        untyped!(Expr::WhileExpr {
-            condition: Box::new(condition),
-            body,
-        })
+        untyped!(self, Expr::WhileExpr { condition, body })
     }
 
     #[inline]
-    fn parse_body(&mut self) -> Vec<Node> {
+    fn parse_body(&mut self) -> Vec<NodeId> {
         let mut body = vec![];
+        let at = self.ast.at;
 
         self.except(Token::LeftBracket);
         while self.current() != Token::RightBracket && self.current() != Token::EOF {
             self.current_scope = Scope::Top;
+            self.ast.at = (self.line, self.column);
             let expr = self.parse_level(0);
             if expr.is_ok() {
                 let mut expr = expr.unwrap();
 
                 if !self.current_scope.is_used() {
-                    expr = untyped(Expr::Discard(Box::new(expr)));
+                    expr = self.ast.untyped(Expr::Discard(expr));
                 }
 
                 body.push(expr);
             }
         }
         self.except(Token::RightBracket);
+        self.ast.at = at;
 
         body
     }
 
-    fn parse_ret_expr(&mut self) -> Result<Node, ()> {
+    fn parse_ret_expr(&mut self) -> Result<NodeId, ()> {
         self.next();
         self.current_scope = Scope::Value;
         let expr = self.parse_level(0)?;
-        untyped!(Expr::RetExpr(Box::new(expr)))
+        untyped!(self, Expr::RetExpr(expr))
     }
 }
@@ -0,0 +1,51 @@
+// identifiers are interned once per compilation thread, a SymbolId is compared and hashed like the
+// u32 it is and gives its name back with as_str. interned names live until the compiler exits
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SymbolId(u32);
+
+#[derive(Default)]
+struct Interner {
+    ids: HashMap<&'static str, SymbolId>,
+    names: Vec<&'static str>,
+}
+
+thread_local! {
+    static INTERNER: RefCell<Interner> = RefCell::new(Interner::default());
+}
+
+impl SymbolId {
+    pub fn intern(name: &str) -> Self {
+        INTERNER.with(|interner| {
+            let mut interner = interner.borrow_mut();
+            if let Some(id) = interner.ids.get(name) {
+                return *id;
+            }
+
+            let name: &'static str = Box::leak(name.to_string().into_boxed_str());
+            let id = SymbolId(interner.names.len() as u32);
+            interner.names.push(name);
+            interner.ids.insert(name, id);
+            id
+        })
+    }
+
+    pub fn as_str(self) -> &'static str {
+        INTERNER.with(|interner| interner.borrow().names[self.0 as usize])
+    }
+}
+
+impl fmt::Display for SymbolId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for SymbolId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
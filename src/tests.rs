@@ -0,0 +1,1023 @@
+use super::*;
+
+// the example programs in code/ that the IR tests go through
+const EXAMPLES: &[&str] = &[
+    "casts",
+    "ifaces",
+    "infer",
+    "aliases",
+    "dynamic",
+    "mutability",
+    "reassign",
+    "returns",
+    "recursion",
+    "overloads",
+    "consts",
+    "scopes",
+    "entry",
+    "modules",
+    "lints",
+];
+
+fn test_config(path: &str, workdir: &str) -> CompilerConfig {
+    let prog = fs::read_to_string(path).unwrap();
+    // the output is named after the whole path, files of different dirs share stems like main
+    let name: String = Path::new(path)
+        .with_extension("")
+        .to_str()
+        .unwrap()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let outdir = env::temp_dir().join("covalent_tests");
+    fs::create_dir_all(&outdir).unwrap();
+
+    let mut config = CompilerConfig::new(
+        prog,
+        Backend::C(CSettings::new(None, Vec::new())),
+        false,
+        outdir.join(&name).to_str().unwrap().to_string(),
+        workdir.to_string(),
+    );
+    // test executables are in target/<profile>/deps, the runtime is in target/<profile>/lib
+    let exe = current_exe().unwrap();
+    config.libdir = format!("{}/lib", exe.parent().unwrap().parent().unwrap().display());
+    config
+}
+
+// builds the program with gcc and runs the executable, then runs the program with the
+// interpreter, both get input as stdin and have to print expected and exit with code
+fn run_test(path: &str, workdir: &str, input: &str, expected: &str, code: i32) {
+    use ir::interp::{Interpreter, Program};
+    use std::process::{Command, Stdio};
+
+    let config = test_config(path, workdir);
+    let _ = fs::remove_file(&config.output);
+    let ir = if path.ends_with(".air") {
+        config.compile_ir();
+        config.parse_ir()
+    } else {
+        config.compile();
+        config.gen_ir()
+    };
+
+    let mut exe = Command::new(&config.output)
+        .arg("a")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    exe.stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let out = exe.wait_with_output().unwrap();
+    assert_eq!(
+        String::from_utf8(out.stdout).unwrap(),
+        expected,
+        "{path} executable"
+    );
+    assert_eq!(out.status.code(), Some(code), "{path} executable");
+
+    let program = Program::new(&ir);
+    let (mut out, mut stdin) = (Vec::new(), input.as_bytes());
+    let args = vec![config.output.clone(), "a".to_string()];
+    let result = Interpreter::new(&program, &mut out, &mut stdin).run(args);
+    let exit = result.unwrap_or_else(|trap| {
+        writeln!(out, "{trap}").unwrap();
+        trap.code
+    });
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        expected,
+        "{path} interpreted"
+    );
+    assert_eq!(exit, code, "{path} interpreted");
+}
+
+// the program is rejected with a diagnostic containing each of msgs
+fn err_test(src: &str, msgs: &[&str]) {
+    deny_test(src, &[], msgs);
+}
+
+// like err_test with the lints in deny denied
+fn deny_test(src: &str, deny: &[&str], msgs: &[&str]) {
+    let mut config = CompilerConfig::new(
+        src.to_string(),
+        Backend::C(CSettings::new(None, Vec::new())),
+        false,
+        String::new(),
+        "code".to_string(),
+    );
+    config.lints = deny
+        .iter()
+        .map(|lint| (lint.to_string(), Level::Deny))
+        .collect();
+    err::take_reported();
+    assert!(config.try_gen_ir().is_err(), "{src}");
+    let reported = err::take_reported().join("\n");
+    for msg in msgs {
+        assert!(reported.contains(msg), "{msg:?} is not in {reported:?}");
+    }
+}
+
+#[test]
+fn test() {
+    let expected = "name: age: age + 5
+35
+Hello, bob you are 30!
+5! ->
+120
+5.0! ->
+doing float things!
+doing float things!
+doing float things!
+doing float things!
+doing float things!
+120.000000
+6! ->
+720
+LIST[0]: \n1
+s.size: \n9
+s[0] = 0:
+0
+last: \n9
+factorial of \n5
+is \n120
+";
+    run_test("TestProj/main.atoms", "TestProj", "bob\n30\n", expected, 0);
+}
+
+#[test]
+fn casts() {
+    let expected = "3
+3.750000
+false
+1
+true
+43
+5.000000
+true
+8
+covalent runtime error: cannot convert str \"forty two\" into int
+";
+    run_test("code/casts.atoms", "code", "", expected, 6);
+
+    err_test(
+        "writeln: [1] as str",
+        &["cannot convert from List(int) into str"],
+    );
+    err_test(
+        "atom P { x@int }\nwriteln: (P: 1) as str",
+        &["cannot convert from P into str"],
+    );
+    err_test("set f: x@int { ret x }\nwriteln: f as str", &["into str"]);
+    err_test(
+        "set g: x@int { writeln: x }\nset v = (g: 1) as Dynamic",
+        &["cannot convert from void into Dynamic"],
+    );
+    err_test(
+        "set g: x@int { writeln: x }\nset d@Dynamic = 1\nd = g: 1",
+        &["cannot assign void to d"],
+    );
+
+    // the whole str is the value and it has to fit in an int
+    let path = env::temp_dir().join("covalent_stoi.atoms");
+    for value in ["99999999999", "-2147483649", " 1"] {
+        fs::write(&path, format!("writeln: \"{value}\" as int")).unwrap();
+        let expected = format!("covalent runtime error: cannot convert str \"{value}\" into int\n");
+        run_test(path.to_str().unwrap(), "code", "", &expected, 6);
+    }
+}
+
+#[test]
+fn ifaces() {
+    run_test(
+        "code/ifaces.atoms",
+        "code",
+        "",
+        "(4, 6)\nint 5\n6\ntrue\n",
+        0,
+    );
+
+    // atoms are mangled in C, their names can be C keywords or runtime types
+    let path = env::temp_dir().join("covalent_atom_names.atoms");
+    let src = "atom Obj { x@int }\natom main { y@int }\nwriteln: (Obj: 3).x + (main: 4).y";
+    fs::write(&path, src).unwrap();
+    run_test(path.to_str().unwrap(), "code", "", "7\n", 0);
+
+    err_test(
+        "atom P { x@int }\natom P { y@int }",
+        &["type P is already declared"],
+    );
+    err_test(
+        "atom P { x@int }\nset p = P: 1, 2",
+        &["atom P has 1 fields got 2 arguments"],
+    );
+    err_test(
+        "atom P { x@int }\nset p = P: \"one\"",
+        &["field x of atom P expects int, got str"],
+    );
+    err_test(
+        "iface Show { set show: self }\natom P { x@int }\nimpl Show for P { }",
+        &["P is missing method show required by Show"],
+    );
+    err_test("atom P { x@int }\nwriteln: (P: 1).y", &["P has no field y"]);
+}
+
+#[test]
+fn infer() {
+    run_test("code/infer.atoms", "code", "", "8\n120\n9.500000\n", 0);
+
+    // a Dynamic boxes a list once its item type is known
+    let path = env::temp_dir().join("covalent_infer_dynamic.atoms");
+    let src = "set xs = []\nwriteln: xs\nxs.push: 1\nset d@Dynamic = 1\nd = xs\nwriteln: d";
+    fs::write(&path, src).unwrap();
+    run_test(path.to_str().unwrap(), "code", "", "[]\n[1]\n", 0);
+
+    err_test(
+        "set xs = []",
+        &["cannot infer type List(?0) of xs, add a type tag"],
+    );
+    err_test(
+        "set xs = []\nxs.push: 1\nxs.push: \"two\"",
+        &["cannot push str into List(int)"],
+    );
+    err_test(
+        "extern cprompt@str: msg@str\nwriteln: cprompt: \"a\", \"b\"",
+        &["expected 1 arguments got 2, for function cprompt"],
+    );
+    err_test(
+        "set f: a {\n ret a\n}\nf: []",
+        &["cannot infer type of argument 0 to f, got List(?0)"],
+    );
+    err_test(
+        "set xs = [1, 2]\nwriteln: xs + 1.5",
+        &["operator + cannot be applied to List(int) and float"],
+    );
+    err_test(
+        "set xs = []\nwriteln: xs + 1.5",
+        &["operator + cannot be applied to List(?0) and float"],
+    );
+}
+
+#[test]
+fn aliases() {
+    run_test("code/aliases.atoms", "code", "", "7\n7\n11\nalan\n", 0);
+
+    err_test(
+        "type Row = 5",
+        &["cannot alias int as Row, it is not a type"],
+    );
+    err_test(
+        "type Pair(T) = List(T)\nset p@Pair(int, int) = [1]",
+        &["expected 1 generics got 2"],
+    );
+    err_test(
+        "type Row = List(int)\nset row@Row = [\"a\"]",
+        &["unexpected type List(str), for id row"],
+    );
+}
+
+#[test]
+fn dynamic() {
+    let expected = "[1, 2, 3]
+Point { x: 1, y: 2 }
+an int 7
+a List(int)
+a Point
+[1, two, [1, 2, 3]]
+true
+3
+[1, 2, 3, 4]
+true
+float
+covalent runtime error: cannot downcast Dynamic holding List(int) into Point
+";
+    run_test("code/dynamic.atoms", "code", "", expected, 7);
+
+    // == is false for values of different types, other operators stop the program
+    let path = env::temp_dir().join("covalent_dynamic_eq.atoms");
+    let src = "atom P { x@int }
+atom Q { x@int }
+set a@Dynamic = P: 1
+set b@Dynamic = Q: 1
+writeln: a == b
+writeln: a == ((P: 1) as Dynamic)
+set c@Dynamic = 1
+set d@Dynamic = \"1\"
+writeln: c == d
+set xs@List(Dynamic) = []
+xs.push: 1
+xs.push: \"two\"
+writeln: xs
+writeln: c > d";
+    fs::write(&path, src).unwrap();
+    let expected = "false
+true
+false
+[1, two]
+covalent runtime error: cannot operate on int and str
+";
+    run_test(path.to_str().unwrap(), "code", "", expected, 5);
+
+    err_test(
+        "set g: x@int { ret x }\nwriteln: (1 as Dynamic) is g",
+        &["is not a type"],
+    );
+}
+
+#[test]
+fn mutability() {
+    run_test("code/mutability.atoms", "code", "", "hello\n10\n", 0);
+
+    err_test(
+        "part limit = 5\nlimit = 6",
+        &["cannot assign to part limit, it is immutable"],
+    );
+}
+
+#[test]
+fn reassign() {
+    run_test("code/reassign.atoms", "code", "", "2.000000\ndone\n", 0);
+
+    err_test(
+        "set i = 0\ni = \"four\"",
+        &["cannot assign str to i, it was declared as int"],
+    );
+}
+
+#[test]
+fn returns() {
+    run_test("code/returns.atoms", "code", "", "-1\n8\n", 0);
+
+    err_test(
+        "set half: n {\n\tif n > 0 {\n\t\tret n / 2\n\t}\n}\nwriteln: half: 4",
+        &["function half(int) doesn't return int on every path"],
+    );
+}
+
+#[test]
+fn recursion() {
+    run_test("code/recursion.atoms", "code", "", "true\ntrue\n111\n", 0);
+
+    err_test(
+        "set spin: n {\n\tret spun: n\n}\nset spun: n {\n\tret spin: n\n}\nwriteln: spin: 1",
+        &["cannot infer return type of spin(int), it only depends on itself and spun(int)"],
+    );
+}
+
+#[test]
+fn overloads() {
+    run_test(
+        "code/overloads.atoms",
+        "code",
+        "",
+        "int\nfloat\nsomething\ntwo args\n1.500000\n",
+        0,
+    );
+
+    err_test(
+        "set pick: x@int, y {\n\tret 1\n}\nset pick: x, y@int {\n\tret 2\n}\nwriteln: pick: 1, 2",
+        &[
+            "call to pick(int, int) is ambiguous, it matches these overloads equally well:",
+            "\tpick(int, any)\n\tpick(any, int)",
+        ],
+    );
+    err_test(
+        "set describe: x@int {\n\tret 1\n}\nset describe: label@str, x {\n\tret 2\n}\nwriteln: describe: true, false",
+        &[
+            "no overload of describe matches describe(bool, bool), overloads are:",
+            "describe(int): takes 1 args, got 2",
+            "describe(str, any): param label takes str, got bool",
+        ],
+    );
+}
+
+#[test]
+fn consts() {
+    run_test(
+        "code/consts.atoms",
+        "code",
+        "",
+        "grid of cells\n32\n3.000000\ntrue\n19\n[8, 2, 3, 4]\n4\n86400\n",
+        0,
+    );
+
+    err_test(
+        "const WIDTH = 8\nconst BAD = WIDTH / (WIDTH - 8)",
+        &["cannot evaluate const BAD, division by zero"],
+    );
+    err_test(
+        "set cells = 4\nconst RUNTIME = cells + 1",
+        &["cannot evaluate const RUNTIME, cells is not a const"],
+    );
+    err_test(
+        "const WIDTH = 8\nWIDTH = 10",
+        &["cannot assign to const WIDTH, it is evaluated at compile time"],
+    );
+    err_test(
+        "const N = 3\nset cells@List(N) = [1]",
+        &["cannot use const N as a generic arg of List(T), generic args are types"],
+    );
+    err_test(
+        "const N = 3\nset cells@List(int, N) = [1, 2]",
+        &["expected 3 items for List(int, 3), got 2"],
+    );
+    err_test(
+        "set cells@List(int, 2) = [1, 2]\ncells.push: 3",
+        &["cannot push List(int, 2), its size is fixed"],
+    );
+    err_test(
+        "set cells@List(int, 2) = [1, 2]\nwriteln: cells[2]",
+        &["index 2 is out of bounds for List(int, 2)"],
+    );
+    err_test(
+        "set n = 2\nset cells@List(int, n) = [1, 2]",
+        &["the size of List(T, N) has to be known at compile time"],
+    );
+    err_test(
+        "type Row(N) = List(int, N)\nset n = 2\nset row@Row(n) = [1, 2]",
+        &["cannot use a value of type int as a generic arg of Row, generic args are types or int consts"],
+    );
+    err_test(
+        "const BIG = 99999999999",
+        &["int 99999999999 does not fit in an int"],
+    );
+    err_test("const V = 1.2.3", &["invalid float 1.2.3"]);
+
+    // outside of a const a division by zero is left to the runtime
+    let path = env::temp_dir().join("covalent_div_zero.atoms");
+    fs::write(&path, "if false {\n\twriteln: 1 / 0\n}\nwriteln: 1.0 / 0.0").unwrap();
+    run_test(path.to_str().unwrap(), "code", "", "inf\n", 0);
+}
+
+#[test]
+fn scopes() {
+    run_test(
+        "code/scopes.atoms",
+        "code",
+        "",
+        "inner\nouter\n0\n1\n4\n30\n",
+        0,
+    );
+
+    err_test(
+        "set count = 3\nif count > 2 {\n\tset extra = count * 2\n}\nwriteln: extra",
+        &["extra is used outside of the block it was declared in"],
+    );
+    err_test(
+        "set count = 3\nset add_count: n {\n\tret n + count\n}\nwriteln: add_count: 1",
+        &["count is declared outside of the blueprint using it, pass it as an arg or make it a const"],
+    );
+    err_test(
+        "set label = \"outer\"\nset label = \"again\"",
+        &["label is already declared"],
+    );
+}
+
+#[test]
+fn entry() {
+    // args[0] is the program as it was run, the test runs it by its output path
+    let output = test_config("code/entry.atoms", "code").output;
+    let expected = format!("starting\nhello from\n{output}\n1\n");
+    run_test("code/entry.atoms", "code", "", &expected, 0);
+
+    // its int return is the exit code
+    let path = env::temp_dir().join("covalent_exit_code.atoms");
+    fs::write(&path, "set main: args {\n\tret 3\n}").unwrap();
+    run_test(path.to_str().unwrap(), "code", "", "", 3);
+
+    err_test(
+        "set main: args {\n\tret \"done\"\n}",
+        &["main returns str, it can only return an int exit code or nothing"],
+    );
+    err_test(
+        "set main: a, b {\n\tret 0\n}",
+        &["main takes 2 params, it can only take none or the command line args as one List(str) param"],
+    );
+}
+
+#[test]
+fn modules() {
+    run_test(
+        "code/modules.atoms",
+        "code",
+        "",
+        "geometry loaded\n10\n60\n30\n30\nmain loaded\n",
+        0,
+    );
+
+    err_test(
+        "use \"lib/shapes.atoms\"\nset total = sum",
+        &["sum is private to module lib/shapes.atoms, declare it with pub to use it here"],
+    );
+    // the names of geometry are in its namespace, shapes doesn't pass them on
+    err_test(
+        "use \"lib/shapes.atoms\"\nwriteln: UNIT",
+        &["UNIT is not declared"],
+    );
+    // uses are reported where they are even though they are loaded first
+    err_test(
+        "writeln: 1\nuse \"missing.atoms\"",
+        &["cannot find module missing.atoms", "at line:2, column:0"],
+    );
+
+    // private blueprints are named by their module, two modules can both have a helper
+    let dir = env::temp_dir().join("covalent_private");
+    fs::create_dir_all(&dir).unwrap();
+    let module = |name: &str, body: &str| {
+        let src = format!(
+            "set helper: n {{\n\tret {body}\n}}\npub set {name}: n {{\n\tret helper: n\n}}"
+        );
+        fs::write(dir.join(format!("{name}.atoms")), src).unwrap();
+    };
+    module("one", "n + 1");
+    module("two", "n * 2");
+    let main = dir.join("main.atoms");
+    let src = "use \"one.atoms\"\nuse \"two.atoms\"\nwriteln: one: 5\nwriteln: two: 5";
+    fs::write(&main, src).unwrap();
+    run_test(
+        main.to_str().unwrap(),
+        dir.to_str().unwrap(),
+        "",
+        "6\n10\n",
+        0,
+    );
+
+    err_test(
+        "use \"lib/shapes.atoms\"\nset area: w {\n\tret w\n}",
+        &["blueprint area is already declared in lib/shapes.atoms"],
+    );
+
+    let dir = env::temp_dir().join("covalent_cycle");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("a.atoms"), "use \"b.atoms\"").unwrap();
+    fs::write(dir.join("b.atoms"), "writeln: 1\nuse \"a.atoms\"").unwrap();
+    err_test(
+        &format!("use \"{}\"", dir.join("a.atoms").display()),
+        &[
+            "import cycle: ",
+            "b.atoms -> a.atoms",
+            "at line:2, column:0",
+        ],
+    );
+
+    // only the main of the root file is the entry point, a module's main isn't used
+    let dir = env::temp_dir().join("covalent_module_main");
+    fs::create_dir_all(&dir).unwrap();
+    let lib = "pub set main: args {\n\twriteln: \"lib main\"\n\tret 1\n}";
+    fs::write(dir.join("lib.atoms"), lib).unwrap();
+    let app = dir.join("module_main.atoms");
+    fs::write(&app, "use \"lib.atoms\"\nwriteln: \"top\"").unwrap();
+    run_test(app.to_str().unwrap(), dir.to_str().unwrap(), "", "top\n", 0);
+    let app = dir.join("own_main.atoms");
+    let src = "use \"lib.atoms\"\nset main: args {\n\twriteln: \"own main\"\n}";
+    fs::write(&app, src).unwrap();
+    run_test(
+        app.to_str().unwrap(),
+        dir.to_str().unwrap(),
+        "",
+        "own main\n",
+        0,
+    );
+}
+
+#[test]
+fn lints() {
+    run_test("code/lints.atoms", "code", "", "12\nsame\n", 0);
+
+    // each hit is reported where it is
+    deny_test(
+        "set x = 1\nif x > 0 {\n\tset y = 2\n}",
+        &["unused_variables"],
+        &["unused variable y in top level code [unused_variables]\nat line:3"],
+    );
+    // a read of an inner x doesn't use the outer one
+    deny_test(
+        "set x = 1\nif true {\n\tset x = 2\n\twriteln: x\n}",
+        &["unused_variables"],
+        &["unused variable x in top level code [unused_variables]\nat line:1"],
+    );
+    // #allow on a main blueprint doesn't reach top level code
+    deny_test(
+        "set x = 1\n#allow(unused_variables)\nset main: args {\n\tset y = args.size\n}",
+        &["unused_variables"],
+        &["unused variable x in top level code [unused_variables]\nat line:1"],
+    );
+    deny_test(
+        "set twice: n {\n\tret n * 2\n}",
+        &["all"],
+        &["blueprint twice is never called [unused_blueprints]\nat line:1"],
+    );
+    // #allow takes the names --allow does
+    err_test(
+        "#allow(unused_variables, nonsense)\nset x = 1",
+        &["unknown lint nonsense, lints are: all, always_true"],
+    );
+}
+
+#[test]
+fn ir_input() {
+    run_test("code/ir.air", "code", "", "10\none\nsecond is 2\n", 0);
+}
+
+// the IR of every example prints and parses back to the same IR
+#[test]
+fn ir_text() {
+    for example in EXAMPLES {
+        let ir = test_config(&format!("code/{example}.atoms"), "code").gen_ir();
+        let text = ir::text::print(&ir);
+        let parsed = ir::text::parse(&text).unwrap();
+        assert_eq!(parsed, ir, "{example}");
+        assert_eq!(ir::text::print(&parsed), text, "{example}");
+    }
+
+    // an int that doesn't fit isn't read as a float
+    assert!(ir::text::parse("const 99999999999\n").is_err());
+    assert!(ir::text::parse("const 1.5e300\n").is_ok());
+}
+
+// the bytecode of every example reads back to the same IR, and files of another version or
+// cut short are rejected
+#[test]
+fn bytecode() {
+    use ir::bytecode;
+
+    for example in EXAMPLES {
+        let ir = test_config(&format!("code/{example}.atoms"), "code").gen_ir();
+        let bytes = bytecode::write(&ir);
+        assert_eq!(bytecode::read(&bytes).unwrap(), ir, "{example}");
+        assert!(bytes.len() < ir::text::print(&ir).len(), "{example}");
+    }
+
+    let ir = ir::text::parse(&fs::read_to_string("code/ir.air").unwrap()).unwrap();
+    let bytes = bytecode::write(&ir);
+    assert_eq!(&bytes[..4], bytecode::MAGIC);
+    assert_eq!(bytes[4..6], bytecode::VERSION.to_le_bytes());
+
+    let mut newer = bytes.clone();
+    newer[4..6].copy_from_slice(&(bytecode::VERSION + 1).to_le_bytes());
+    assert!(bytecode::read(&newer).is_err());
+    for len in [0, 6, bytes.len() / 2, bytes.len() - 1] {
+        assert!(bytecode::read(&bytes[..len]).is_err(), "{len}");
+    }
+
+    // the body of f is replaced with a def of f, the code is the body of f then the top level
+    let ir = ir::text::parse("def void f() {\nret void\n}\n").unwrap();
+    let mut bytes = bytecode::write(&ir);
+    let body = bytes.len() - 6;
+    assert_eq!(bytes[body..body + 2], [1, 5]);
+    bytes[body..body + 3].copy_from_slice(&[1, 3, 0]);
+    assert!(bytecode::read(&bytes).is_err());
+}
+
+// the verifier accepts generated IR and rejects IR that would break the backend
+#[test]
+fn ir_verify() {
+    let verify = |src: &str| ir::tools::verify(&ir::text::parse(src).unwrap());
+
+    for example in EXAMPLES {
+        let ir = test_config(&format!("code/{example}.atoms"), "code").gen_ir();
+        assert!(ir::tools::verify(&ir).is_ok(), "{example}");
+    }
+    assert!(verify(&std::fs::read_to_string("code/ir.air").unwrap()).is_ok());
+
+    // the int ret is converted to the float the function returns
+    let path = env::temp_dir().join("covalent_mixed_rets.atoms");
+    fs::write(
+        &path,
+        "set f: n@int {\n\tif n > 0 {\n\t\tret 1\n\t}\n\tret 2.5\n}\nwriteln: f: 1\nwriteln: f: 0",
+    )
+    .unwrap();
+    let ir = test_config(path.to_str().unwrap(), "code").gen_ir();
+    assert!(ir::tools::verify(&ir).is_ok());
+    run_test(
+        path.to_str().unwrap(),
+        "code",
+        "",
+        "1.000000\n2.500000\n",
+        0,
+    );
+
+    // a type with no IR syntax is reported, not printed by panicking
+    let path = env::temp_dir().join("covalent_blueprint_value.atoms");
+    fs::write(
+        &path,
+        "set f: x@int {\n\tret x\n}\nset g = f\nwriteln: g: 2",
+    )
+    .unwrap();
+    let ir = test_config(path.to_str().unwrap(), "code").gen_ir();
+    assert!(ir::tools::verify(&ir).is_err());
+
+    let def = "def int id(int x) {\n    load int x\n    ret int\n}\n";
+    let bad = [
+        // stack underflow
+        "const 1\nadd int\npop\n",
+        // operands of another type
+        "const 1\nconst 2.0\nadd int\npop\n",
+        "const 1\nconst true\ncomp\npop\n",
+        "const 1\nconv Dynamic float\npop\n",
+        // wrong arity
+        "const 1\nconst 2\nload Fn(int) -> int id\ncall int 2\npop\n",
+        // undefined name
+        "load int y\npop\n",
+        // a condition that isn't a bool
+        "while {\n    const 1\n} do {\n}\n",
+        // ret of another type than the def
+        "def int f() {\n    const 1.0\n    ret float\n}\n",
+        // values left on the stack
+        "const 1\n",
+    ];
+    for src in bad {
+        assert!(verify(&format!("{def}{src}")).is_err(), "{src}");
+    }
+}
+
+// -O2 keeps the IR of every example valid, and folds, inlines and removes what it should
+#[test]
+fn passes() {
+    use ir::tools::PassManager;
+
+    for example in EXAMPLES {
+        let mut ir = test_config(&format!("code/{example}.atoms"), "code").gen_ir();
+        PassManager::new(2).run(&mut ir);
+        assert!(ir::tools::verify(&ir).is_ok(), "{example}");
+    }
+
+    let src = "def int twice(int x) {
+    load int x
+    load int x
+    add int
+    ret int
+}
+def int unused() {
+    const 1
+    ret int
+}
+import Fn(Dynamic) -> void std writeln(Dynamic)
+const 2
+const 3
+add int
+conv int int
+load Fn(int) -> int twice
+call int 1
+conv Dynamic int
+load Fn(Dynamic) -> void writeln
+call void 1
+const 0
+ret int
+const 1
+pop
+";
+    let mut ir = ir::text::parse(src).unwrap();
+    let mut manager = PassManager::new(2);
+    manager.run(&mut ir);
+
+    let expected = "import Fn(Dynamic) -> void std writeln(Dynamic)
+const 10
+conv Dynamic int
+load Fn(Dynamic) -> void writeln
+call void 1
+const 0
+ret int
+";
+    assert_eq!(ir::text::print(&ir), expected);
+    let changes: Vec<_> = manager
+        .stats
+        .iter()
+        .map(|stats| (stats.pass, stats.changes))
+        .collect();
+    assert_eq!(
+        changes,
+        [
+            ("noop_convs", 1),
+            ("const_fold", 1),
+            ("inline", 1),
+            ("const_fold", 1),
+            ("dead_code", 2),
+            ("dead_functions", 2)
+        ]
+    );
+    assert!(PassManager::new(0).stats.is_empty());
+}
+
+// every example lowers to well formed SSA and the analyses see through phis and branches
+#[test]
+fn ssa() {
+    use ir::ssa::analysis::{self, Lattice};
+    use ir::ssa::{InstKind, Temp, Term};
+    use parser::ast::Literal;
+
+    for example in EXAMPLES {
+        let ir = test_config(&format!("code/{example}.atoms"), "code").gen_ir();
+        for func in ir::ssa::lower(&ir).funcs {
+            let mut defined = vec![0; func.temps.len()];
+            for block in &func.blocks {
+                for inst in &block.insts {
+                    if let Some(dest) = inst.dest {
+                        defined[dest.0 as usize] += 1;
+                    }
+                }
+                for succ in block.term.succs() {
+                    assert!((succ.0 as usize) < func.blocks.len(), "{example}");
+                }
+            }
+            assert!(defined.iter().all(|count| *count == 1), "{example}");
+        }
+    }
+
+    let src = "def int pick() {
+    alloc int x
+    const 2
+    store int x
+    const 1
+    const 1
+    eq
+    if void {
+        load int x
+        const 3
+        set int
+    } else {
+        load int x
+        const 4
+        set int
+    }
+    load int x
+    ret int
+}
+def List(int) make(int n) {
+    alloc List(int) kept
+    list int {
+        item {
+            load int n
+        }
+    }
+    store List(int) kept
+    list int {
+        item {
+            const 1
+        }
+    }
+    const 0
+    loadidx int
+    pop
+    load List(int) kept
+    ret List(int)
+}
+";
+    let module = ir::ssa::lower(&ir::text::parse(src).unwrap());
+    let (pick, make) = (&module.funcs[0], &module.funcs[1]);
+
+    // only the then branch runs, so x is 3 at the ret
+    let consts = analysis::const_prop(pick);
+    let Term::Ret(Some(ret)) = pick.blocks.last().unwrap().term else {
+        panic!("pick doesn't ret at its end");
+    };
+    assert_eq!(
+        consts.values[ret.0 as usize],
+        Lattice::Const(Literal::Int(3))
+    );
+    assert_eq!(
+        consts.reachable.iter().filter(|reached| !**reached).count(),
+        1
+    );
+
+    // the returned list escapes, the indexed one doesn't
+    let lists: Vec<Temp> = make.blocks[0]
+        .insts
+        .iter()
+        .filter(|inst| matches!(inst.kind, InstKind::List(_)))
+        .map(|inst| inst.dest.unwrap())
+        .collect();
+    let escapes = analysis::escapes(make);
+    assert!(escapes.contains(&lists[0]) && !escapes.contains(&lists[1]));
+
+    // the loop of count keeps n live around it
+    let ir = ir::text::parse(&fs::read_to_string("code/ir.air").unwrap()).unwrap();
+    let count = &ir::ssa::lower(&ir).funcs[0];
+    let live = analysis::liveness(count);
+    assert!(live.live_in[1].contains(&Temp(0)) && live.live_out[2].contains(&Temp(0)));
+}
+
+// the interpreter prints what the executable prints, stops at runtime errors with the exit
+// code of the C runtime and calls externs through host functions
+#[test]
+fn interp() {
+    use ir::interp::{Interpreter, Program, Trap, Value};
+
+    let program = Program::new(&test_config("code/dynamic.atoms", "code").gen_ir());
+    let (mut out, mut input) = (Vec::new(), io::empty());
+    let trap = Interpreter::new(&program, &mut out, &mut input)
+        .run(Vec::new())
+        .unwrap_err();
+    let expected = "[1, 2, 3]
+Point { x: 1, y: 2 }
+an int 7
+a List(int)
+a Point
+[1, two, [1, 2, 3]]
+true
+3
+[1, 2, 3, 4]
+true
+float
+";
+    assert_eq!(String::from_utf8(out).unwrap(), expected);
+    assert_eq!(trap.code, 7);
+
+    // both runtimes check indexes and int divisions
+    let path = env::temp_dir().join("covalent_interp_index.atoms");
+    let src = "set xs = [1, 2]\nset i = 2\nwriteln: xs[i - 1]\nwriteln: xs[i]";
+    fs::write(&path, src).unwrap();
+    let expected = "2\ncovalent runtime error: index 2 is out of bounds for a list of size 2\n";
+    run_test(path.to_str().unwrap(), "code", "", expected, 10);
+
+    let path = env::temp_dir().join("covalent_interp_div.atoms");
+    let src = "set n = 0\nwriteln: 7 % (n + 2)\nset d@Dynamic = 7\nwriteln: d / n";
+    fs::write(&path, src).unwrap();
+    let expected = "1\ncovalent runtime error: division by zero\n";
+    run_test(path.to_str().unwrap(), "code", "", expected, 11);
+
+    let src = "extern int twice(int x)
+extern str cprompt(str msg)
+import Fn(Dynamic) -> void std writeln(Dynamic)
+const \"name: \"
+load Fn(str) -> str cprompt
+call str 1
+conv Dynamic str
+load Fn(Dynamic) -> void writeln
+call void 1
+const 21
+load Fn(int) -> int twice
+call int 1
+ret int
+";
+    fn twice(_: &mut Interpreter<'_>, args: Vec<Value>) -> Result<Value, Trap> {
+        match args[0] {
+            Value::Int(i) => Ok(Value::Int(i * 2)),
+            _ => Err(Trap::new("twice takes an int", 1)),
+        }
+    }
+    let program = Program::new(&ir::text::parse(src).unwrap());
+    let (mut out, mut input) = (Vec::new(), "  bob\n".as_bytes());
+    let mut interp = Interpreter::new(&program, &mut out, &mut input);
+    assert_eq!(interp.run(Vec::new()).unwrap_err().code, 12);
+    interp.register("twice", twice);
+    assert_eq!(interp.run(Vec::new()).unwrap(), 42);
+    assert_eq!(String::from_utf8(out).unwrap(), "name: bob\nname: \n");
+}
+
+#[test]
+fn mangling() {
+    use types::{AtomKind, AtomType, BasicType};
+
+    let int = AtomType {
+        kind: AtomKind::Basic(BasicType::Int),
+        details: None,
+    };
+    let list = AtomType {
+        kind: AtomKind::Atom(types::List.spec(std::slice::from_ref(&int))),
+        details: None,
+    };
+
+    let mangle = types::type_mangle("my_fn".to_string(), vec![list.clone(), int.clone()]);
+    assert!(mangle
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_'));
+    assert_eq!(types::demangle(&mangle), "my_fn(List(int), int)");
+    assert_eq!(types::type_mangle(mangle, Vec::new()), "at_5my_fnv");
+
+    // the size of a List(T, N) is part of its mangle
+    let AtomKind::Atom(atom) = &list.kind else {
+        unreachable!()
+    };
+    let sized = AtomType {
+        kind: AtomKind::Atom(atom.sized(Some(12))),
+        details: None,
+    };
+    let mangle = types::type_mangle("row".to_string(), vec![sized, int.clone()]);
+    assert_eq!(mangle, "at_3rowA4ListGiN12_Ei");
+    assert_eq!(types::demangle(&mangle), "row(List(int, 12), int)");
+
+    // blueprints of a module keep their module when they are mangled again
+    let mangle = types::type_mangle("shapes.sum".to_string(), Vec::new());
+    assert_eq!(mangle, "at_M6shapes3sumv");
+    assert_eq!(
+        types::demangle(&types::type_mangle(mangle, vec![int])),
+        "shapes.sum(int)"
+    );
+
+    // types that only exist during analysis mangle distinctly
+    let var = |n| AtomType {
+        kind: AtomKind::Var(n),
+        details: None,
+    };
+    let (a, b) = (
+        types::type_mangle("f".to_string(), vec![var(0)]),
+        types::type_mangle("f".to_string(), vec![var(1)]),
+    );
+    assert_ne!(a, b);
+    assert_eq!(types::demangle(&b), "f(?1)");
+}
@@ -1,9 +1,10 @@
 use core::fmt::Display;
 use indexmap::IndexMap;
-use std::collections::HashMap;
 
 use lazy_static::lazy_static;
 
+use crate::symbol::SymbolId;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BasicType {
     Int,
@@ -40,41 +41,56 @@ impl Display for FunctionType {
 #[derive(Debug, Clone, PartialEq)]
 pub struct BlueprintType {
     pub name: String,
-    pub overloads: Vec<String>,
+    pub overloads: Vec<SymbolId>,
 }
 
 impl Display for BlueprintType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Blueprint({}, {})", self.name, self.overloads.join(", "))
+        let overloads: Vec<&str> = self.overloads.iter().map(|o| o.as_str()).collect();
+        write!(f, "Blueprint({}, {})", self.name, overloads.join(", "))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Iface {
+    pub name: String,
+    pub methods: IndexMap<String, FunctionType>, // untagged params are any
+}
+
+impl Display for Iface {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct Atom {
     pub name: String,
-    pub fields: HashMap<String, AtomType>,
+    pub fields: IndexMap<String, AtomType>, // ordered, user atoms are constructed in field order
     pub generics: IndexMap<String, AtomType>,
+    pub len: Option<u32>, // the size of a List(T, N), its items are set but not pushed or popped
 }
 
 impl PartialEq for Atom {
     fn eq(&self, other: &Self) -> bool {
         self.name == other.name
             && self.fields == other.fields
-            && self.generics.keys().collect::<Vec<&String>>()
-                == other.generics.keys().collect::<Vec<&String>>()
+            && self.generics.iter().eq(other.generics.iter())
+            && self.len == other.len
     }
 }
 
 impl Atom {
     pub fn new(
         name: String,
-        fields: HashMap<String, AtomType>,
+        fields: IndexMap<String, AtomType>,
         generics: IndexMap<String, AtomType>,
     ) -> Atom {
         Atom {
             name,
             fields,
             generics,
+            len: None,
         }
     }
 
@@ -86,6 +102,14 @@ impl Atom {
         }
         this
     }
+
+    // the List(T, N) of a List(T), None removes the size
+    pub fn sized(&self, len: Option<u32>) -> Self {
+        Atom {
+            len,
+            ..self.clone()
+        }
+    }
 }
 
 // makes an atom easily
@@ -93,7 +117,7 @@ macro_rules! complex {
     ($name:expr, { $($field_name:expr => $field_type:expr),* }, { $($generic_name:expr),* }) => {
         Atom::new(
             $name.to_owned(),
-            HashMap::from([$(($field_name.to_owned(), AtomType { kind: $field_type, details: None})),*]),
+            IndexMap::from([$(($field_name.to_owned(), AtomType { kind: $field_type, details: None})),*]),
             IndexMap::from([$(($generic_name.to_owned(), AtomType { kind: AtomKind::Unknown, details: None})),*]),
         )
     };
@@ -107,6 +131,17 @@ lazy_static! {
     pub static ref Const: Atom = complex!("Const", {"T" => AtomKind::Unknown}, {"T"});
 }
 
+pub fn is_list(ty: &AtomType) -> bool {
+    matches!(&ty.kind, AtomKind::Atom(atom) if atom.name == List.name)
+}
+
+// built-in atoms can't be constructed or declared by the user
+pub fn is_builtin(atom: &Atom) -> bool {
+    [&*List, &*Str, &*Back, &*Const]
+        .iter()
+        .any(|builtin| builtin.name == atom.name)
+}
+
 impl Display for Atom {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let generics = if !self.generics.is_empty() {
@@ -120,6 +155,7 @@ impl Display for Atom {
                         gen.to_string()
                     }
                 })
+                .chain(self.len.map(|len| len.to_string()))
                 .collect();
             format!("({})", gen_str.join(", "))
         } else {
@@ -135,23 +171,33 @@ pub enum AtomKind {
     Atom(Atom),
     Function(FunctionType),
     Blueprint(BlueprintType),
-    Dynamic, // may be scrapped, says that type is only known at runtime
-    Unknown, // Unknown and no details means that expr type is unknown later on it should be replaced with Unknown(AtomType) where AtomType is an assumption and even later it is unwarped or converted to the Some type (may be replaced to be simpler)
-    Any,     // mainly used for mangling and Symbol.expected, means that symbol can be of Any type
+    Iface(Iface), // only valid as a type tag, constrains blueprint params to implementing types
+    Dynamic,      // may be scrapped, says that type is only known at runtime
+    Unknown,      // unspecified generic of an atom or a node that wasn't analyzed yet
+    Var(usize),   // type variable solved by analysis::infer, never left in analyzed nodes
+    Any, // mainly used for mangling and Symbol.expected, means that symbol can be of Any type
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum AtomDetails {
     Type,
-    Unknown(Box<AtomType>),
+    AliasType(String), // type declared by `type Name = T`
+    Alias(String),     // value of a type named by an alias, only changes how the type is displayed
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct AtomType {
     pub kind: AtomKind,
     pub details: Option<AtomDetails>,
 }
 
+// aliases are transparent, Grid is the same type as List(List(int))
+impl PartialEq for AtomType {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind && self.is_type() == other.is_type()
+    }
+}
+
 impl Display for AtomKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -161,23 +207,44 @@ impl Display for AtomKind {
             AtomKind::Atom(a) => write!(f, "{}", a),
             AtomKind::Blueprint(b) => write!(f, "{}", b),
             AtomKind::Function(fun) => write!(f, "{}", fun),
+            AtomKind::Iface(i) => write!(f, "{}", i),
             AtomKind::Unknown => write!(f, "Unknown"),
+            AtomKind::Var(var) => write!(f, "?{}", var),
         }
     }
 }
 
 impl Display for AtomType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.kind)
+        match &self.details {
+            Some(AtomDetails::Alias(name)) | Some(AtomDetails::AliasType(name)) => {
+                write!(f, "{}", name)
+            }
+            _ => write!(f, "{}", self.kind),
+        }
     }
 }
 
 impl AtomType {
     pub fn is_type(&self) -> bool {
-        self.details == Some(AtomDetails::Type)
+        matches!(
+            self.details,
+            Some(AtomDetails::Type) | Some(AtomDetails::AliasType(_))
+        )
     }
 
-    pub fn get(&self, name: &String) -> Option<&Self> {
+    // the type of values of this type, keeps the alias name the type was declared with
+    pub fn instance(&self) -> AtomType {
+        AtomType {
+            kind: self.kind.clone(),
+            details: match &self.details {
+                Some(AtomDetails::AliasType(name)) => Some(AtomDetails::Alias(name.clone())),
+                _ => None,
+            },
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Self> {
         match &self.kind {
             AtomKind::Atom(a) => a.fields.get(name),
 
@@ -185,6 +252,18 @@ impl AtomType {
         }
     }
 
+    // if the type still has type variables to be solved in it
+    pub fn has_vars(&self) -> bool {
+        match &self.kind {
+            AtomKind::Var(_) => true,
+            AtomKind::Atom(atom) => atom.generics.values().any(|gen| gen.has_vars()),
+            AtomKind::Function(func) => {
+                func.return_type.has_vars() || func.params.iter().any(|param| param.has_vars())
+            }
+            _ => false,
+        }
+    }
+
     pub fn generics(&self) -> i32 {
         if self.is_type() {
             match &self.kind {
@@ -197,69 +276,289 @@ impl AtomType {
     }
 }
 
-pub fn type_mangle(mut name: String, types: Vec<AtomType>) -> String {
-    let name = {
-        let idx = name.find('$');
-        if idx.is_some() {
-            let idx = idx.unwrap();
-            name.truncate(idx);
+// the name a type is known by in impls, generics are ignored so an impl for List covers every List(T)
+pub fn impl_name(ty: &AtomType) -> String {
+    match &ty.kind {
+        AtomKind::Atom(atom) => atom.name.clone(),
+        kind => kind.to_string(),
+    }
+}
+
+// name of the blueprint that implements an interface method for a type (List, push -> Listdotpush)
+pub fn method_name(ty: &AtomType, method: &str) -> String {
+    format!("{}dot{}", impl_name(ty), method)
+}
+
+// blueprints are built into one function per argument types, each named by a mangle of the blueprint name and the types
+// a mangle is a valid C identifier that can be read back with demangle:
+// at_<len><name> followed by the param types or v if there are none, blueprints declared in a
+// module are named module.name and mangled as at_M<len><module><len><name> so every module has
+// names of its own
+//
+// i int, f float, b bool, v void, d Dynamic, a any, u unknown, V<n> type var n
+// s str, A<len><name> atom followed by G<generics>E if it has any
+// I<len><name> interface, F<return><params>E function, B<len><name> blueprint
+//
+// fact(int) => at_4facti, sum(List(int)) => at_3sumA4ListGiE, shapes.sum(int) => at_M6shapes3sumi
+const MANGLE_PREFIX: &str = "at_";
+
+fn mangle_id(name: &str, mangle: &mut String) {
+    mangle.push_str(&name.len().to_string());
+    mangle.push_str(name);
+}
+
+// types are mangled by kind so aliases mangle as the type they name
+fn mangle_ty(ty: &AtomType, mangle: &mut String) {
+    match &ty.kind {
+        AtomKind::Basic(BasicType::Int) => mangle.push('i'),
+        AtomKind::Basic(BasicType::Float) => mangle.push('f'),
+        AtomKind::Basic(BasicType::Bool) => mangle.push('b'),
+        AtomKind::Basic(BasicType::Void) => mangle.push('v'),
+        AtomKind::Dynamic => mangle.push('d'),
+        AtomKind::Any => mangle.push('a'),
+        AtomKind::Atom(atom) if atom == &*Str => mangle.push('s'),
+
+        AtomKind::Atom(atom) => {
+            mangle.push('A');
+            mangle_id(&atom.name, mangle);
+
+            if !atom.generics.is_empty() {
+                mangle.push('G');
+                for gen in atom.generics.values() {
+                    mangle_ty(gen, mangle);
+                }
+                // the size ends with _ so an id after it doesn't run into it
+                if let Some(len) = atom.len {
+                    mangle.push_str(&format!("N{len}_"));
+                }
+                mangle.push('E');
+            }
         }
-        name
-    }; // removes any previous mangles from name
 
-    let mut mangle = String::new();
-    mangle.push_str(name.as_str());
-    mangle.push('$'); // type start
+        AtomKind::Iface(iface) => {
+            mangle.push('I');
+            mangle_id(&iface.name, mangle);
+        }
 
-    if types.len() == 0 {
-        mangle.push_str("empty");
+        AtomKind::Function(func) => {
+            mangle.push('F');
+            mangle_ty(&func.return_type, mangle);
+            for param in &func.params {
+                mangle_ty(param, mangle);
+            }
+            mangle.push('E');
+        }
+
+        AtomKind::Blueprint(blueprint) => {
+            mangle.push('B');
+            mangle_id(&blueprint.name, mangle);
+        }
+        AtomKind::Unknown => mangle.push('u'),
+        AtomKind::Var(var) => {
+            mangle.push('V');
+            mangle.push_str(&var.to_string());
+        }
     }
+}
 
-    let mut first = true; // if its the first time running loop (under)
+// the mangle of a single type, int => i, List(int) => A4ListGiE
+pub fn ty_code(ty: &AtomType) -> String {
+    let mut code = String::new();
+    mangle_ty(ty, &mut code);
+    code
+}
 
-    for type_n in types {
-        if !first {
-            mangle.push('_');
-        } else {
-            first = false
+// the name of a user atom in generated code, its type code behind the prefix so it can't collide
+// with a keyword, a runtime type or a blueprint, Point => at_A5Point
+pub fn atom_mangle(name: &str) -> String {
+    let mut mangle = String::from(MANGLE_PREFIX);
+    mangle.push('A');
+    mangle_id(name, &mut mangle);
+    mangle
+}
+
+pub fn type_mangle(name: String, types: Vec<AtomType>) -> String {
+    // removes any previous mangles from name
+    let name = match demangle_parts(&name) {
+        Some((name, _)) => name,
+        None => name,
+    };
+
+    let mut mangle = String::from(MANGLE_PREFIX);
+    match name.rsplit_once('.') {
+        Some((module, name)) => {
+            mangle.push('M');
+            mangle_id(module, &mut mangle);
+            mangle_id(name, &mut mangle);
         }
+        None => mangle_id(&name, &mut mangle),
+    }
 
-        mangle.push_str(
-            type_n
-                .to_string()
-                .replace("(", "__")
-                .replace(")", "__")
-                .replace(",", "_")
-                .as_str(),
-        );
+    if types.is_empty() {
+        mangle.push('v');
     }
 
-    return mangle;
+    for ty in &types {
+        mangle_ty(ty, &mut mangle);
+    }
+
+    mangle
 }
 
-pub fn mangle_types(mangle: String) -> Vec<String> {
-    let types = mangle.get(mangle.find('$').unwrap() + 1..).unwrap();
-    types.split('_').map(|s| s.to_string()).collect()
+struct Demangler<'a> {
+    mangle: &'a [u8],
+    pos: usize,
+}
+
+impl Demangler<'_> {
+    fn next(&mut self) -> Option<u8> {
+        let c = self.mangle.get(self.pos).copied();
+        self.pos += 1;
+        c
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.mangle.get(self.pos).copied()
+    }
+
+    fn id(&mut self) -> Option<String> {
+        let start = self.pos;
+        while self.peek()?.is_ascii_digit() {
+            self.pos += 1;
+        }
+
+        let len: usize = std::str::from_utf8(&self.mangle[start..self.pos])
+            .ok()?
+            .parse()
+            .ok()?;
+        let id = self.mangle.get(self.pos..self.pos + len)?;
+        self.pos += len;
+
+        String::from_utf8(id.to_vec()).ok()
+    }
+
+    // types until E
+    fn types_until_end(&mut self) -> Option<Vec<String>> {
+        let mut types = Vec::new();
+        while self.peek()? != b'E' {
+            types.push(self.ty()?);
+        }
+        self.pos += 1;
+        Some(types)
+    }
+
+    fn ty(&mut self) -> Option<String> {
+        let ty = match self.next()? {
+            b'i' => "int".to_string(),
+            b'f' => "float".to_string(),
+            b'b' => "bool".to_string(),
+            b'v' => "void".to_string(),
+            b'd' => "Dynamic".to_string(),
+            b'a' => "any".to_string(),
+            b'u' => "?".to_string(),
+            b'V' => {
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                    self.pos += 1;
+                }
+                format!(
+                    "?{}",
+                    std::str::from_utf8(&self.mangle[start..self.pos]).ok()?
+                )
+            }
+            b's' => "str".to_string(),
+            b'N' => {
+                let start = self.pos;
+                while self.next()? != b'_' {}
+                let len = std::str::from_utf8(&self.mangle[start..self.pos - 1]).ok()?;
+                len.parse::<u32>().ok()?.to_string()
+            }
+
+            b'A' => {
+                let name = self.id()?;
+                if self.peek() == Some(b'G') {
+                    self.pos += 1;
+                    format!("{name}({})", self.types_until_end()?.join(", "))
+                } else {
+                    name
+                }
+            }
+
+            b'I' => self.id()?,
+            b'B' => format!("Blueprint({})", demangle(&self.id()?)),
+            b'F' => {
+                let ret = self.ty()?;
+                format!("Fn({}) -> {ret}", self.types_until_end()?.join(", "))
+            }
+            _ => return None,
+        };
+
+        Some(ty)
+    }
+}
+
+// the blueprint name and param types of a mangle, None if it isn't one
+pub fn demangle_parts(mangle: &str) -> Option<(String, Vec<String>)> {
+    let mut demangler = Demangler {
+        mangle: mangle.strip_prefix(MANGLE_PREFIX)?.as_bytes(),
+        pos: 0,
+    };
+
+    let name = match demangler.peek()? {
+        b'M' => {
+            demangler.pos += 1;
+            let module = demangler.id()?;
+            format!("{module}.{}", demangler.id()?)
+        }
+        _ => demangler.id()?,
+    };
+    let mut types = Vec::new();
+
+    if demangler.peek() == Some(b'v') && demangler.mangle.len() == demangler.pos + 1 {
+        return Some((name, types));
+    }
+
+    while demangler.peek().is_some() {
+        types.push(demangler.ty()?);
+    }
+
+    if types.is_empty() {
+        return None;
+    }
+    Some((name, types))
+}
+
+// fact(int) from at_4facti, anything that isn't a mangle is returned as is
+pub fn demangle(mangle: &str) -> String {
+    match demangle_parts(mangle) {
+        Some((name, types)) => format!("{name}({})", types.join(", ")),
+        None => mangle.to_string(),
+    }
 }
 
 // from => [into]
 pub fn implicit_conversions(from: &AtomKind) -> Vec<AtomKind> {
     // AtomKind::Any conversions (anything convert to these)
     let mut results = vec![
-        AtomKind::Dynamic,
-        AtomKind::Atom(Str.clone()),
         // Const(T) (TODO! this is a bit of a hack, but it works for now (C backend const pointers))
         AtomKind::Atom(Const.spec(&[AtomType {
             kind: from.clone(),
             details: None,
         }])),
     ];
+    if boxable(from) {
+        results.push(AtomKind::Dynamic);
+    }
 
     // reset of conversions for each specific type
     results.append(&mut match from {
         AtomKind::Atom(ref atom) if atom == &*Const => vec![atom.generics[0].kind.clone()],
 
-        AtomKind::Basic(BasicType::Int) => vec![AtomKind::Basic(BasicType::Float)],
+        AtomKind::Basic(BasicType::Int) => vec![
+            AtomKind::Basic(BasicType::Float),
+            AtomKind::Atom(Str.clone()),
+        ],
+        AtomKind::Basic(BasicType::Float | BasicType::Bool) => vec![AtomKind::Atom(Str.clone())],
 
         AtomKind::Dynamic => vec![AtomKind::Any],
         _ => Vec::new(),
@@ -271,5 +570,73 @@ pub fn implicit_conversions(from: &AtomKind) -> Vec<AtomKind> {
 pub fn can_implicitly_convert(from: &AtomKind, to: &AtomKind) -> bool {
     let conversions = implicit_conversions(from);
 
-    conversions.contains(to) || conversions.contains(&AtomKind::Any)
+    conversions.contains(to) || (conversions.contains(&AtomKind::Any) && downcastable(to))
+}
+
+// values that can be boxed into a Dynamic, void and blueprints have no value and backend types
+// never leave the backend
+fn boxable(kind: &AtomKind) -> bool {
+    matches!(kind, AtomKind::Function(_)) || downcastable(kind)
+}
+
+// types a Dynamic can be checked and downcasted into
+fn downcastable(kind: &AtomKind) -> bool {
+    match kind {
+        AtomKind::Basic(basic) => *basic != BasicType::Void,
+        AtomKind::Atom(atom) => atom.name != Back.name && atom.name != Const.name,
+        _ => false,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Conversion {
+    Implicit, // inserted by the compiler whenever needed
+    Explicit, // always succeeds but may lose information, needs `as`
+    Fallible, // needs `as`, checked at runtime and fails with a runtime error
+}
+
+// explicit conversion table (`expr as T`), rows are from and columns are into
+//
+//          | int      | float    | bool     | str      | Dynamic
+// int      | -        | implicit | explicit | implicit | implicit
+// float    | explicit | -        | explicit | implicit | implicit
+// bool     | explicit | explicit | -        | implicit | implicit
+// str      | fallible | fallible | fallible | -        | implicit
+// Dynamic  | fallible | fallible | fallible | fallible | -
+//
+// float -> int truncates towards zero, int/float -> bool is `!= 0`, bool -> int/float is 0 or 1
+// str -> int/float parses the whole string, str -> bool accepts "true" and "false" only
+// T -> Dynamic boxes any value along with a runtime descriptor of T, void and blueprints have none
+// Dynamic -> T is a checked downcast, it fails if the value held isn't a T, lists and atoms can be downcasted into too
+// str <-> Back(str) and T <-> Const(T) are explicit backend conversions
+pub fn conversion(from: &AtomKind, into: &AtomKind) -> Option<Conversion> {
+    use BasicType::*;
+
+    if from == into {
+        return Some(Conversion::Implicit);
+    }
+
+    // a blueprint is a set of overloads, there is no single value to convert
+    if let AtomKind::Blueprint(_) = from {
+        return None;
+    }
+
+    if can_implicitly_convert(from, into) && from != &AtomKind::Dynamic {
+        return Some(Conversion::Implicit);
+    }
+
+    let is_str = |kind: &AtomKind| matches!(kind, AtomKind::Atom(atom) if atom == &*Str);
+    let is_back_str = |kind: &AtomKind| matches!(kind, AtomKind::Atom(atom) if atom.name == Back.name && is_str(&atom.generics[0].kind));
+
+    match (from, into) {
+        (AtomKind::Basic(Int | Float | Bool), AtomKind::Basic(Int | Float | Bool)) => {
+            Some(Conversion::Explicit)
+        }
+        (from, AtomKind::Basic(Int | Float | Bool)) if is_str(from) => Some(Conversion::Fallible),
+        (AtomKind::Dynamic, into) if downcastable(into) => Some(Conversion::Fallible),
+
+        (from, into) if is_str(from) && is_back_str(into) => Some(Conversion::Explicit),
+        (from, into) if is_back_str(from) && is_str(into) => Some(Conversion::Explicit),
+        _ => None,
+    }
 }